@@ -13,6 +13,19 @@ mod m20250906_000001_sync_user_base_role;
 mod m20250906_000002_allow_multiple_user_roles;
 mod m20250912_000001_add_user_search_indexes;
 mod m20250917_000001_add_person_permissions;
+mod m20251118_000001_create_oauth_tables;
+mod m20251118_000002_add_client_credentials_to_oauth_clients;
+mod m20251118_000003_add_token_families;
+mod m20251119_000001_create_user_mfa;
+mod m20251119_000002_create_webauthn_tables;
+mod m20251119_000003_add_account_lockout;
+mod m20251120_000001_add_email_change;
+mod m20251121_000001_add_rbac_permissions;
+mod m20251122_000001_add_grant_validity;
+mod m20251123_000001_create_organizations;
+mod m20251124_000001_create_audit_events;
+mod m20251125_000001_add_mfa_challenge_attempts;
+mod m20251125_000002_add_authorization_code_nonce;
 
 pub struct Migrator;
 
@@ -33,6 +46,19 @@ impl MigratorTrait for Migrator {
             Box::new(m20250906_000002_allow_multiple_user_roles::Migration),
             Box::new(m20250912_000001_add_user_search_indexes::Migration),
             Box::new(m20250917_000001_add_person_permissions::Migration),
+            Box::new(m20251118_000001_create_oauth_tables::Migration),
+            Box::new(m20251118_000002_add_client_credentials_to_oauth_clients::Migration),
+            Box::new(m20251118_000003_add_token_families::Migration),
+            Box::new(m20251119_000001_create_user_mfa::Migration),
+            Box::new(m20251119_000002_create_webauthn_tables::Migration),
+            Box::new(m20251119_000003_add_account_lockout::Migration),
+            Box::new(m20251120_000001_add_email_change::Migration),
+            Box::new(m20251121_000001_add_rbac_permissions::Migration),
+            Box::new(m20251122_000001_add_grant_validity::Migration),
+            Box::new(m20251123_000001_create_organizations::Migration),
+            Box::new(m20251124_000001_create_audit_events::Migration),
+            Box::new(m20251125_000001_add_mfa_challenge_attempts::Migration),
+            Box::new(m20251125_000002_add_authorization_code_nonce::Migration),
         ]
     }
 }
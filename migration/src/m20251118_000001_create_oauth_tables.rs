@@ -0,0 +1,207 @@
+use sea_orm_migration::prelude::*;
+use ::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Ensure tables are created under auth
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "SET search_path TO auth, public;".to_string(),
+        ))
+        .await?;
+
+        // oauth_clients: registered applications allowed to use /oauth/*
+        manager
+            .create_table(
+                Table::create()
+                    .table(OauthClients::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OauthClients::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(
+                        ColumnDef::new(OauthClients::ClientId)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(OauthClients::Name).string().not_null())
+                    // NULL for public clients (SPAs), argon2 hash for confidential ones
+                    .col(ColumnDef::new(OauthClients::ClientSecretHash).string())
+                    .col(
+                        ColumnDef::new(OauthClients::RedirectUris)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    .col(
+                        ColumnDef::new(OauthClients::IsActive)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        ColumnDef::new(OauthClients::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(OauthClients::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // authorization_codes: short-lived, single-use codes bound to a PKCE challenge
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuthorizationCodes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AuthorizationCodes::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(
+                        ColumnDef::new(AuthorizationCodes::CodeHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(AuthorizationCodes::ClientId).uuid().not_null())
+                    .col(ColumnDef::new(AuthorizationCodes::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(AuthorizationCodes::RedirectUri)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AuthorizationCodes::CodeChallenge)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AuthorizationCodes::CodeChallengeMethod)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AuthorizationCodes::Scope).text())
+                    .col(
+                        ColumnDef::new(AuthorizationCodes::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AuthorizationCodes::IsUsed)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(AuthorizationCodes::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_authorization_codes_client")
+                            .from(AuthorizationCodes::Table, AuthorizationCodes::ClientId)
+                            .to(OauthClients::Table, OauthClients::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_authorization_codes_user")
+                            .from(AuthorizationCodes::Table, AuthorizationCodes::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_authorization_codes_user_id")
+                    .table(AuthorizationCodes::Table)
+                    .col(AuthorizationCodes::UserId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "SET search_path TO auth, public;".to_string(),
+        ))
+        .await?;
+
+        manager
+            .drop_table(Table::drop().table(AuthorizationCodes::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(OauthClients::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum OauthClients {
+    Table,
+    Id,
+    ClientId,
+    Name,
+    ClientSecretHash,
+    RedirectUris,
+    IsActive,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum AuthorizationCodes {
+    Table,
+    Id,
+    CodeHash,
+    ClientId,
+    UserId,
+    RedirectUri,
+    CodeChallenge,
+    CodeChallengeMethod,
+    Scope,
+    ExpiresAt,
+    IsUsed,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Grants a client may use and the permissions a client_credentials token may carry
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new("oauth_clients").into_iden()))
+                    .add_column(
+                        ColumnDef::new(OauthClients::GrantTypes)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust(r#"'["authorization_code"]'::jsonb"#)),
+                    )
+                    .add_column(
+                        ColumnDef::new(OauthClients::AllowedPermissions)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new("oauth_clients").into_iden()))
+                    .drop_column(OauthClients::GrantTypes)
+                    .drop_column(OauthClients::AllowedPermissions)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OauthClients {
+    GrantTypes,
+    AllowedPermissions,
+}
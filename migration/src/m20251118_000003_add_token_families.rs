@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Refresh token rotation chains: parent_id is the token it replaced,
+        // family_id the first token of the chain (its own id for a fresh login)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new("tokens").into_iden()))
+                    .add_column(ColumnDef::new(Tokens::ParentId).uuid().null())
+                    .add_column(ColumnDef::new(Tokens::FamilyId).uuid().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_tokens_family_id")
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new("tokens").into_iden()))
+                    .col(Tokens::FamilyId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_tokens_family_id")
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new("tokens").into_iden()))
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new("tokens").into_iden()))
+                    .drop_column(Tokens::ParentId)
+                    .drop_column(Tokens::FamilyId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Tokens {
+    ParentId,
+    FamilyId,
+}
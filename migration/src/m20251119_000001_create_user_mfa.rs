@@ -0,0 +1,111 @@
+use sea_orm_migration::prelude::*;
+use ::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Ensure tables are created under auth
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "SET search_path TO auth, public;".to_string(),
+        ))
+        .await?;
+
+        // user_mfa: one TOTP enrolment per user, pending until the first code is verified
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserMfa::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserMfa::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(
+                        ColumnDef::new(UserMfa::UserId)
+                            .uuid()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(UserMfa::TotpSecret).string().not_null())
+                    .col(
+                        ColumnDef::new(UserMfa::Enabled)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(ColumnDef::new(UserMfa::EnabledAt).timestamp_with_time_zone())
+                    // Last accepted 30s time step, a code is never accepted twice
+                    .col(ColumnDef::new(UserMfa::LastUsedStep).big_integer())
+                    // SHA-256 hashes of the unused recovery codes
+                    .col(
+                        ColumnDef::new(UserMfa::RecoveryCodes)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    .col(
+                        ColumnDef::new(UserMfa::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(UserMfa::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_mfa_user")
+                            .from(UserMfa::Table, UserMfa::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "SET search_path TO auth, public;".to_string(),
+        ))
+        .await?;
+
+        manager
+            .drop_table(Table::drop().table(UserMfa::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserMfa {
+    Table,
+    Id,
+    UserId,
+    TotpSecret,
+    Enabled,
+    EnabledAt,
+    LastUsedStep,
+    RecoveryCodes,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
@@ -0,0 +1,184 @@
+use sea_orm_migration::prelude::*;
+use ::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Ensure tables are created under auth
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "SET search_path TO auth, public;".to_string(),
+        ))
+        .await?;
+
+        // webauthn_credentials: passkeys registered by users
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebauthnCredentials::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(ColumnDef::new(WebauthnCredentials::UserId).uuid().not_null())
+                    // base64url credential id as sent by the authenticator
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::CredentialId)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::PublicKey)
+                            .json_binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::SignCount)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::Transports)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'[]'::jsonb")),
+                    )
+                    // Full credential record as the webauthn library stores it
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::Passkey)
+                            .json_binary()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(WebauthnCredentials::Name).string())
+                    .col(
+                        ColumnDef::new(WebauthnCredentials::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(WebauthnCredentials::LastUsedAt).timestamp_with_time_zone())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_webauthn_credentials_user")
+                            .from(WebauthnCredentials::Table, WebauthnCredentials::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_webauthn_credentials_user_id")
+                    .table(WebauthnCredentials::Table)
+                    .col(WebauthnCredentials::UserId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        // webauthn_ceremonies: server state between the start and finish of a ceremony
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebauthnCeremonies::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WebauthnCeremonies::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(ColumnDef::new(WebauthnCeremonies::UserId).uuid().not_null())
+                    // 'registration' or 'authentication'
+                    .col(ColumnDef::new(WebauthnCeremonies::Kind).string().not_null())
+                    .col(ColumnDef::new(WebauthnCeremonies::State).json_binary().not_null())
+                    .col(
+                        ColumnDef::new(WebauthnCeremonies::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(WebauthnCeremonies::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_webauthn_ceremonies_user")
+                            .from(WebauthnCeremonies::Table, WebauthnCeremonies::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "SET search_path TO auth, public;".to_string(),
+        ))
+        .await?;
+
+        manager
+            .drop_table(Table::drop().table(WebauthnCeremonies::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(WebauthnCredentials::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum WebauthnCredentials {
+    Table,
+    Id,
+    UserId,
+    CredentialId,
+    PublicKey,
+    SignCount,
+    Transports,
+    Passkey,
+    Name,
+    CreatedAt,
+    LastUsedAt,
+}
+
+#[derive(DeriveIden)]
+enum WebauthnCeremonies {
+    Table,
+    Id,
+    UserId,
+    Kind,
+    State,
+    ExpiresAt,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
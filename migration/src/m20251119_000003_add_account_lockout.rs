@@ -0,0 +1,56 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Failed password attempts since the last successful login or lock,
+        // lockout_count drives the exponential lock duration
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new("users").into_iden()))
+                    .add_column(
+                        ColumnDef::new(Users::FailedLoginAttempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(
+                        ColumnDef::new(Users::LockoutCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(
+                        ColumnDef::new(Users::LockedUntil)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new("users").into_iden()))
+                    .drop_column(Users::FailedLoginAttempts)
+                    .drop_column(Users::LockoutCount)
+                    .drop_column(Users::LockedUntil)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    FailedLoginAttempts,
+    LockoutCount,
+    LockedUntil,
+}
@@ -0,0 +1,46 @@
+use crate::sea_orm::Statement;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Single-use token mailed to the new address before an email change is applied
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                manager.get_database_backend(),
+                "ALTER TYPE public.token_type ADD VALUE IF NOT EXISTS 'EMAIL_CHANGE';".to_string(),
+            ))
+            .await?;
+
+        // Address waiting for confirmation, users.email only changes once it is confirmed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new("users").into_iden()))
+                    .add_column(ColumnDef::new(Users::PendingEmail).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Postgres cannot drop an enum value, EMAIL_CHANGE stays in token_type
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new("users").into_iden()))
+                    .drop_column(Users::PendingEmail)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    PendingEmail,
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+use ::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "SET search_path TO auth, public;".to_string(),
+        ))
+        .await?;
+
+        // Permissions guarding the RBAC administration API
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            r#"
+            INSERT INTO auth.permissions (code, description)
+            VALUES
+              ('rbac.read', 'Read roles, permissions and assignments'),
+              ('rbac.write', 'Manage roles, permissions and assignments')
+            ON CONFLICT (code) DO NOTHING;
+            "#.to_string(),
+        ))
+        .await?;
+
+        // Only ADMIN gets them
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            r#"
+            INSERT INTO auth.role_permissions (role_id, permission_id)
+            SELECT r.id, p.id
+            FROM auth.roles r
+            JOIN auth.permissions p ON p.code IN ('rbac.read', 'rbac.write')
+            WHERE r.code = 'ADMIN'
+            ON CONFLICT DO NOTHING;
+            "#.to_string(),
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Mappings go with the permissions through ON DELETE CASCADE
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            r#"
+            DELETE FROM auth.permissions p
+            WHERE p.code IN ('rbac.read', 'rbac.write');
+            "#.to_string(),
+        ))
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Optional validity window of a role assignment or override, NULL means unbounded
+        for table in ["user_roles", "user_permission_overrides"] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new(table).into_iden()))
+                        .add_column(
+                            ColumnDef::new(Grants::ValidFrom)
+                                .timestamp_with_time_zone()
+                                .null(),
+                        )
+                        .add_column(
+                            ColumnDef::new(Grants::ValidUntil)
+                                .timestamp_with_time_zone()
+                                .null(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // The sweeper looks up expired rows
+            manager
+                .create_index(
+                    Index::create()
+                        .name(format!("idx_{table}_valid_until"))
+                        .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new(table).into_iden()))
+                        .col(Grants::ValidUntil)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in ["user_roles", "user_permission_overrides"] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), Alias::new(table).into_iden()))
+                        .drop_column(Grants::ValidFrom)
+                        .drop_column(Grants::ValidUntil)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Grants {
+    ValidFrom,
+    ValidUntil,
+}
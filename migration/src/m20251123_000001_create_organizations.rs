@@ -0,0 +1,290 @@
+use sea_orm_migration::prelude::*;
+use ::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Ensure tables are created under auth
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "SET search_path TO auth, public;".to_string(),
+        ))
+        .await?;
+
+        // organizations: tenants such as a hospital or a clinic
+        manager
+            .create_table(
+                Table::create()
+                    .table(Organizations::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Organizations::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(
+                        ColumnDef::new(Organizations::Slug)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Organizations::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(Organizations::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(Organizations::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // organization_members: users belonging to an organization
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrganizationMembers::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrganizationMembers::OrganizationId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(OrganizationMembers::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(OrganizationMembers::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(OrganizationMembers::OrganizationId)
+                            .col(OrganizationMembers::UserId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_organization_members_organization")
+                            .from(OrganizationMembers::Table, OrganizationMembers::OrganizationId)
+                            .to(Organizations::Table, Organizations::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_organization_members_user")
+                            .from(OrganizationMembers::Table, OrganizationMembers::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_organization_members_user_id")
+                    .table(OrganizationMembers::Table)
+                    .col(OrganizationMembers::UserId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // organization_member_roles: roles a member holds inside one organization only
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrganizationMemberRoles::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrganizationMemberRoles::OrganizationId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OrganizationMemberRoles::UserId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OrganizationMemberRoles::RoleId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(OrganizationMemberRoles::OrganizationId)
+                            .col(OrganizationMemberRoles::UserId)
+                            .col(OrganizationMemberRoles::RoleId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_organization_member_roles_member")
+                            .from_tbl(OrganizationMemberRoles::Table)
+                            .from_col(OrganizationMemberRoles::OrganizationId)
+                            .from_col(OrganizationMemberRoles::UserId)
+                            .to_tbl(OrganizationMembers::Table)
+                            .to_col(OrganizationMembers::OrganizationId)
+                            .to_col(OrganizationMembers::UserId)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_organization_member_roles_role")
+                            .from(OrganizationMemberRoles::Table, OrganizationMemberRoles::RoleId)
+                            .to(Roles::Table, Roles::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Organization the session's access tokens are issued for, kept across refreshes
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Sessions::Table)
+                    .add_column(
+                        ColumnDef::new(Sessions::ActiveOrganizationId)
+                            .uuid()
+                            .null(),
+                    )
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_sessions_active_organization")
+                            .from_tbl(Sessions::Table)
+                            .from_col(Sessions::ActiveOrganizationId)
+                            .to_tbl(Organizations::Table)
+                            .to_col(Organizations::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Permissions guarding organization administration, ADMIN only
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            r#"
+            INSERT INTO auth.permissions (code, description)
+            VALUES
+              ('org.read', 'Read organizations and their members'),
+              ('org.write', 'Manage organizations, members and member roles')
+            ON CONFLICT (code) DO NOTHING;
+            "#.to_string(),
+        ))
+        .await?;
+
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            r#"
+            INSERT INTO auth.role_permissions (role_id, permission_id)
+            SELECT r.id, p.id
+            FROM auth.roles r
+            JOIN auth.permissions p ON p.code IN ('org.read', 'org.write')
+            WHERE r.code = 'ADMIN'
+            ON CONFLICT DO NOTHING;
+            "#.to_string(),
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "SET search_path TO auth, public;".to_string(),
+        ))
+        .await?;
+
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            r#"
+            DELETE FROM auth.permissions p
+            WHERE p.code IN ('org.read', 'org.write');
+            "#.to_string(),
+        ))
+        .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Sessions::Table)
+                    .drop_foreign_key(Alias::new("fk_sessions_active_organization"))
+                    .drop_column(Sessions::ActiveOrganizationId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(OrganizationMemberRoles::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(OrganizationMembers::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Organizations::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Organizations {
+    Table,
+    Id,
+    Slug,
+    Name,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum OrganizationMembers {
+    Table,
+    OrganizationId,
+    UserId,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum OrganizationMemberRoles {
+    Table,
+    OrganizationId,
+    UserId,
+    RoleId,
+}
+
+#[derive(DeriveIden)]
+enum Sessions {
+    Table,
+    ActiveOrganizationId,
+}
+
+#[derive(DeriveIden)]
+enum Roles {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
@@ -0,0 +1,178 @@
+use sea_orm_migration::prelude::*;
+use ::sea_orm::Statement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Ensure tables are created under auth
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "SET search_path TO auth, public;".to_string(),
+        ))
+        .await?;
+
+        // audit_events: security history. No foreign keys, events outlive the users they name
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditEvents::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AuditEvents::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(
+                        ColumnDef::new(AuditEvents::OccurredAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(AuditEvents::EventType).string_len(64).not_null())
+                    .col(ColumnDef::new(AuditEvents::Outcome).string_len(16).not_null())
+                    .col(ColumnDef::new(AuditEvents::ActorId).uuid().null())
+                    .col(ColumnDef::new(AuditEvents::TargetUserId).uuid().null())
+                    .col(ColumnDef::new(AuditEvents::IpAddress).string().null())
+                    .col(ColumnDef::new(AuditEvents::UserAgent).text().null())
+                    .col(
+                        ColumnDef::new(AuditEvents::Metadata)
+                            .json_binary()
+                            .not_null()
+                            .default(Expr::cust("'{}'::jsonb")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Newest first with `id` as tie-breaker, the order of the admin query API
+        for sql in [
+            "CREATE INDEX IF NOT EXISTS idx_audit_events_occurred_at ON auth.audit_events (occurred_at DESC, id DESC);",
+            "CREATE INDEX IF NOT EXISTS idx_audit_events_actor_id ON auth.audit_events (actor_id, occurred_at DESC);",
+            "CREATE INDEX IF NOT EXISTS idx_audit_events_target_user_id ON auth.audit_events (target_user_id, occurred_at DESC);",
+            "CREATE INDEX IF NOT EXISTS idx_audit_events_event_type ON auth.audit_events (event_type, occurred_at DESC);",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_string(),
+            ))
+            .await?;
+        }
+
+        // Append-only: rows can be inserted, never changed or removed
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            r#"
+            CREATE OR REPLACE FUNCTION auth.reject_audit_event_change()
+            RETURNS trigger
+            LANGUAGE plpgsql
+            AS $$
+            BEGIN
+              RAISE EXCEPTION 'audit_events is append-only, % is not allowed', TG_OP;
+            END;
+            $$;
+            "#.to_string(),
+        ))
+        .await?;
+
+        for sql in [
+            "DROP TRIGGER IF EXISTS trg_audit_events_append_only ON auth.audit_events;",
+            r#"
+            CREATE TRIGGER trg_audit_events_append_only
+            BEFORE UPDATE OR DELETE ON auth.audit_events
+            FOR EACH ROW EXECUTE FUNCTION auth.reject_audit_event_change();
+            "#,
+            "DROP TRIGGER IF EXISTS trg_audit_events_no_truncate ON auth.audit_events;",
+            r#"
+            CREATE TRIGGER trg_audit_events_no_truncate
+            BEFORE TRUNCATE ON auth.audit_events
+            FOR EACH STATEMENT EXECUTE FUNCTION auth.reject_audit_event_change();
+            "#,
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_string(),
+            ))
+            .await?;
+        }
+
+        // Permission guarding the audit query API, ADMIN only
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            r#"
+            INSERT INTO auth.permissions (code, description)
+            VALUES ('audit.read', 'Read the security audit log')
+            ON CONFLICT (code) DO NOTHING;
+            "#.to_string(),
+        ))
+        .await?;
+
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            r#"
+            INSERT INTO auth.role_permissions (role_id, permission_id)
+            SELECT r.id, p.id
+            FROM auth.roles r
+            JOIN auth.permissions p ON p.code = 'audit.read'
+            WHERE r.code = 'ADMIN'
+            ON CONFLICT DO NOTHING;
+            "#.to_string(),
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            r#"
+            DELETE FROM auth.permissions p
+            WHERE p.code = 'audit.read';
+            "#.to_string(),
+        ))
+        .await?;
+
+        // Dropping the table drops its triggers
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(TableRef::SchemaTable(
+                        Alias::new("auth").into_iden(),
+                        AuditEvents::Table.into_iden(),
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "DROP FUNCTION IF EXISTS auth.reject_audit_event_change();".to_string(),
+        ))
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AuditEvents {
+    Table,
+    Id,
+    OccurredAt,
+    EventType,
+    Outcome,
+    ActorId,
+    TargetUserId,
+    IpAddress,
+    UserAgent,
+    Metadata,
+}
@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The latest MFA challenge of the user and the codes tried against it
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), UserMfa::Table.into_iden()))
+                    .add_column(ColumnDef::new(UserMfa::ChallengeJti).uuid().null())
+                    .add_column(
+                        ColumnDef::new(UserMfa::ChallengeAttempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TableRef::SchemaTable(Alias::new("auth").into_iden(), UserMfa::Table.into_iden()))
+                    .drop_column(UserMfa::ChallengeJti)
+                    .drop_column(UserMfa::ChallengeAttempts)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserMfa {
+    Table,
+    ChallengeJti,
+    ChallengeAttempts,
+}
@@ -0,0 +1,21 @@
+use super::services::AuditService;
+use crate::components::auth::functions::AuthUser;
+use crate::entity::audit_events::AuditEventsQuery;
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::prepared_response::check_response_ok_or_return_error;
+use actix_web::{get, web, HttpResponse};
+
+/// `?user_id=&event_type=&outcome=&from=&until=&cursor=&limit=`, needs `audit.read`
+#[get("/admin/audit-events")]
+pub async fn list_audit_events(
+    auth: AuthUser,
+    query: web::Query<AuditEventsQuery>,
+    service: web::Data<AuditService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&["audit.read"])?;
+    check_response_ok_or_return_error(service.list(query.into_inner()).await)
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(list_audit_events);
+}
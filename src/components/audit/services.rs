@@ -0,0 +1,208 @@
+use crate::components::auth::functions::TokenDetails;
+use crate::entity::audit_events::{ActiveModel, AuditEventPage, AuditEventsQuery, Column, Entity};
+use crate::entity::enums::{AuditEventType, AuditOutcome};
+use crate::entity::sessions::ClientInfo;
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait, NotSet, QueryFilter,
+    QueryOrder, QuerySelect, Set,
+};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+const DEFAULT_PAGE_SIZE: u64 = 50;
+const MAX_PAGE_SIZE: u64 = 200;
+
+/// An event to record with `AuditService::record`
+#[derive(Debug, Clone)]
+pub struct NewAuditEvent {
+    pub event_type: AuditEventType,
+    pub outcome: AuditOutcome,
+    pub actor_id: Option<Uuid>,
+    pub target_user_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub metadata: Value,
+}
+
+impl NewAuditEvent {
+    pub fn new(event_type: AuditEventType, outcome: AuditOutcome) -> Self {
+        NewAuditEvent {
+            event_type,
+            outcome,
+            actor_id: None,
+            target_user_id: None,
+            ip_address: None,
+            user_agent: None,
+            metadata: json!({}),
+        }
+    }
+
+    pub fn success(event_type: AuditEventType) -> Self {
+        Self::new(event_type, AuditOutcome::Success)
+    }
+
+    pub fn failure(event_type: AuditEventType) -> Self {
+        Self::new(event_type, AuditOutcome::Failure)
+    }
+
+    pub fn with_actor(mut self, actor_id: Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn with_target(mut self, target_user_id: Uuid) -> Self {
+        self.target_user_id = Some(target_user_id);
+        self
+    }
+
+    /// A user acting on their own account
+    pub fn with_user(self, user_id: Uuid) -> Self {
+        self.with_actor(user_id).with_target(user_id)
+    }
+
+    /// The token holder; service clients have no user id and are named in `metadata`
+    pub fn with_caller(self, caller: &TokenDetails) -> Self {
+        match caller.subject.user_id() {
+            Some(user_id) => self.with_actor(user_id),
+            None => self.with_metadata(json!({ "client_id": caller.subject.sub() })),
+        }
+    }
+
+    pub fn with_client(mut self, client: &ClientInfo) -> Self {
+        self.user_agent = client.user_agent.clone();
+        self.with_ip_address(&client.ip_address)
+    }
+
+    /// For requests where only the address is known
+    pub fn with_ip_address(mut self, ip_address: &str) -> Self {
+        self.ip_address = Some(ip_address.to_string());
+        self
+    }
+
+    /// Keys of `metadata` are added to those already set
+    pub fn with_metadata(mut self, metadata: Value) -> Self {
+        match (self.metadata.as_object_mut(), metadata) {
+            (Some(existing), Value::Object(added)) => existing.extend(added),
+            (_, metadata) => self.metadata = metadata,
+        }
+        self
+    }
+}
+
+#[derive(Clone)]
+pub struct AuditService {
+    conn: DatabaseConnection,
+}
+
+impl AuditService {
+    pub fn new(conn: &DatabaseConnection) -> Self {
+        Self { conn: conn.clone() }
+    }
+
+    /// Best effort: a failed write is logged and never fails the request being audited.
+    /// Pass the connection rather than a transaction, so failures that roll back stay recorded.
+    pub async fn record<C: ConnectionTrait>(event: NewAuditEvent, conn: &C) {
+        let result = Entity::insert(ActiveModel {
+            id: Set(Uuid::new_v4()),
+            occurred_at: NotSet,
+            event_type: Set(event.event_type),
+            outcome: Set(event.outcome),
+            actor_id: Set(event.actor_id),
+            target_user_id: Set(event.target_user_id),
+            ip_address: Set(event.ip_address),
+            user_agent: Set(event.user_agent),
+            metadata: Set(event.metadata),
+        })
+        .exec_without_returning(conn)
+        .await;
+
+        if let Err(e) = result {
+            log::error!("Audit event {:?} error: {:?}", event.event_type, e);
+        }
+    }
+
+    /// Newest first, `occurred_at` then `id` so pages neither skip nor repeat events
+    pub async fn list(&self, query: AuditEventsQuery) -> Result<AuditEventPage, CustomError> {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let mut condition = Condition::all();
+        if let Some(user_id) = query.user_id {
+            condition = condition.add(
+                Condition::any()
+                    .add(Column::ActorId.eq(user_id))
+                    .add(Column::TargetUserId.eq(user_id)),
+            );
+        }
+        if let Some(event_type) = query.event_type {
+            condition = condition.add(Column::EventType.eq(event_type));
+        }
+        if let Some(outcome) = query.outcome {
+            condition = condition.add(Column::Outcome.eq(outcome));
+        }
+        if let Some(from) = query.from {
+            condition = condition.add(Column::OccurredAt.gte(from));
+        }
+        if let Some(until) = query.until {
+            condition = condition.add(Column::OccurredAt.lt(until));
+        }
+        if let Some(cursor) = query.cursor.as_deref() {
+            let (occurred_at, id) = decode_cursor(cursor)?;
+            condition = condition.add(
+                Condition::any().add(Column::OccurredAt.lt(occurred_at)).add(
+                    Condition::all()
+                        .add(Column::OccurredAt.eq(occurred_at))
+                        .add(Column::Id.lt(id)),
+                ),
+            );
+        }
+
+        let mut events = Entity::find()
+            .filter(condition)
+            .order_by_desc(Column::OccurredAt)
+            .order_by_desc(Column::Id)
+            .limit(limit + 1)
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        let next_cursor = if events.len() as u64 > limit {
+            events.truncate(limit as usize);
+            events
+                .last()
+                .map(|last| encode_cursor(last.occurred_at, last.id))
+        } else {
+            None
+        };
+
+        Ok(AuditEventPage {
+            events,
+            next_cursor,
+        })
+    }
+}
+
+/// Opaque to clients: the position of the last event of a page
+fn encode_cursor(occurred_at: DateTimeWithTimeZone, id: Uuid) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", occurred_at.timestamp_micros(), id))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(DateTimeWithTimeZone, Uuid), CustomError> {
+    let invalid = || CustomError::new(HttpCodeW::BadRequest, "Invalid cursor".to_string());
+    let decoded = URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(invalid)?;
+    let (micros, id) = decoded.split_once(':').ok_or_else(invalid)?;
+    let occurred_at = micros
+        .parse::<i64>()
+        .ok()
+        .and_then(chrono::DateTime::from_timestamp_micros)
+        .ok_or_else(invalid)?;
+    let id = Uuid::parse_str(id).map_err(|_| invalid())?;
+    Ok((occurred_at.fixed_offset(), id))
+}
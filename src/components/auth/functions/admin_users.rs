@@ -0,0 +1,221 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::config::ConfigService;
+use crate::components::mail_send::MailSendService;
+use crate::components::sessions::SessionsService;
+use crate::components::tokens::TokensService;
+use crate::components::users::enums::SearchValue;
+use crate::components::users::UsersService;
+use crate::entity::users::{
+    ActiveModel, AdminCreateUserRequestBody, AdminUserResponse, AuthRequestBody,
+    UpdateUserStatusRequestBody,
+};
+use crate::entity::{AuditEventType, UserStatus};
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use crate::http_response::HttpCodeW::InternalServerError;
+use crate::utils::helpers::{
+    generate_secure_token, hash_password, now_date_time_utc, validate_password,
+};
+use actix_web::dev::ConnectionInfo;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set, TransactionTrait};
+use serde_json::json;
+use uuid::Uuid;
+
+pub async fn admin_create_user_logic(
+    users_service: &UsersService,
+    conn: &DatabaseConnection,
+    admin_id: Uuid,
+    payload: AdminCreateUserRequestBody,
+    conn_info: ConnectionInfo,
+) -> Result<AdminUserResponse, CustomError> {
+    match users_service
+        .find("email", SearchValue::String(payload.email.clone()))
+        .await
+    {
+        Ok(_) => {
+            return Err(CustomError::new(
+                HttpCodeW::Conflict,
+                "User with this email already exists".to_string(),
+            ))
+        }
+        Err(e) if e.error_status_code == HttpCodeW::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    validate_password(&payload.password, &payload.email, Some(&payload.username))?;
+
+    let mut active_user = UsersService::create_payload(
+        AuthRequestBody {
+            email: payload.email,
+            username: Some(payload.username),
+            password: payload.password,
+            first_name: payload.first_name,
+            last_name: payload.last_name,
+        },
+        conn_info,
+    );
+    UsersService::add_details_login(
+        &mut active_user,
+        json!({
+            "timestamp": now_date_time_utc(),
+            "notes": "Created by administrator",
+            "created_by": admin_id,
+        }),
+    );
+    active_user.status = Set(UserStatus::Active);
+    active_user.email_verified = Set(true);
+
+    let created = active_user.insert(conn).await.map_err(|e| {
+        if e.to_string()
+            .contains("duplicate key value violates unique constraint")
+        {
+            CustomError::new(
+                HttpCodeW::Conflict,
+                "Email or username is already taken".to_string(),
+            )
+        } else {
+            CustomError::new(InternalServerError, format!("Error creating user: {e}"))
+        }
+    })?;
+
+    Ok(AdminUserResponse::from(created))
+}
+
+/// Suspending or deactivating an account signs it out everywhere, so live
+/// sessions agree with `can_login`.
+pub async fn update_user_status_logic(
+    users_service: &UsersService,
+    tokens_service: &TokensService,
+    conn: &DatabaseConnection,
+    admin_id: Uuid,
+    user_id: Uuid,
+    payload: UpdateUserStatusRequestBody,
+) -> Result<AdminUserResponse, CustomError> {
+    if payload.status == UserStatus::PendingVerification {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            "Status can only be set to Active, Inactive or Suspended".to_string(),
+        ));
+    }
+    if user_id == admin_id {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            "You cannot change the status of your own account".to_string(),
+        ));
+    }
+
+    let user = users_service.find("id", SearchValue::Uuid(user_id)).await?;
+    let previous_status = user.status.clone();
+    let signs_out = !payload.status.is_active();
+
+    let txn = conn.begin().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn begin error: {e}"))
+    })?;
+
+    let mut active_user: ActiveModel = user.into();
+    UsersService::add_details_login(
+        &mut active_user,
+        json!({
+            "timestamp": now_date_time_utc(),
+            "notes": "Status changed by administrator",
+            "previous_status": previous_status,
+            "status": payload.status,
+            "changed_by": admin_id,
+        }),
+    );
+    active_user.status = Set(payload.status);
+    let updated = active_user.update(&txn).await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Failed to update user: {e}"))
+    })?;
+
+    if signs_out {
+        TokensService::revoke_all_refresh_tokens_for_user(user_id, &txn).await?;
+        SessionsService::end_all_sessions_for_user(user_id, &txn).await?;
+    }
+
+    txn.commit().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn commit error: {e}"))
+    })?;
+    if signs_out {
+        tokens_service
+            .revoke_all_access_tokens_for_user(user_id, conn)
+            .await?;
+        AuditService::record(
+            NewAuditEvent::success(AuditEventType::TokenRevoke)
+                .with_actor(admin_id)
+                .with_target(user_id)
+                .with_metadata(json!({
+                    "reason": "Account status changed",
+                    "status": updated.status,
+                })),
+            conn,
+        )
+        .await;
+    }
+
+    Ok(AdminUserResponse::from(updated))
+}
+
+/// Replaces the password with a random one nobody knows, signs the user out
+/// everywhere and mails a reset link.
+pub async fn force_password_reset_logic(
+    users_service: &UsersService,
+    tokens_service: &TokensService,
+    mail_send_service: &MailSendService,
+    conn: &DatabaseConnection,
+    admin_id: Uuid,
+    user_id: Uuid,
+    service_config: &ConfigService,
+) -> Result<String, CustomError> {
+    let user = users_service.find("id", SearchValue::Uuid(user_id)).await?;
+    let email = user.email.clone();
+    let hashed = hash_password(generate_secure_token().as_str()).map_err(|e| {
+        CustomError::new(InternalServerError, format!("Failed to hash password: {e}"))
+    })?;
+
+    let txn = conn.begin().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn begin error: {e}"))
+    })?;
+
+    let mut active_user: ActiveModel = user.into();
+    UsersService::add_details_login(
+        &mut active_user,
+        json!({
+            "timestamp": now_date_time_utc(),
+            "notes": "Password reset forced by administrator",
+            "forced_by": admin_id,
+        }),
+    );
+    active_user.password_hash = Set(hashed);
+    active_user.update(&txn).await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Failed to update user: {e}"))
+    })?;
+
+    TokensService::revoke_all_refresh_tokens_for_user(user_id, &txn).await?;
+    SessionsService::end_all_sessions_for_user(user_id, &txn).await?;
+
+    txn.commit().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn commit error: {e}"))
+    })?;
+    tokens_service
+        .revoke_all_access_tokens_for_user(user_id, conn)
+        .await?;
+    AuditService::record(
+        NewAuditEvent::success(AuditEventType::PasswordReset)
+            .with_actor(admin_id)
+            .with_target(user_id)
+            .with_metadata(json!({ "reason": "Forced by administrator" })),
+        conn,
+    )
+    .await;
+
+    let (raw_token, _row) = tokens_service
+        .create_reset_password_token_for_user(user_id)
+        .await?;
+    if let Err(e) =
+        mail_send_service.send_forced_password_reset_mail(email, raw_token, service_config)
+    {
+        log::error!("Forced password reset mail error: {:?}", e);
+    }
+
+    Ok("Password reset forced, a reset link has been sent to the user".to_string())
+}
@@ -0,0 +1,114 @@
+use crate::components::auth::functions::{verify_jwt_token, TokenClaims, TokenDetails, KEY_RING};
+use crate::components::tokens::TokensService;
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use auth::permission_matching::has_permission;
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use actix_web::{web, FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use uuid::Uuid;
+
+/// Raw token from an `Authorization: Bearer <token>` header.
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Caller of a protected endpoint, taken from a verified, non-revoked bearer token.
+///
+/// Add it as a handler argument to require authentication (401 otherwise), then use
+/// `require_permissions` for routes that need more than a signed-in caller (403).
+/// `Option<AuthUser>` accepts anonymous callers.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub claims: TokenClaims,
+    pub details: TokenDetails,
+}
+
+impl AuthUser {
+    /// Verifies the bearer token and checks it against the access token denylist.
+    pub async fn authenticate(req: &HttpRequest) -> Result<Self, CustomError> {
+        let token = bearer_token(req).ok_or_else(|| {
+            CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Missing bearer token".to_string(),
+            )
+        })?;
+
+        let (claims, details) = verify_jwt_token(&KEY_RING, token).map_err(|_| {
+            CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Invalid or expired access token".to_string(),
+            )
+        })?;
+
+        let tokens_service = req.app_data::<web::Data<TokensService>>().ok_or_else(|| {
+            CustomError::new(
+                HttpCodeW::InternalServerError,
+                "TokensService is not registered".to_string(),
+            )
+        })?;
+        if tokens_service
+            .is_access_token_revoked(details.token_uuid)
+            .await?
+        {
+            return Err(CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Access token has been revoked".to_string(),
+            ));
+        }
+
+        Ok(Self { claims, details })
+    }
+
+    /// For endpoints acting on the signed-in user itself, client_credentials tokens get 403.
+    pub fn user_id(&self) -> Result<Uuid, CustomError> {
+        self.details.subject.user_id().ok_or_else(|| {
+            CustomError::new(
+                HttpCodeW::Forbidden,
+                "This endpoint requires a user access token".to_string(),
+            )
+        })
+    }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        has_permission(&self.claims.perms, permission)
+    }
+
+    /// Permission guard: every code in `permissions` must be granted to the token.
+    ///
+    /// ```rust
+    /// auth.require_permissions(&["user.read"])?;
+    /// ```
+    pub fn require_permissions(&self, permissions: &[&str]) -> Result<(), CustomError> {
+        let missing: Vec<&str> = permissions
+            .iter()
+            .copied()
+            .filter(|permission| !self.has_permission(permission))
+            .collect();
+
+        match missing.is_empty() {
+            true => Ok(()),
+            false => Err(CustomError::new(
+                HttpCodeW::Forbidden,
+                format!("Missing permission {}", missing.join(", ")),
+            )),
+        }
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Error = CustomError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move { AuthUser::authenticate(&req).await })
+    }
+}
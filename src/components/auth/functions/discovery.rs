@@ -0,0 +1,62 @@
+use crate::components::config::ConfigService;
+use serde::Serialize;
+
+/// OpenID Connect discovery document (OpenID Connect Discovery 1.0, section 3).
+#[derive(Debug, Serialize, Clone)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    pub userinfo_endpoint: String,
+    pub introspection_endpoint: String,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub scopes_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+pub fn openid_configuration(config: &ConfigService) -> OpenIdConfiguration {
+    let base_url = config.port_host.trim_end_matches('/');
+
+    OpenIdConfiguration {
+        issuer: config.jwt_issuer.clone(),
+        authorization_endpoint: format!("{base_url}/oauth/authorize"),
+        token_endpoint: format!("{base_url}/oauth/token"),
+        jwks_uri: format!("{base_url}/.well-known/jwks.json"),
+        userinfo_endpoint: format!("{base_url}/v1/auth/userinfo"),
+        introspection_endpoint: format!("{base_url}/v1/auth/introspect"),
+        response_types_supported: strings(&["code"]),
+        grant_types_supported: strings(&["authorization_code", "client_credentials"]),
+        code_challenge_methods_supported: strings(&["S256"]),
+        token_endpoint_auth_methods_supported: strings(&[
+            "none",
+            "client_secret_basic",
+            "client_secret_post",
+        ]),
+        subject_types_supported: strings(&["public"]),
+        id_token_signing_alg_values_supported: strings(&["RS256"]),
+        scopes_supported: strings(&["openid", "email", "profile"]),
+        claims_supported: strings(&[
+            "iss",
+            "aud",
+            "sub",
+            "exp",
+            "iat",
+            "nonce",
+            "email",
+            "email_verified",
+            "given_name",
+            "family_name",
+            "preferred_username",
+        ]),
+    }
+}
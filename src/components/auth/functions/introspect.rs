@@ -0,0 +1,117 @@
+use crate::components::auth::functions::{decode_jwt_claims, KEY_RING};
+use crate::components::tokens::TokensService;
+use crate::components::users::enums::SearchValue;
+use crate::components::users::UsersService;
+use crate::config_service;
+use crate::entity::tokens::IntrospectResponse;
+use crate::http_response::error_handler::CustomError;
+use sea_orm::DatabaseConnection;
+use uuid::Uuid;
+
+/// RFC 7662 introspection. Every token that cannot be used, for whatever reason,
+/// is reported as `{active: false}` without saying why.
+pub async fn introspect_logic(
+    tokens_service: &TokensService,
+    users_service: &UsersService,
+    conn: &DatabaseConnection,
+    token: &str,
+    token_type_hint: Option<&str>,
+) -> Result<IntrospectResponse, CustomError> {
+    // The hint only decides which lookup runs first (RFC 7662 section 2.1)
+    if token_type_hint == Some("refresh_token") {
+        if let Some(response) =
+            introspect_refresh_token(tokens_service, users_service, conn, token).await?
+        {
+            return Ok(response);
+        }
+        return Ok(introspect_access_token(tokens_service, token)
+            .await?
+            .unwrap_or_else(IntrospectResponse::inactive));
+    }
+
+    if let Some(response) = introspect_access_token(tokens_service, token).await? {
+        return Ok(response);
+    }
+    Ok(
+        introspect_refresh_token(tokens_service, users_service, conn, token)
+            .await?
+            .unwrap_or_else(IntrospectResponse::inactive),
+    )
+}
+
+async fn introspect_access_token(
+    tokens_service: &TokensService,
+    token: &str,
+) -> Result<Option<IntrospectResponse>, CustomError> {
+    let claims = match decode_jwt_claims(&KEY_RING, token) {
+        Ok(claims) if claims.subject().is_ok() => claims,
+        _ => return Ok(None),
+    };
+    let token_uuid = match Uuid::parse_str(&claims.token_uuid) {
+        Ok(token_uuid) => token_uuid,
+        Err(_) => return Ok(None),
+    };
+    if tokens_service.is_access_token_revoked(token_uuid).await? {
+        return Ok(Some(IntrospectResponse::inactive()));
+    }
+
+    Ok(Some(IntrospectResponse {
+        active: true,
+        scope: Some(claims.perms.join(" ")),
+        client_id: claims.client_id.clone(),
+        username: (!claims.email.is_empty()).then(|| claims.email.clone()),
+        token_type: Some("Bearer".to_string()),
+        exp: Some(claims.exp),
+        iat: Some(claims.iat),
+        nbf: Some(claims.nbf),
+        sub: Some(claims.sub),
+        aud: Some(claims.aud),
+        iss: Some(claims.iss),
+        jti: Some(claims.token_uuid),
+        perms: Some(claims.perms),
+        roles: Some(claims.roles),
+        email: (!claims.email.is_empty()).then_some(claims.email),
+        org_id: claims.org_id,
+        org_roles: (!claims.org_roles.is_empty()).then_some(claims.org_roles),
+        org_perms: (!claims.org_perms.is_empty()).then_some(claims.org_perms),
+    }))
+}
+
+/// `None` when the value is not a refresh token we issued; inactive when it is but can't be used.
+async fn introspect_refresh_token(
+    tokens_service: &TokensService,
+    users_service: &UsersService,
+    conn: &DatabaseConnection,
+    token: &str,
+) -> Result<Option<IntrospectResponse>, CustomError> {
+    let refresh = match tokens_service.find_refresh_by_raw(token, conn).await? {
+        Some(refresh) => refresh,
+        None => return Ok(None),
+    };
+    if !refresh.is_valid() {
+        return Ok(Some(IntrospectResponse::inactive()));
+    }
+
+    let user = match users_service
+        .find("id", SearchValue::Uuid(refresh.user_id))
+        .await
+    {
+        Ok(user) if user.can_login() => user,
+        _ => return Ok(Some(IntrospectResponse::inactive())),
+    };
+
+    let config = config_service();
+    Ok(Some(IntrospectResponse {
+        active: true,
+        username: Some(user.email.clone()),
+        token_type: Some("refresh_token".to_string()),
+        exp: Some(refresh.expires_at.timestamp()),
+        iat: Some(refresh.created_at.timestamp()),
+        sub: Some(user.id.to_string()),
+        aud: Some(config.jwt_audience),
+        iss: Some(config.jwt_issuer),
+        jti: Some(refresh.id.to_string()),
+        email: Some(user.email),
+        ..IntrospectResponse::default()
+    }))
+}
@@ -0,0 +1,107 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::{
+    compute_roles_and_permissions, generate_jwt_token, TokenSubject, KEY_RING,
+};
+use crate::components::sessions::SessionsService;
+use crate::components::tokens::TokensService;
+use crate::config_service;
+use crate::entity::enums::AuditEventType;
+use crate::entity::sessions::ClientInfo;
+use crate::entity::users::{AuthResponseBody, BodyToken, Model};
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use actix_web::cookie::{time, Cookie, SameSite};
+use sea_orm::{DatabaseConnection, TransactionTrait};
+
+/// Issues an access token and a new refresh token for an already authenticated user.
+/// Every sign-in method (password, OAuth code, ...) ends here.
+pub async fn issue_tokens_for_user(
+    conn: &DatabaseConnection,
+    tokens_service: &TokensService,
+    user: &Model,
+    client: &ClientInfo,
+) -> Result<AuthResponseBody, CustomError> {
+    let access = compute_roles_and_permissions(conn, user.id, None)
+        .await
+        .map_err(|e| {
+            CustomError::new(
+                HttpCodeW::InternalServerError,
+                format!("Failed to compute permissions: {e}"),
+            )
+        })?;
+
+    let token_details = generate_jwt_token(
+        TokenSubject::User(user.id),
+        config_service().access_token_max_age,
+        &KEY_RING,
+        access,
+        user.email.clone(),
+    )
+    .map_err(|e| {
+        log::error!("JWT generation error: {:?}", e);
+        CustomError::new(
+            HttpCodeW::InternalServerError,
+            "Failed to generate access token".to_string(),
+        )
+    })?;
+
+    // The refresh token, its session and the access token row exist together or not at all
+    let txn = conn.begin().await.map_err(|e| {
+        CustomError::new(
+            HttpCodeW::InternalServerError,
+            format!("Txn begin error: {e}"),
+        )
+    })?;
+    let (refresh_raw, refresh_row) = tokens_service
+        .create_refresh_token_for_user(user.id, config_service().refresh_token_max_age, &txn)
+        .await?;
+    SessionsService::upsert_for_refresh_token(&refresh_row, client, &txn).await?;
+    tokens_service
+        .record_access_token(
+            user.id,
+            token_details.token_uuid,
+            refresh_row.family(),
+            token_details.expires_in.unwrap_or_default(),
+            &txn,
+        )
+        .await?;
+    txn.commit().await.map_err(|e| {
+        CustomError::new(
+            HttpCodeW::InternalServerError,
+            format!("Txn commit error: {e}"),
+        )
+    })?;
+
+    AuditService::record(
+        NewAuditEvent::success(AuditEventType::Login)
+            .with_user(user.id)
+            .with_client(client),
+        conn,
+    )
+    .await;
+
+    Ok(AuthResponseBody {
+        body: BodyToken {
+            access_token: token_details.token.unwrap_or_default(),
+            username: user.username.clone(),
+        },
+        refresh_token: refresh_raw,
+    })
+}
+
+pub fn refresh_cookie(refresh_token: String) -> Cookie<'static> {
+    Cookie::build("refresh_token", refresh_token)
+        .path("/")
+        .max_age(time::Duration::days(config_service().refresh_token_max_age))
+        .same_site(SameSite::None)
+        .http_only(true)
+        .secure(true)
+        .finish()
+}
+
+/// Expired twin of `refresh_cookie`, the browser drops the stored token.
+pub fn clear_refresh_cookie() -> Cookie<'static> {
+    let mut cookie = refresh_cookie(String::new());
+    cookie.make_removal();
+    cookie
+}
@@ -0,0 +1,152 @@
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use once_cell::sync::Lazy;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPublicKey;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::components::config::ConfigService;
+use crate::config_service;
+
+/// Keys used to sign and verify access tokens, built once from the config.
+pub static KEY_RING: Lazy<KeyRing> = Lazy::new(|| {
+    KeyRing::from_config(&config_service())
+        .unwrap_or_else(|e| panic!("Invalid access token keys: {e}"))
+});
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(rename = "use")]
+    pub key_use: String,
+    pub alg: String,
+    pub kid: String,
+    pub n: String,
+    pub e: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+struct VerificationKey {
+    jwk: Jwk,
+    decoding_key: DecodingKey,
+}
+
+/// The active signing key plus every public key that is still accepted.
+///
+/// Rotation: move the current key pair into `ACCESS_TOKEN_VERIFICATION_KEYS`
+/// (as `kid:base64_public_pem`), set the new pair as the signing key, and drop
+/// the old entry once the tokens it signed have expired.
+pub struct KeyRing {
+    signing_kid: String,
+    encoding_key: EncodingKey,
+    keys: Vec<VerificationKey>,
+}
+
+impl KeyRing {
+    pub fn from_config(config: &ConfigService) -> Result<Self, String> {
+        let private_pem = decode_pem(&config.access_token_private_key)?;
+        let encoding_key = EncodingKey::from_rsa_pem(private_pem.as_bytes())
+            .map_err(|e| format!("private key: {e}"))?;
+
+        let signing_key = verification_key(
+            config.access_token_key_id.clone(),
+            &config.access_token_public_key,
+        )?;
+        let signing_kid = signing_key.jwk.kid.clone();
+
+        let mut keys = vec![signing_key];
+        for entry in config
+            .access_token_verification_keys
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            // Base64 never contains ':', so an optional `kid:` prefix is unambiguous
+            let key = match entry.split_once(':') {
+                Some((kid, public_key)) => verification_key(Some(kid.to_string()), public_key)?,
+                None => verification_key(None, entry)?,
+            };
+            if keys.iter().any(|k| k.jwk.kid == key.jwk.kid) {
+                return Err(format!("duplicate kid {}", key.jwk.kid));
+            }
+            keys.push(key);
+        }
+
+        Ok(Self {
+            signing_kid,
+            encoding_key,
+            keys,
+        })
+    }
+
+    pub fn signing_kid(&self) -> &str {
+        &self.signing_kid
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    /// Tokens issued before `kid` was introduced carry none and belong to the signing key.
+    pub fn decoding_key(&self, kid: Option<&str>) -> Option<&DecodingKey> {
+        let kid = kid.unwrap_or(&self.signing_kid);
+        self.keys
+            .iter()
+            .find(|k| k.jwk.kid == kid)
+            .map(|k| &k.decoding_key)
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().map(|k| k.jwk.clone()).collect(),
+        }
+    }
+}
+
+fn decode_pem(base64_pem: &str) -> Result<String, String> {
+    let bytes = STANDARD
+        .decode(base64_pem.trim())
+        .map_err(|e| format!("base64: {e}"))?;
+    String::from_utf8(bytes).map_err(|e| format!("utf8: {e}"))
+}
+
+fn verification_key(kid: Option<String>, base64_public_pem: &str) -> Result<VerificationKey, String> {
+    let pem = decode_pem(base64_public_pem)?;
+    let public_key = RsaPublicKey::from_public_key_pem(&pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(&pem))
+        .map_err(|e| format!("public key: {e}"))?;
+    let decoding_key =
+        DecodingKey::from_rsa_pem(pem.as_bytes()).map_err(|e| format!("public key: {e}"))?;
+
+    let n = URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be());
+    let e = URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be());
+    let kid = kid
+        .filter(|kid| !kid.is_empty())
+        .unwrap_or_else(|| thumbprint(&n, &e));
+
+    Ok(VerificationKey {
+        jwk: Jwk {
+            kty: "RSA".to_string(),
+            key_use: "sig".to_string(),
+            alg: "RS256".to_string(),
+            kid,
+            n,
+            e,
+        },
+        decoding_key,
+    })
+}
+
+/// RFC 7638 JWK thumbprint, used as the default `kid`.
+fn thumbprint(n: &str, e: &str) -> String {
+    let canonical = format!(r#"{{"e":"{e}","kty":"RSA","n":"{n}"}}"#);
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
@@ -1,32 +1,56 @@
-use crate::components::auth::functions::{compute_roles_and_permissions, generate_jwt_token};
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::{
+    generate_mfa_challenge, is_mfa_enabled, issue_tokens_for_user,
+};
 use crate::components::tokens::TokensService;
 use crate::components::users::enums::SearchValue;
 use crate::components::users::UsersService;
-use crate::config_service;
-use crate::entity::users::{ActiveModel, AuthRequestBody, AuthResponseBody, BodyToken};
+use crate::entity::enums::AuditEventType;
+use crate::entity::sessions::ClientInfo;
+use crate::entity::user_mfa::MfaChallengeResponse;
+use crate::entity::users::{ActiveModel, AuthRequestBody, AuthResponseBody};
 use crate::http_response::error_handler::CustomError;
 use crate::http_response::HttpCodeW;
-use actix_web::dev::ConnectionInfo;
 use sea_orm::{ActiveModelTrait, DatabaseConnection};
 use serde_json::json;
 use crate::utils::helpers::now_date_time_utc;
 
+/// A correct password either signs the user in or, with 2FA enabled, asks for a code.
+pub enum LoginOutcome {
+    Authenticated(AuthResponseBody),
+    MfaRequired(MfaChallengeResponse),
+}
+
 pub async fn login_logic(
     users_service: &UsersService,
     payload: AuthRequestBody,
-    conn_info: ConnectionInfo,
+    client: ClientInfo,
     conn: &DatabaseConnection,
     tokens_service: &TokensService,
-) -> Result<Result<Option<AuthResponseBody>, CustomError>, CustomError> {
-    let ip_address = conn_info
-        .realip_remote_addr()
-        .map(|addr| addr.to_string())
-        .unwrap_or_else(|| "unknown".to_string());
+) -> Result<Result<Option<LoginOutcome>, CustomError>, CustomError> {
+    let ip_address = client.ip_address.clone();
 
     let user = users_service
         .find("email", SearchValue::String(payload.email.to_string()))
         .await;
-    let user_model = user?;
+    let user_model = match user {
+        Ok(user_model) => user_model,
+        Err(e) => {
+            AuditService::record(
+                NewAuditEvent::failure(AuditEventType::Login)
+                    .with_client(&client)
+                    .with_metadata(json!({
+                        "method": "password",
+                        "email": payload.email,
+                        "reason": e.error_message,
+                    })),
+                conn,
+            )
+            .await;
+            return Err(e);
+        }
+    };
+    let user_id = user_model.id;
     let check_pass = users_service
         .check_credentials_and_email_verification(payload, &ip_address, user_model)
         .await
@@ -40,58 +64,40 @@ pub async fn login_logic(
                 format!("Invalid credentials, {:?}", e),
             )),
         });
+    if let Err(e) = &check_pass {
+        AuditService::record(
+            NewAuditEvent::failure(AuditEventType::Login)
+                .with_target(user_id)
+                .with_client(&client)
+                .with_metadata(json!({ "method": "password", "reason": e.error_message })),
+            conn,
+        )
+        .await;
+    }
     Ok(match check_pass {
         Ok(model) => {
+            let mfa_enabled = is_mfa_enabled(conn, user_id).await?;
             let mut active_model: ActiveModel = model;
             let new_login = json!({
                 "timestamp": now_date_time_utc(),
-                "notes": "User Logged",
+                "notes": if mfa_enabled { "Password verified, 2FA required" } else { "User Logged" },
                 "ip_address": ip_address,
             });
             UsersService::add_details_login(&mut active_model, new_login);
+            // With 2FA the counters are cleared by /auth/mfa/verify, so a known password
+            // cannot reset the lockout between guesses of the second factor
+            if !mfa_enabled {
+                UsersService::reset_failed_logins(&mut active_model);
+            }
             let update = active_model.update(conn).await;
             match update {
-                Ok(update_model) => {
-                    let (roles, perms) = match compute_roles_and_permissions(conn, update_model.id).await {
-                        Ok((r, p)) => (r, p),
-                        Err(e) => {
-                            return Err(CustomError::new(
-                                HttpCodeW::InternalServerError,
-                                format!("Failed to compute permissions: {e}"),
-                            ));
-                        }
-                    };
-                    let jwt_token = generate_jwt_token(
-                        update_model.id,
-                        config_service().access_token_max_age,
-                        config_service().access_token_private_key.to_owned(),
-                        perms,
-                        roles,
-                        update_model.email.clone(),
-                    );
-                    let (refresh_raw, _row) = tokens_service
-                        .create_refresh_token_for_user(
-                            update_model.id,
-                            config_service().refresh_token_max_age,
-                        )
-                        .await?;
-                    match jwt_token {
-                        Ok(token_details) => Ok(Some(AuthResponseBody {
-                            body: BodyToken {
-                                access_token: token_details.token.unwrap_or_default(),
-                                username: update_model.username.clone(),
-                            },
-                            refresh_token: refresh_raw,
-                        })),
-                        Err(e) => {
-                            println!("JWT generation error: {:?}", e);
-                            Err(CustomError::new(
-                                HttpCodeW::InternalServerError,
-                                "Failed to generate access token".to_string(),
-                            ))
-                        }
-                    }
-                }
+                // No refresh cookie until /auth/mfa/verify succeeds
+                Ok(_) if mfa_enabled => generate_mfa_challenge(conn, user_id)
+                    .await
+                    .map(|challenge| Some(LoginOutcome::MfaRequired(challenge))),
+                Ok(update_model) => issue_tokens_for_user(conn, tokens_service, &update_model, &client)
+                    .await
+                    .map(|tokens| Some(LoginOutcome::Authenticated(tokens))),
                 Err(_) => Err(CustomError::new(
                     HttpCodeW::InternalServerError,
                     "Failed to update user".to_string(),
@@ -0,0 +1,101 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::TokenDetails;
+use crate::components::sessions::SessionsService;
+use crate::components::tokens::TokensService;
+use crate::entity::enums::AuditEventType;
+use crate::entity::sessions::ClientInfo;
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use crate::http_response::HttpCodeW::InternalServerError;
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use serde_json::json;
+
+/// Ends the current session. Logging out twice, or without a cookie, is not an error.
+pub async fn logout_logic(
+    tokens_service: &TokensService,
+    conn: &DatabaseConnection,
+    refresh_token: Option<String>,
+    access_token: Option<TokenDetails>,
+    client: &ClientInfo,
+) -> Result<String, CustomError> {
+    let mut user_id = access_token
+        .as_ref()
+        .and_then(|details| details.subject.user_id());
+    if let Some(refresh_token) = refresh_token {
+        let txn = conn.begin().await.map_err(|e| {
+            CustomError::new(InternalServerError, format!("Txn begin error: {e}"))
+        })?;
+
+        if let Some(model) = tokens_service
+            .find_refresh_by_raw(&refresh_token, &txn)
+            .await?
+        {
+            user_id = Some(model.user_id);
+            SessionsService::end_session_for_family(model.family(), &txn).await?;
+            if !model.is_revoked {
+                TokensService::revoke_token(model, &txn).await?;
+            }
+        }
+
+        txn.commit().await.map_err(|e| {
+            CustomError::new(InternalServerError, format!("Txn commit error: {e}"))
+        })?;
+    }
+
+    // The access token that came with the request stops working now instead of at `exp`
+    if let Some(details) = access_token {
+        tokens_service
+            .revoke_access_token(details.token_uuid, conn)
+            .await?;
+    }
+
+    if let Some(user_id) = user_id {
+        AuditService::record(
+            NewAuditEvent::success(AuditEventType::TokenRevoke)
+                .with_user(user_id)
+                .with_client(client)
+                .with_metadata(json!({ "reason": "Logout" })),
+            conn,
+        )
+        .await;
+    }
+    Ok("Logged out".to_string())
+}
+
+/// Signs the user out on every device: all refresh tokens and outstanding access tokens.
+pub async fn logout_all_logic(
+    tokens_service: &TokensService,
+    conn: &DatabaseConnection,
+    access_token: TokenDetails,
+    client: &ClientInfo,
+) -> Result<String, CustomError> {
+    let user_id = access_token.subject.user_id().ok_or_else(|| {
+        CustomError::new(
+            HttpCodeW::Forbidden,
+            "Logout requires a user access token".to_string(),
+        )
+    })?;
+
+    let txn = conn.begin().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn begin error: {e}"))
+    })?;
+    TokensService::revoke_all_refresh_tokens_for_user(user_id, &txn).await?;
+    SessionsService::end_all_sessions_for_user(user_id, &txn).await?;
+    txn.commit().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn commit error: {e}"))
+    })?;
+
+    tokens_service
+        .revoke_all_access_tokens_for_user(user_id, conn)
+        .await?;
+
+    AuditService::record(
+        NewAuditEvent::success(AuditEventType::TokenRevoke)
+            .with_user(user_id)
+            .with_client(client)
+            .with_metadata(json!({ "reason": "Logout from all devices" })),
+        conn,
+    )
+    .await;
+    Ok("Logged out from all devices".to_string())
+}
@@ -0,0 +1,605 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::{hash_refresh, issue_tokens_for_user, KEY_RING};
+use crate::components::tokens::TokensService;
+use crate::components::users::enums::SearchValue;
+use crate::components::users::UsersService;
+use crate::config_service;
+use crate::entity::enums::AuditEventType;
+use crate::entity::sessions::ClientInfo;
+use crate::entity::user_mfa::{
+    ActiveModel, Column, Entity, MfaChallengeResponse, MfaVerifyRequestBody, Model,
+    RecoveryCodesResponse, TotpEnrollmentResponse,
+};
+use crate::entity::users;
+use crate::entity::users::AuthResponseBody;
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use crate::utils::helpers::now_date_time_utc;
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{Algorithm, Header};
+use rand::RngCore;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, ModelTrait,
+    QueryFilter, Set,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha1::Sha1;
+use url::Url;
+use uuid::Uuid;
+
+/// RFC 6238 defaults, the only parameters every authenticator app supports
+const TOTP_STEP_SECONDS: i64 = 30;
+const TOTP_DIGITS: u32 = 6;
+/// Codes from the previous and next step are accepted to absorb clock drift
+const TOTP_ALLOWED_SKEW: i64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+const MFA_CHALLENGE_TTL_MINUTES: i64 = 5;
+/// Codes that can be tried against one challenge, wrong ones also count towards the lockout
+const MFA_CHALLENGE_MAX_ATTEMPTS: i32 = 5;
+const MFA_PENDING: &str = "mfa_pending";
+
+/// Claims of the challenge token returned by login when a second factor is needed.
+/// Its audience differs from access tokens, so it can never be used as one.
+#[derive(Debug, Serialize, Deserialize)]
+struct MfaChallengeClaims {
+    iss: String,
+    aud: String,
+    sub: String,
+    token_use: String,
+    /// Matches `user_mfa.challenge_jti` while the challenge is the user's latest
+    jti: String,
+    exp: i64,
+    iat: i64,
+}
+
+fn mfa_audience() -> String {
+    format!("{}/{MFA_PENDING}", config_service().jwt_audience)
+}
+
+/// Issues a challenge for the user, replacing any earlier one and its attempt count.
+pub async fn generate_mfa_challenge(
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+) -> Result<MfaChallengeResponse, CustomError> {
+    let jti = Uuid::new_v4();
+    Entity::update_many()
+        .col_expr(Column::ChallengeJti, Expr::value(jti))
+        .col_expr(Column::ChallengeAttempts, Expr::value(0))
+        .filter(Column::UserId.eq(user_id))
+        .exec(conn)
+        .await
+        .map_err(CustomError::from)?;
+
+    let now = chrono::Utc::now();
+    let exp = (now + chrono::Duration::minutes(MFA_CHALLENGE_TTL_MINUTES)).timestamp();
+    let claims = MfaChallengeClaims {
+        iss: config_service().jwt_issuer,
+        aud: mfa_audience(),
+        sub: user_id.to_string(),
+        token_use: MFA_PENDING.to_string(),
+        jti: jti.to_string(),
+        exp,
+        iat: now.timestamp(),
+    };
+
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(KEY_RING.signing_kid().to_string());
+    let mfa_token = jsonwebtoken::encode(&header, &claims, KEY_RING.encoding_key()).map_err(|e| {
+        CustomError::new(
+            HttpCodeW::InternalServerError,
+            format!("Failed to create MFA challenge: {e}"),
+        )
+    })?;
+
+    Ok(MfaChallengeResponse {
+        mfa_required: true,
+        mfa_token,
+        expires_in: MFA_CHALLENGE_TTL_MINUTES * 60,
+    })
+}
+
+/// The user and the `jti` the challenge was issued for
+fn verify_mfa_challenge(token: &str) -> Result<(Uuid, Uuid), CustomError> {
+    let invalid = || {
+        CustomError::new(
+            HttpCodeW::Unauthorized,
+            "Invalid or expired MFA challenge".to_string(),
+        )
+    };
+
+    let header = jsonwebtoken::decode_header(token).map_err(|_| invalid())?;
+    let decoding_key = KEY_RING
+        .decoding_key(header.kid.as_deref())
+        .ok_or_else(invalid)?;
+    let mut validation = jsonwebtoken::Validation::new(Algorithm::RS256);
+    validation.set_issuer(&[config_service().jwt_issuer]);
+    validation.set_audience(&[mfa_audience()]);
+
+    let claims = jsonwebtoken::decode::<MfaChallengeClaims>(token, decoding_key, &validation)
+        .map_err(|_| invalid())?
+        .claims;
+    if claims.token_use != MFA_PENDING {
+        return Err(invalid());
+    }
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| invalid())?;
+    let jti = Uuid::parse_str(&claims.jti).map_err(|_| invalid())?;
+    Ok((user_id, jti))
+}
+
+fn invalid_challenge() -> CustomError {
+    CustomError::new(
+        HttpCodeW::Unauthorized,
+        "Invalid or expired MFA challenge".to_string(),
+    )
+}
+
+/// Counts one code tried against the challenge. Fails once the challenge was used,
+/// replaced by a newer login or has run out of attempts.
+async fn claim_challenge_attempt(
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+    jti: Uuid,
+) -> Result<(), CustomError> {
+    let result = Entity::update_many()
+        .col_expr(
+            Column::ChallengeAttempts,
+            Expr::col(Column::ChallengeAttempts).add(1),
+        )
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::ChallengeJti.eq(jti))
+        .filter(Column::ChallengeAttempts.lt(MFA_CHALLENGE_MAX_ATTEMPTS))
+        .exec(conn)
+        .await
+        .map_err(CustomError::from)?;
+    if result.rows_affected == 0 {
+        return Err(invalid_challenge());
+    }
+    Ok(())
+}
+
+/// Ends the challenge after a correct code, so it signs in only once.
+async fn consume_challenge(
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+    jti: Uuid,
+) -> Result<(), CustomError> {
+    let result = Entity::update_many()
+        .col_expr(Column::ChallengeJti, Expr::value(Option::<Uuid>::None))
+        .filter(Column::UserId.eq(user_id))
+        .filter(Column::ChallengeJti.eq(jti))
+        .exec(conn)
+        .await
+        .map_err(CustomError::from)?;
+    if result.rows_affected == 0 {
+        return Err(invalid_challenge());
+    }
+    Ok(())
+}
+
+pub fn generate_totp_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// HOTP (RFC 4226) value for one time step
+fn totp_code(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&(step as u64).to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(TOTP_DIGITS),
+        width = TOTP_DIGITS as usize
+    )
+}
+
+/// Time step matched by `code`, if any. Steps up to `last_used_step` are rejected (replay).
+pub fn verify_totp(secret: &str, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.trim();
+    let current = chrono::Utc::now().timestamp() / TOTP_STEP_SECONDS;
+
+    (current - TOTP_ALLOWED_SKEW..=current + TOTP_ALLOWED_SKEW)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| totp_code(&secret, *step) == code)
+}
+
+pub fn otpauth_uri(secret: &str, account: &str, issuer: &str) -> String {
+    let mut uri = Url::parse("otpauth://totp/").expect("static URI is valid");
+    uri.set_path(&format!("/{issuer}:{account}"));
+    uri.query_pairs_mut()
+        .append_pair("secret", secret)
+        .append_pair("issuer", issuer)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &TOTP_DIGITS.to_string())
+        .append_pair("period", &TOTP_STEP_SECONDS.to_string());
+    uri.to_string()
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.trim().replace('-', "").to_lowercase()
+}
+
+/// Raw codes for the user and their hashes for storage
+fn generate_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let alphabet: Vec<char> = "abcdefghjkmnpqrstuvwxyz23456789".chars().collect();
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code = nanoid::nanoid!(10, &alphabet);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect();
+    let hashes = codes
+        .iter()
+        .map(|code| hash_refresh(&normalize_recovery_code(code)))
+        .collect();
+    (codes, hashes)
+}
+
+async fn find_mfa(conn: &DatabaseConnection, user_id: Uuid) -> Result<Option<Model>, CustomError> {
+    Entity::find()
+        .filter(Column::UserId.eq(user_id))
+        .one(conn)
+        .await
+        .map_err(CustomError::from)
+}
+
+pub async fn is_mfa_enabled(conn: &DatabaseConnection, user_id: Uuid) -> Result<bool, CustomError> {
+    Ok(find_mfa(conn, user_id).await?.is_some_and(|mfa| mfa.enabled))
+}
+
+/// Checks a TOTP code or, failing that, consumes a recovery code.
+///
+/// Both writes are conditional on the row still holding what was read, so two
+/// concurrent logins can't spend the same step or recovery code.
+async fn verify_second_factor(
+    conn: &DatabaseConnection,
+    mfa: Model,
+    code: &str,
+) -> Result<bool, CustomError> {
+    if let Some(step) = verify_totp(&mfa.totp_secret, code, mfa.last_used_step) {
+        let result = Entity::update_many()
+            .col_expr(Column::LastUsedStep, Expr::value(step))
+            .col_expr(Column::UpdatedAt, Expr::value(now_date_time_utc()))
+            .filter(Column::Id.eq(mfa.id))
+            .filter(
+                Condition::any()
+                    .add(Column::LastUsedStep.is_null())
+                    .add(Column::LastUsedStep.lt(step)),
+            )
+            .exec(conn)
+            .await
+            .map_err(CustomError::from)?;
+        return Ok(result.rows_affected == 1);
+    }
+
+    let hash = hash_refresh(&normalize_recovery_code(code));
+    let mut hashes = mfa.recovery_code_hashes();
+    match hashes.iter().position(|stored| *stored == hash) {
+        Some(index) => {
+            hashes.remove(index);
+            let result = Entity::update_many()
+                .col_expr(Column::RecoveryCodes, Expr::value(json!(hashes)))
+                .col_expr(Column::UpdatedAt, Expr::value(now_date_time_utc()))
+                .filter(Column::Id.eq(mfa.id))
+                .filter(Column::RecoveryCodes.eq(mfa.recovery_codes))
+                .exec(conn)
+                .await
+                .map_err(CustomError::from)?;
+            Ok(result.rows_affected == 1)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Starts (or restarts) enrolment with a fresh secret. Enabled 2FA has to be disabled first.
+pub async fn enroll_totp_logic(
+    conn: &DatabaseConnection,
+    user: &users::Model,
+) -> Result<TotpEnrollmentResponse, CustomError> {
+    let secret = generate_totp_secret();
+    let result = match find_mfa(conn, user.id).await? {
+        Some(mfa) if mfa.enabled => {
+            return Err(CustomError::new(
+                HttpCodeW::Conflict,
+                "Two-factor authentication is already enabled".to_string(),
+            ))
+        }
+        Some(mfa) => {
+            let mut active: ActiveModel = mfa.into();
+            active.totp_secret = Set(secret.clone());
+            active.last_used_step = Set(None);
+            active.update(conn).await
+        }
+        None => {
+            ActiveModel {
+                id: Set(Uuid::new_v4()),
+                user_id: Set(user.id),
+                totp_secret: Set(secret.clone()),
+                enabled: Set(false),
+                enabled_at: Set(None),
+                last_used_step: Set(None),
+                recovery_codes: Set(json!([])),
+                challenge_jti: Set(None),
+                challenge_attempts: Set(0),
+                created_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+                updated_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+            }
+            .insert(conn)
+            .await
+        }
+    };
+    result.map_err(CustomError::from)?;
+
+    let issuer = config_service().totp_issuer;
+    Ok(TotpEnrollmentResponse {
+        otpauth_uri: otpauth_uri(&secret, &user.email, &issuer),
+        secret,
+    })
+}
+
+/// Confirms the authenticator works and turns 2FA on. Recovery codes are returned only here.
+pub async fn enable_totp_logic(
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+    code: &str,
+) -> Result<RecoveryCodesResponse, CustomError> {
+    let mfa = find_mfa(conn, user_id)
+        .await?
+        .filter(|mfa| !mfa.enabled)
+        .ok_or_else(|| {
+            CustomError::new(
+                HttpCodeW::BadRequest,
+                "No pending two-factor enrolment".to_string(),
+            )
+        })?;
+    let step = verify_totp(&mfa.totp_secret, code, mfa.last_used_step).ok_or_else(|| {
+        CustomError::new(HttpCodeW::BadRequest, "Invalid TOTP code".to_string())
+    })?;
+
+    let (recovery_codes, hashes) = generate_recovery_codes();
+    let mut active: ActiveModel = mfa.into();
+    active.enabled = Set(true);
+    active.enabled_at = Set(Some(DateTimeWithTimeZone::from(now_date_time_utc())));
+    active.last_used_step = Set(Some(step));
+    active.recovery_codes = Set(json!(hashes));
+    active.update(conn).await.map_err(CustomError::from)?;
+
+    Ok(RecoveryCodesResponse { recovery_codes })
+}
+
+pub async fn disable_totp_logic(
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+    code: &str,
+) -> Result<String, CustomError> {
+    let mfa = find_mfa(conn, user_id)
+        .await?
+        .filter(|mfa| mfa.enabled)
+        .ok_or_else(|| {
+            CustomError::new(
+                HttpCodeW::BadRequest,
+                "Two-factor authentication is not enabled".to_string(),
+            )
+        })?;
+    if !verify_second_factor(conn, mfa.clone(), code).await? {
+        return Err(CustomError::new(
+            HttpCodeW::Unauthorized,
+            "Invalid two-factor code".to_string(),
+        ));
+    }
+
+    mfa.delete(conn).await.map_err(CustomError::from)?;
+    Ok("Two-factor authentication disabled".to_string())
+}
+
+/// Second step of a login with 2FA: exchanges the challenge and a code for tokens.
+/// Wrong codes count towards the account lockout like wrong passwords, and each
+/// challenge takes at most `MFA_CHALLENGE_MAX_ATTEMPTS` codes.
+pub async fn mfa_verify_logic(
+    conn: &DatabaseConnection,
+    users_service: &UsersService,
+    tokens_service: &TokensService,
+    payload: MfaVerifyRequestBody,
+    client: &ClientInfo,
+) -> Result<AuthResponseBody, CustomError> {
+    let (user_id, jti) = verify_mfa_challenge(&payload.mfa_token)?;
+    let user = users_service.find("id", SearchValue::Uuid(user_id)).await?;
+    if !user.can_login() {
+        return Err(CustomError::new(
+            HttpCodeW::Unauthorized,
+            "User cannot sign in".to_string(),
+        ));
+    }
+    UsersService::ensure_not_locked(&user)?;
+
+    let mfa = find_mfa(conn, user_id)
+        .await?
+        .filter(|mfa| mfa.enabled)
+        .ok_or_else(|| {
+            CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Two-factor authentication is not enabled".to_string(),
+            )
+        })?;
+    claim_challenge_attempt(conn, user_id, jti).await?;
+    if !verify_second_factor(conn, mfa, &payload.code).await? {
+        AuditService::record(
+            NewAuditEvent::failure(AuditEventType::Login)
+                .with_target(user_id)
+                .with_client(client)
+                .with_metadata(json!({ "method": "mfa", "reason": "Invalid two-factor code" })),
+            conn,
+        )
+        .await;
+        if let Some(locked_until) = users_service
+            .record_failed_login(&user, &client.ip_address)
+            .await?
+        {
+            return Err(UsersService::locked_error(locked_until));
+        }
+        return Err(CustomError::new(
+            HttpCodeW::Unauthorized,
+            "Invalid two-factor code".to_string(),
+        ));
+    }
+    consume_challenge(conn, user_id, jti).await?;
+
+    let mut active_user: users::ActiveModel = user.into();
+    UsersService::add_details_login(
+        &mut active_user,
+        json!({
+            "timestamp": now_date_time_utc(),
+            "notes": "User Logged (2FA)",
+            "ip_address": client.ip_address,
+        }),
+    );
+    UsersService::reset_failed_logins(&mut active_user);
+    let user = active_user.update(conn).await.map_err(|_| {
+        CustomError::new(
+            HttpCodeW::InternalServerError,
+            "Failed to update user".to_string(),
+        )
+    })?;
+
+    issue_tokens_for_user(conn, tokens_service, &user, client).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_db;
+
+    async fn insert_mfa(db: &DatabaseConnection, user_id: Uuid, hashes: Vec<String>) -> Model {
+        ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            totp_secret: Set(generate_totp_secret()),
+            enabled: Set(true),
+            enabled_at: Set(Some(DateTimeWithTimeZone::from(now_date_time_utc()))),
+            last_used_step: Set(None),
+            recovery_codes: Set(json!(hashes)),
+            challenge_jti: Set(None),
+            challenge_attempts: Set(0),
+            created_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+            updated_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+        }
+        .insert(db)
+        .await
+        .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn second_factor_is_spent_once_from_a_stale_read() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let (codes, hashes) = generate_recovery_codes();
+        // Both attempts verify against the same snapshot, as two concurrent logins would
+        let stale = insert_mfa(&db, user_id, hashes).await;
+
+        let secret = BASE32_NOPAD.decode(stale.totp_secret.as_bytes()).unwrap();
+        let step = chrono::Utc::now().timestamp() / TOTP_STEP_SECONDS;
+        let totp = totp_code(&secret, step);
+        let totp_first = verify_second_factor(&db, stale.clone(), &totp).await.unwrap();
+        let totp_replay = verify_second_factor(&db, stale.clone(), &totp).await.unwrap();
+
+        let recovery_first = verify_second_factor(&db, stale.clone(), &codes[0]).await.unwrap();
+        let recovery_replay = verify_second_factor(&db, stale.clone(), &codes[0]).await.unwrap();
+        let remaining = find_mfa(&db, user_id).await.unwrap().unwrap().recovery_code_hashes();
+
+        test_db::delete_user(&db, user_id).await;
+        assert!(totp_first);
+        assert!(!totp_replay);
+        assert!(recovery_first);
+        assert!(!recovery_replay);
+        assert_eq!(remaining.len(), RECOVERY_CODE_COUNT - 1);
+    }
+
+    fn current_code(mfa: &Model) -> String {
+        let secret = BASE32_NOPAD.decode(mfa.totp_secret.as_bytes()).unwrap();
+        totp_code(&secret, chrono::Utc::now().timestamp() / TOTP_STEP_SECONDS)
+    }
+
+    async fn verify(db: &DatabaseConnection, mfa_token: &str, code: &str) -> Result<(), CustomError> {
+        let users_service = UsersService::new(db);
+        let tokens_service = TokensService::new(db, &users_service);
+        let payload = MfaVerifyRequestBody {
+            mfa_token: mfa_token.to_string(),
+            code: code.to_string(),
+        };
+        let client = ClientInfo {
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: None,
+        };
+        mfa_verify_logic(db, &users_service, &tokens_service, payload, &client)
+            .await
+            .map(|_| ())
+    }
+
+    #[actix_rt::test]
+    async fn wrong_codes_lock_the_account() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        test_db::configure_env();
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let mfa = insert_mfa(&db, user_id, Vec::new()).await;
+        let challenge = generate_mfa_challenge(&db, user_id).await.unwrap();
+
+        let max_failed = config_service().login_max_failed_attempts;
+        let mut errors = Vec::new();
+        for _ in 0..max_failed {
+            errors.push(verify(&db, &challenge.mfa_token, "not-a-code").await.unwrap_err());
+        }
+        let fresh = generate_mfa_challenge(&db, user_id).await.unwrap();
+        let after_lock = verify(&db, &fresh.mfa_token, &current_code(&mfa)).await.unwrap_err();
+
+        test_db::delete_user(&db, user_id).await;
+        let (last, earlier) = errors.split_last().unwrap();
+        assert!(earlier
+            .iter()
+            .all(|e| e.error_status_code == HttpCodeW::Unauthorized));
+        assert_eq!(last.error_status_code, HttpCodeW::Locked);
+        assert_eq!(after_lock.error_status_code, HttpCodeW::Locked);
+    }
+
+    #[actix_rt::test]
+    async fn challenge_is_single_use_and_replaced_by_the_next_login() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        test_db::configure_env();
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let (codes, hashes) = generate_recovery_codes();
+        insert_mfa(&db, user_id, hashes).await;
+
+        let replaced = generate_mfa_challenge(&db, user_id).await.unwrap();
+        let latest = generate_mfa_challenge(&db, user_id).await.unwrap();
+        let replaced_result = verify(&db, &replaced.mfa_token, &codes[0]).await;
+        let first = verify(&db, &latest.mfa_token, &codes[1]).await;
+        let reused = verify(&db, &latest.mfa_token, &codes[2]).await;
+
+        test_db::delete_user(&db, user_id).await;
+        assert_eq!(
+            replaced_result.unwrap_err().error_message,
+            "Invalid or expired MFA challenge"
+        );
+        assert!(first.is_ok());
+        assert_eq!(reused.unwrap_err().error_message, "Invalid or expired MFA challenge");
+    }
+}
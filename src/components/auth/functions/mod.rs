@@ -1,7 +1,31 @@
 mod login;
 pub(crate) mod token;
 mod refresh;
+mod password_reset;
+mod key_ring;
+mod bearer;
+mod discovery;
+mod issue;
+mod introspect;
+mod logout;
+mod mfa;
+mod profile;
+mod admin_users;
+mod permission_cache;
+mod organization;
 
 pub use login::*;
 pub use token::*;
-pub use refresh::*;
\ No newline at end of file
+pub use refresh::*;
+pub use password_reset::*;
+pub use key_ring::*;
+pub use bearer::*;
+pub use discovery::*;
+pub use issue::*;
+pub use introspect::*;
+pub use logout::*;
+pub use mfa::*;
+pub use profile::*;
+pub use admin_users::*;
+pub use permission_cache::*;
+pub use organization::*;
\ No newline at end of file
@@ -0,0 +1,103 @@
+use crate::components::auth::functions::{
+    compute_roles_and_permissions, generate_jwt_token, TokenDetails, TokenSubject, KEY_RING,
+};
+use crate::components::sessions::SessionsService;
+use crate::components::tokens::TokensService;
+use crate::components::users::enums::SearchValue;
+use crate::components::users::UsersService;
+use crate::config_service;
+use crate::entity::users::BodyToken;
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use crate::http_response::HttpCodeW::InternalServerError;
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use uuid::Uuid;
+
+/// Re-issues the access token with the roles the user holds in `organization_id`, or with
+/// the global roles only for `None`. The choice is stored on the session of the refresh
+/// cookie, so refreshes keep it, and the access token of the request is revoked.
+pub async fn switch_organization_logic(
+    users_service: &UsersService,
+    tokens_service: &TokensService,
+    conn: &DatabaseConnection,
+    caller: TokenDetails,
+    refresh_token: Option<String>,
+    organization_id: Option<Uuid>,
+) -> Result<BodyToken, CustomError> {
+    let user_id = caller.subject.user_id().ok_or_else(|| {
+        CustomError::new(
+            HttpCodeW::Forbidden,
+            "Switching organization requires a user access token".to_string(),
+        )
+    })?;
+
+    let refresh_token = refresh_token.ok_or_else(|| {
+        CustomError::new(HttpCodeW::Unauthorized, "Missing refresh token".to_string())
+    })?;
+    let family = match tokens_service.find_refresh_by_raw(&refresh_token, conn).await? {
+        Some(m) if m.user_id == user_id && !m.is_revoked && !m.is_expired() => m.family(),
+        _ => {
+            return Err(CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Invalid refresh token".to_string(),
+            ))
+        }
+    };
+
+    let access = compute_roles_and_permissions(conn, user_id, organization_id)
+        .await
+        .map_err(|e| {
+            CustomError::new(
+                InternalServerError,
+                format!("Failed to compute permissions: {e}"),
+            )
+        })?;
+    if access.org_id != organization_id {
+        return Err(CustomError::new(
+            HttpCodeW::Forbidden,
+            "You are not a member of this organization".to_string(),
+        ));
+    }
+
+    let user = users_service.find("id", SearchValue::Uuid(user_id)).await?;
+    let token_details = generate_jwt_token(
+        TokenSubject::User(user_id),
+        config_service().access_token_max_age,
+        &KEY_RING,
+        access,
+        user.email,
+    )
+    .map_err(|e| {
+        log::error!("JWT generation error: {:?}", e);
+        CustomError::new(
+            InternalServerError,
+            "Failed to generate access token".to_string(),
+        )
+    })?;
+
+    let txn = conn.begin().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn begin error: {e}"))
+    })?;
+    SessionsService::set_active_organization(family, organization_id, &txn).await?;
+    tokens_service
+        .record_access_token(
+            user_id,
+            token_details.token_uuid,
+            family,
+            token_details.expires_in.unwrap_or_default(),
+            &txn,
+        )
+        .await?;
+    txn.commit().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn commit error: {e}"))
+    })?;
+
+    tokens_service
+        .revoke_access_token(caller.token_uuid, conn)
+        .await?;
+
+    Ok(BodyToken {
+        username: user.username,
+        access_token: token_details.token.unwrap_or_default(),
+    })
+}
@@ -0,0 +1,118 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::config::ConfigService;
+use crate::components::mail_send::MailSendService;
+use crate::components::sessions::SessionsService;
+use crate::components::tokens::TokensService;
+use crate::components::users::enums::SearchValue;
+use crate::components::users::UsersService;
+use crate::entity::enums::AuditEventType;
+use crate::entity::users::{ActiveModel, ForgotPasswordRequestBody, ResetPasswordRequestBody};
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use crate::http_response::HttpCodeW::InternalServerError;
+use crate::utils::helpers::{hash_password, now_date_time_utc, validate_password};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set, TransactionTrait};
+use serde_json::json;
+
+const FORGOT_PASSWORD_MESSAGE: &str =
+    "If an account with this email exists, a password reset link has been sent";
+
+pub async fn forgot_password_logic(
+    users_service: &UsersService,
+    tokens_service: &TokensService,
+    mail_send_service: &MailSendService,
+    payload: ForgotPasswordRequestBody,
+    service_config: &ConfigService,
+) -> Result<String, CustomError> {
+    let user = match users_service
+        .find("email", SearchValue::String(payload.email))
+        .await
+    {
+        Ok(user) => user,
+        // Same answer whether the account exists or not, so emails can't be enumerated
+        Err(e) if e.error_status_code == HttpCodeW::NotFound => {
+            return Ok(FORGOT_PASSWORD_MESSAGE.to_string())
+        }
+        Err(e) => return Err(e),
+    };
+
+    if !user.can_login() {
+        return Ok(FORGOT_PASSWORD_MESSAGE.to_string());
+    }
+
+    let (raw_token, _row) = tokens_service
+        .create_reset_password_token_for_user(user.id)
+        .await?;
+
+    if let Err(e) = mail_send_service.send_password_reset_mail(
+        user.email.clone(),
+        raw_token,
+        service_config,
+    ) {
+        log::error!("Password reset mail error: {:?}", e);
+    }
+
+    Ok(FORGOT_PASSWORD_MESSAGE.to_string())
+}
+
+pub async fn reset_password_logic(
+    users_service: &UsersService,
+    tokens_service: &TokensService,
+    conn: &DatabaseConnection,
+    payload: ResetPasswordRequestBody,
+    ip_address: String,
+) -> Result<String, CustomError> {
+    let txn = conn.begin().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn begin error: {e}"))
+    })?;
+
+    let token_model = tokens_service
+        .find_reset_password_token_by_raw(&payload.token, &txn)
+        .await?;
+    let user_id = token_model.user_id;
+    // Single use: claimed before anything else, a concurrent reset with the same token loses
+    if !TokensService::revoke_token(token_model, &txn).await? {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            "Invalid or expired reset token".to_string(),
+        ));
+    }
+
+    let user = users_service.find("id", SearchValue::Uuid(user_id)).await?;
+    validate_password(&payload.password, &user.email, Some(&user.username))?;
+    let hashed = hash_password(payload.password.as_str()).map_err(|e| {
+        CustomError::new(InternalServerError, format!("Failed to hash password: {e}"))
+    })?;
+
+    let mut active_user: ActiveModel = user.into();
+    let new_login = json!({
+        "timestamp": now_date_time_utc(),
+        "notes": "Password reset",
+        "ip_address": ip_address,
+    });
+    UsersService::add_details_login(&mut active_user, new_login);
+    active_user.password_hash = Set(hashed);
+    active_user.update(&txn).await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Failed to update user: {e}"))
+    })?;
+
+    // Every session created with the old password goes away
+    TokensService::revoke_all_refresh_tokens_for_user(user_id, &txn).await?;
+    SessionsService::end_all_sessions_for_user(user_id, &txn).await?;
+
+    txn.commit().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn commit error: {e}"))
+    })?;
+    tokens_service
+        .revoke_all_access_tokens_for_user(user_id, conn)
+        .await?;
+
+    AuditService::record(
+        NewAuditEvent::success(AuditEventType::PasswordReset)
+            .with_user(user_id)
+            .with_ip_address(&ip_address),
+        conn,
+    )
+    .await;
+    Ok("Password reset successfully".to_string())
+}
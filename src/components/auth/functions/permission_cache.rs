@@ -0,0 +1,142 @@
+use crate::components::auth::functions::ResolvedAccess;
+use crate::config_service;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Roles and permissions resolved per user and active organization, shared by every worker of this instance.
+/// RBAC writes going through the API invalidate it; the TTL bounds how long changes
+/// made elsewhere (migrations, other instances) take to show up.
+pub static PERMISSION_CACHE: Lazy<PermissionCache> =
+    Lazy::new(|| PermissionCache::new(config_service().permission_cache_ttl_seconds));
+
+/// Expired entries are swept once the map grows past this size. If it is still full
+/// afterwards, new entries are not cached until some expire.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+struct CachedAccess {
+    access: ResolvedAccess,
+    cached_at: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<(Uuid, Option<Uuid>), CachedAccess>,
+    /// Bumped on every invalidation so a lookup that started before it cannot
+    /// store what it read
+    generation: u64,
+}
+
+pub struct PermissionCache {
+    ttl: Duration,
+    max_entries: usize,
+    state: Mutex<CacheState>,
+}
+
+impl PermissionCache {
+    pub fn new(ttl_seconds: u64) -> Self {
+        Self {
+            ttl: Duration::from_secs(ttl_seconds),
+            max_entries: SWEEP_THRESHOLD,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Cached access of the user in `org_id`, or the generation to pass to `insert`
+    pub fn get(&self, user_id: Uuid, org_id: Option<Uuid>) -> Result<ResolvedAccess, u64> {
+        let mut state = self.state.lock().expect("permission cache poisoned");
+        match state.entries.get(&(user_id, org_id)) {
+            Some(cached) if cached.cached_at.elapsed() < self.ttl => Ok(cached.access.clone()),
+            Some(_) => {
+                state.entries.remove(&(user_id, org_id));
+                Err(state.generation)
+            }
+            None => Err(state.generation),
+        }
+    }
+
+    pub fn insert(
+        &self,
+        user_id: Uuid,
+        org_id: Option<Uuid>,
+        generation: u64,
+        access: &ResolvedAccess,
+    ) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut state = self.state.lock().expect("permission cache poisoned");
+        if state.generation != generation {
+            return;
+        }
+        if state.entries.len() >= self.max_entries {
+            let ttl = self.ttl;
+            state.entries.retain(|_, cached| cached.cached_at.elapsed() < ttl);
+            if state.entries.len() >= self.max_entries
+                && !state.entries.contains_key(&(user_id, org_id))
+            {
+                return;
+            }
+        }
+        state.entries.insert(
+            (user_id, org_id),
+            CachedAccess {
+                access: access.clone(),
+                cached_at: Instant::now(),
+            },
+        );
+    }
+
+    /// After a change to the roles, overrides or memberships of one user
+    pub fn invalidate_user(&self, user_id: Uuid) {
+        let mut state = self.state.lock().expect("permission cache poisoned");
+        state.generation += 1;
+        state.entries.retain(|(cached_user, _), _| *cached_user != user_id);
+    }
+
+    /// After a change to a role, permission or organization, which may touch any user
+    pub fn invalidate_all(&self) {
+        let mut state = self.state.lock().expect("permission cache poisoned");
+        state.generation += 1;
+        state.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(ttl: Duration, max_entries: usize) -> PermissionCache {
+        PermissionCache {
+            ttl,
+            max_entries,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    #[test]
+    fn full_cache_sweeps_expired_entries_and_stops_growing() {
+        let access = ResolvedAccess::default();
+        let short_lived = cache(Duration::from_millis(20), 2);
+        short_lived.insert(Uuid::new_v4(), None, 0, &access);
+        short_lived.insert(Uuid::new_v4(), None, 0, &access);
+        std::thread::sleep(Duration::from_millis(30));
+        let fresh = Uuid::new_v4();
+        short_lived.insert(fresh, None, 0, &access);
+        assert_eq!(short_lived.state.lock().unwrap().entries.len(), 1);
+        assert!(short_lived.get(fresh, None).is_ok());
+
+        let long_lived = cache(Duration::from_secs(60), 2);
+        let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        for user_id in [first, second, third] {
+            long_lived.insert(user_id, None, 0, &access);
+        }
+        assert_eq!(long_lived.state.lock().unwrap().entries.len(), 2);
+        assert!(long_lived.get(third, None).is_err());
+        // Entries already cached are still refreshed
+        long_lived.insert(first, None, 0, &access);
+        assert!(long_lived.get(first, None).is_ok());
+    }
+}
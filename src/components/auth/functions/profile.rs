@@ -0,0 +1,327 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::TokenDetails;
+use crate::components::config::ConfigService;
+use crate::components::mail_send::MailSendService;
+use crate::components::sessions::SessionsService;
+use crate::components::tokens::TokensService;
+use crate::components::users::enums::SearchValue;
+use crate::components::users::UsersService;
+use crate::entity::enums::AuditEventType;
+use crate::entity::users::{
+    ActiveModel, ChangeEmailRequestBody, ChangePasswordRequestBody, Column, Entity, Model,
+    ProfileResponse, UpdateProfileRequestBody,
+};
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use crate::http_response::HttpCodeW::InternalServerError;
+use crate::utils::helpers::{hash_password, now_date_time_utc, validate_password, verify_password};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+const USERNAME_MIN_LEN: usize = 3;
+const USERNAME_MAX_LEN: usize = 50;
+
+fn check_current_password(user: &Model, password: &str) -> Result<(), CustomError> {
+    match verify_password(password, &user.password_hash) {
+        Ok(true) => Ok(()),
+        _ => Err(CustomError::new(
+            HttpCodeW::Unauthorized,
+            "Current password is incorrect".to_string(),
+        )),
+    }
+}
+
+/// Blank names clear the field
+fn normalize_name(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+pub async fn update_profile_logic(
+    users_service: &UsersService,
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+    payload: UpdateProfileRequestBody,
+) -> Result<ProfileResponse, CustomError> {
+    let user = users_service.find("id", SearchValue::Uuid(user_id)).await?;
+    let mut active_user: ActiveModel = user.clone().into();
+
+    if let Some(username) = payload.username {
+        let username = username.trim().to_string();
+        let length = username.chars().count();
+        if !(USERNAME_MIN_LEN..=USERNAME_MAX_LEN).contains(&length) {
+            return Err(CustomError::new(
+                HttpCodeW::BadRequest,
+                format!(
+                    "Username must be between {USERNAME_MIN_LEN} and {USERNAME_MAX_LEN} characters"
+                ),
+            ));
+        }
+
+        if username != user.username {
+            let taken = Entity::find()
+                .filter(Column::Username.eq(username.clone()))
+                .filter(Column::Id.ne(user_id))
+                .one(conn)
+                .await
+                .map_err(CustomError::from)?;
+            if taken.is_some() {
+                return Err(CustomError::new(
+                    HttpCodeW::Conflict,
+                    "Username is already taken".to_string(),
+                ));
+            }
+            active_user.username = Set(username);
+        }
+    }
+    if let Some(first_name) = payload.first_name {
+        active_user.first_name = Set(normalize_name(first_name));
+    }
+    if let Some(last_name) = payload.last_name {
+        active_user.last_name = Set(normalize_name(last_name));
+    }
+
+    // The unique index still catches a username claimed between the check and the update
+    let updated = active_user.update(conn).await.map_err(|e| {
+        if e.to_string()
+            .contains("duplicate key value violates unique constraint")
+        {
+            CustomError::new(HttpCodeW::Conflict, "Username is already taken".to_string())
+        } else {
+            CustomError::new(InternalServerError, format!("Failed to update user: {e}"))
+        }
+    })?;
+
+    Ok(ProfileResponse::from(updated))
+}
+
+/// Changes the password and signs out every other session. The session of
+/// `refresh_token` and the access token of the request stay valid.
+pub async fn change_password_logic(
+    users_service: &UsersService,
+    tokens_service: &TokensService,
+    conn: &DatabaseConnection,
+    caller: TokenDetails,
+    refresh_token: Option<String>,
+    payload: ChangePasswordRequestBody,
+    ip_address: String,
+) -> Result<String, CustomError> {
+    let user_id = caller.subject.user_id().ok_or_else(|| {
+        CustomError::new(
+            HttpCodeW::Forbidden,
+            "This endpoint requires a user access token".to_string(),
+        )
+    })?;
+    let user = users_service.find("id", SearchValue::Uuid(user_id)).await?;
+    if let Err(e) = check_current_password(&user, &payload.current_password) {
+        AuditService::record(
+            NewAuditEvent::failure(AuditEventType::PasswordChange)
+                .with_user(user_id)
+                .with_ip_address(&ip_address)
+                .with_metadata(json!({ "reason": e.error_message })),
+            conn,
+        )
+        .await;
+        return Err(e);
+    }
+    validate_password(&payload.new_password, &user.email, Some(&user.username))?;
+
+    let hashed = hash_password(payload.new_password.as_str()).map_err(|e| {
+        CustomError::new(InternalServerError, format!("Failed to hash password: {e}"))
+    })?;
+
+    let txn = conn.begin().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn begin error: {e}"))
+    })?;
+
+    let keep_family = match refresh_token {
+        Some(raw) => tokens_service
+            .find_refresh_by_raw(&raw, &txn)
+            .await?
+            .filter(|model| model.user_id == user_id && model.is_valid())
+            .map(|model| model.family()),
+        None => None,
+    };
+
+    let mut active_user: ActiveModel = user.into();
+    UsersService::add_details_login(
+        &mut active_user,
+        json!({
+            "timestamp": now_date_time_utc(),
+            "notes": "Password changed",
+            "ip_address": ip_address,
+        }),
+    );
+    active_user.password_hash = Set(hashed);
+    active_user.update(&txn).await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Failed to update user: {e}"))
+    })?;
+
+    TokensService::revoke_other_refresh_tokens_for_user(user_id, keep_family, &txn).await?;
+    match keep_family {
+        Some(family_id) => {
+            SessionsService::end_other_sessions_for_user(user_id, family_id, &txn).await?
+        }
+        None => SessionsService::end_all_sessions_for_user(user_id, &txn).await?,
+    };
+
+    txn.commit().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn commit error: {e}"))
+    })?;
+    tokens_service
+        .revoke_other_access_tokens_for_user(user_id, Some(caller.token_uuid), conn)
+        .await?;
+
+    AuditService::record(
+        NewAuditEvent::success(AuditEventType::PasswordChange)
+            .with_user(user_id)
+            .with_ip_address(&ip_address),
+        conn,
+    )
+    .await;
+    Ok("Password changed".to_string())
+}
+
+/// Stores the new address as pending and mails it a confirmation link.
+/// `users.email` only changes once the link is used.
+pub async fn request_email_change_logic(
+    users_service: &UsersService,
+    tokens_service: &TokensService,
+    mail_send_service: &MailSendService,
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+    payload: ChangeEmailRequestBody,
+    service_config: &ConfigService,
+) -> Result<String, CustomError> {
+    let new_email = payload.new_email.trim().to_string();
+    if !email_address::EmailAddress::is_valid(&new_email) {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            "Invalid email format".to_string(),
+        ));
+    }
+
+    let user = users_service.find("id", SearchValue::Uuid(user_id)).await?;
+    check_current_password(&user, &payload.password)?;
+    if new_email.eq_ignore_ascii_case(&user.email) {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            "New email is the same as the current one".to_string(),
+        ));
+    }
+
+    match users_service
+        .find("email", SearchValue::String(new_email.clone()))
+        .await
+    {
+        Ok(_) => {
+            return Err(CustomError::new(
+                HttpCodeW::Conflict,
+                "Email is already in use".to_string(),
+            ))
+        }
+        Err(e) if e.error_status_code == HttpCodeW::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    // Older links are revoked in the same transaction that replaces the pending address,
+    // so a link mailed to a previous address can never confirm this one
+    let txn = conn.begin().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn begin error: {e}"))
+    })?;
+    let (raw_token, _row) = tokens_service
+        .create_email_change_token_for_user(user_id, &txn)
+        .await?;
+    let mut active_user: ActiveModel = user.into();
+    active_user.pending_email = Set(Some(new_email.clone()));
+    active_user.update(&txn).await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Failed to update user: {e}"))
+    })?;
+    txn.commit().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn commit error: {e}"))
+    })?;
+    if let Err(e) = mail_send_service.send_email_change_mail(new_email, raw_token, service_config)
+    {
+        log::error!("Email change mail error: {:?}", e);
+    }
+
+    Ok("A confirmation link has been sent to the new address".to_string())
+}
+
+pub async fn confirm_email_change_logic(
+    users_service: &UsersService,
+    tokens_service: &TokensService,
+    mail_send_service: &MailSendService,
+    conn: &DatabaseConnection,
+    token: String,
+    ip_address: String,
+    service_config: &ConfigService,
+) -> Result<String, CustomError> {
+    let txn = conn.begin().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn begin error: {e}"))
+    })?;
+
+    let token_model = tokens_service
+        .find_email_change_token_by_raw(&token, &txn)
+        .await?;
+    let user = users_service
+        .find("id", SearchValue::Uuid(token_model.user_id))
+        .await?;
+    let new_email = user.pending_email.clone().ok_or_else(|| {
+        CustomError::new(
+            HttpCodeW::BadRequest,
+            "No email change is pending".to_string(),
+        )
+    })?;
+
+    // The address may have been registered since the change was requested
+    match users_service
+        .find("email", SearchValue::String(new_email.clone()))
+        .await
+    {
+        Ok(other) if other.id != user.id => {
+            return Err(CustomError::new(
+                HttpCodeW::Conflict,
+                "Email is already in use".to_string(),
+            ))
+        }
+        Ok(_) => {}
+        Err(e) if e.error_status_code == HttpCodeW::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let old_email = user.email.clone();
+    let mut active_user: ActiveModel = user.into();
+    UsersService::add_details_login(
+        &mut active_user,
+        json!({
+            "timestamp": now_date_time_utc(),
+            "notes": "Email changed",
+            "previous_email": old_email,
+            "ip_address": ip_address,
+        }),
+    );
+    active_user.email = Set(new_email.clone());
+    active_user.pending_email = Set(None);
+    // Following the link proves the new address
+    active_user.email_verified = Set(true);
+    active_user.update(&txn).await.map_err(|e| {
+        CustomError::new(HttpCodeW::Conflict, format!("Failed to change email: {e}"))
+    })?;
+
+    TokensService::revoke_token(token_model, &txn).await?;
+    txn.commit().await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Txn commit error: {e}"))
+    })?;
+
+    if let Err(e) = mail_send_service.send_email_changed_notice(old_email, new_email, service_config)
+    {
+        log::error!("Email changed notice error: {:?}", e);
+    }
+
+    Ok("Email changed successfully".to_string())
+}
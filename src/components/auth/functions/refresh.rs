@@ -1,20 +1,31 @@
-use crate::components::auth::functions::{compute_roles_and_permissions, generate_jwt_token};
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::{
+    compute_roles_and_permissions, generate_jwt_token, TokenSubject, KEY_RING,
+};
+use crate::components::sessions::SessionsService;
 use crate::components::tokens::TokensService;
 use crate::components::users::enums::SearchValue;
 use crate::components::users::UsersService;
 use crate::config_service;
+use crate::entity::enums::AuditEventType;
+use crate::entity::sessions::ClientInfo;
+use crate::entity::tokens::Model;
+use crate::entity::users;
 use crate::entity::users::{AuthResponseBody, BodyToken};
 use crate::http_response::error_handler::CustomError;
 use crate::http_response::HttpCodeW;
 use crate::http_response::HttpCodeW::InternalServerError;
 use actix_web::cookie::Cookie;
-use sea_orm::{DatabaseConnection, TransactionTrait};
+use crate::utils::helpers::now_date_time_utc;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, DatabaseTransaction, TransactionTrait};
+use serde_json::json;
 
 pub async fn refresh_logic(
     tokens_service: &TokensService,
     users_service: &UsersService,
     conn: &DatabaseConnection,
     cookie_refresh_token: Option<Cookie<'_>>,
+    client: ClientInfo,
 ) -> Result<Option<AuthResponseBody>, CustomError> {
     let refresh_token = match cookie_refresh_token {
         None => {
@@ -36,33 +47,82 @@ pub async fn refresh_logic(
         }
     };
     let old_token_model = match tokens_service
-        .is_token_available(&refresh_token, &txn)
-        .await
+        .lock_refresh_by_raw(&refresh_token, &txn)
+        .await?
     {
-        Ok(Some(model)) => model,
-        Ok(None) => {
+        None => {
+            AuditService::record(
+                NewAuditEvent::failure(AuditEventType::TokenRefresh)
+                    .with_client(&client)
+                    .with_metadata(json!({ "reason": "Unknown refresh token" })),
+                conn,
+            )
+            .await;
             return Err(CustomError::new(
                 HttpCodeW::Unauthorized,
                 "Invalid refresh token".into(),
             ));
         }
-        Err(err) => {
-            return Err(err);
+        // Ended by logout, a password change or a terminated session
+        Some(m) if m.is_revoked && !TokensService::was_rotated(&m, &txn).await? => {
+            AuditService::record(
+                NewAuditEvent::failure(AuditEventType::TokenRefresh)
+                    .with_target(m.user_id)
+                    .with_client(&client)
+                    .with_metadata(json!({ "reason": "Refresh token revoked" })),
+                conn,
+            )
+            .await;
+            return Err(CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Refresh token revoked".into(),
+            ));
+        }
+        // Replay of a rotated-out token: either the legitimate client or an attacker
+        // holds a stolen copy, so the whole chain goes (OAuth 2.0 Security BCP 4.14.2)
+        Some(m) if m.is_revoked => {
+            let audit_event = NewAuditEvent::failure(AuditEventType::TokenRefresh)
+                .with_target(m.user_id)
+                .with_client(&client)
+                .with_metadata(json!({
+                    "reason": "Refresh token reuse detected, token family revoked",
+                    "token_family": m.family(),
+                }));
+            handle_refresh_token_reuse(users_service, m, &client.ip_address, &txn).await?;
+            txn.commit().await.map_err(|e| {
+                CustomError::new(InternalServerError, format!("Txn commit error: {e}"))
+            })?;
+            AuditService::record(audit_event, conn).await;
+            return Err(CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Refresh token reuse detected".into(),
+            ));
+        }
+        Some(m) if m.is_expired() => {
+            AuditService::record(
+                NewAuditEvent::failure(AuditEventType::TokenRefresh)
+                    .with_target(m.user_id)
+                    .with_client(&client)
+                    .with_metadata(json!({ "reason": "Refresh token expired" })),
+                conn,
+            )
+            .await;
+            return Err(CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Refresh token expired".into(),
+            ));
         }
+        Some(m) => m,
     };
 
     let user_id = old_token_model.user_id;
 
-    if let Err(e) = TokensService::revoke_token(old_token_model, &txn).await {
-        let _ = txn.rollback().await;
-        return Err(CustomError::new(
-            InternalServerError,
-            format!("Token revoke error: {e}"),
-        ));
-    }
-
-    let (new_raw_refresh, _new_row) = match tokens_service
-        .create_refresh_token_for_user_txn(user_id, config_service().refresh_token_max_age, &txn)
+    let (new_raw_refresh, new_row) = match tokens_service
+        .create_refresh_token_for_user_txn(
+            &old_token_model,
+            config_service().refresh_token_max_age,
+            &txn,
+        )
         .await
     {
         Ok(v) => v,
@@ -75,8 +135,38 @@ pub async fn refresh_logic(
         }
     };
 
-    let (roles, perms) = match compute_roles_and_permissions(conn, user_id).await {
-        Ok((r, p)) => (r, p),
+    // The row is locked, but a rotation must never succeed twice
+    match TokensService::revoke_token(old_token_model, &txn).await {
+        Ok(true) => {}
+        Ok(false) => {
+            let _ = txn.rollback().await;
+            return Err(CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Invalid refresh token".into(),
+            ));
+        }
+        Err(e) => {
+            let _ = txn.rollback().await;
+            return Err(CustomError::new(
+                InternalServerError,
+                format!("Token revoke error: {e}"),
+            ));
+        }
+    }
+
+    let session = match SessionsService::upsert_for_refresh_token(&new_row, &client, &txn).await {
+        Ok(session) => session,
+        Err(e) => {
+            let _ = txn.rollback().await;
+            return Err(e);
+        }
+    };
+
+    // The organization chosen through switch-organization survives rotation
+    let access = match compute_roles_and_permissions(conn, user_id, session.active_organization_id)
+        .await
+    {
+        Ok(access) => access,
         Err(e) => {
             let _ = txn.rollback().await;
             return Err(CustomError::new(
@@ -98,11 +188,10 @@ pub async fn refresh_logic(
     };
 
     let jwt = match generate_jwt_token(
-        user_id,
+        TokenSubject::User(user_id),
         config_service().access_token_max_age,
-        config_service().access_token_private_key.to_owned(),
-        perms,
-        roles,
+        &KEY_RING,
+        access,
         user.email,
     ) {
         Ok(v) => v,
@@ -115,12 +204,33 @@ pub async fn refresh_logic(
         }
     };
 
+    if let Err(e) = tokens_service
+        .record_access_token(
+            user_id,
+            jwt.token_uuid,
+            new_row.family(),
+            jwt.expires_in.unwrap_or_default(),
+            &txn,
+        )
+        .await
+    {
+        let _ = txn.rollback().await;
+        return Err(e);
+    }
+
     if let Err(e) = txn.commit().await {
         return Err(CustomError::new(
             InternalServerError,
             format!("Txn commit error: {e}"),
         ));
     }
+    AuditService::record(
+        NewAuditEvent::success(AuditEventType::TokenRefresh)
+            .with_user(user_id)
+            .with_client(&client),
+        conn,
+    )
+    .await;
 
     Ok(Some(AuthResponseBody {
         body: BodyToken {
@@ -130,3 +240,34 @@ pub async fn refresh_logic(
         refresh_token: new_raw_refresh,
     }))
 }
+
+/// Revokes the family of a replayed refresh token and records it in the login history.
+async fn handle_refresh_token_reuse(
+    users_service: &UsersService,
+    replayed: Model,
+    ip_address: &str,
+    txn: &DatabaseTransaction,
+) -> Result<(), CustomError> {
+    let revoked = TokensService::revoke_token_family(replayed.family(), txn).await?;
+    SessionsService::end_session_for_family(replayed.family(), txn).await?;
+
+    let user = users_service
+        .find("id", SearchValue::Uuid(replayed.user_id))
+        .await?;
+    let mut active_user: users::ActiveModel = user.into();
+    UsersService::add_details_login(
+        &mut active_user,
+        json!({
+            "timestamp": now_date_time_utc(),
+            "notes": "Refresh token reuse detected, token family revoked",
+            "ip_address": ip_address,
+            "token_family": replayed.family(),
+            "revoked_tokens": revoked,
+        }),
+    );
+    active_user.update(txn).await.map_err(|e| {
+        CustomError::new(InternalServerError, format!("Failed to update user: {e}"))
+    })?;
+
+    Ok(())
+}
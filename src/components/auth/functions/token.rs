@@ -1,186 +1,391 @@
-use base64::engine::general_purpose;
 use base64::Engine;
 use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
-use jsonwebtoken::{self, Algorithm, EncodingKey, Header};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{self, Algorithm, Header};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use std::collections::HashSet;
-use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, JoinType, QueryFilter, QuerySelect, RelationTrait};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement};
 
-use crate::entity::{
-    permissions, role_permissions, roles, user_permission_overrides, user_roles, users,
-};
+use crate::components::auth::functions::{KeyRing, PERMISSION_CACHE};
+use crate::config_service;
+use crate::entity::users::{self, UserInfoResponse};
+use auth::permission_matching::has_permission;
+
+/// Who an access token was issued to: a person, or a service via client_credentials.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum TokenSubject {
+    User(Uuid),
+    Client(String),
+}
+
+impl TokenSubject {
+    pub fn user_id(&self) -> Option<Uuid> {
+        match self {
+            TokenSubject::User(user_id) => Some(*user_id),
+            TokenSubject::Client(_) => None,
+        }
+    }
+
+    /// Value of the `sub` claim
+    pub fn sub(&self) -> String {
+        match self {
+            TokenSubject::User(user_id) => user_id.to_string(),
+            TokenSubject::Client(client_id) => client_id.clone(),
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TokenDetails {
-    pub user_id: Uuid,
+    pub subject: TokenSubject,
     pub token_uuid: Uuid,
     pub expires_in: Option<i64>,
     pub token: Option<String>,
+    pub perms: Vec<String>,
+}
+
+impl TokenDetails {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        has_permission(&self.perms, permission)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TokenClaims {
+    pub iss: String,
+    pub aud: String,
     pub sub: String,
     pub token_uuid: String,
     pub perms: Vec<String>,
     pub roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub email: String,
+    /// Set only on client_credentials tokens, where `sub` is the client id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Active organization, the roles held there are in `org_roles` and `org_perms`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+    /// Roles held in the active organization, they are not in `roles`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub org_roles: Vec<String>,
+    /// What the `org_roles` grant inside the active organization only, never checked
+    /// by `require_permissions`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub org_perms: Vec<String>,
+    /// Every organization the user is a member of
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub orgs: Vec<String>,
     pub exp: i64,
     pub iat: i64,
     pub nbf: i64,
 }
 
+/// Global roles of the user (base role and `user_roles`) and the permissions they grant, the
+/// roles held in organization `$2` and the permissions those grant, plus the user's
+/// organizations, as `(kind, code)` rows. Organization roles never feed `perms`, so a role
+/// held in one organization grants nothing outside of it.
+/// Assignments and overrides count only inside their validity window. A role inherits every
+/// base role (the `user_role` values) of lower `priority`; custom roles are never inherited,
+/// so they reach only the users who hold them. Wildcard grants and overrides (`*`,
+/// `resource.*`) are expanded into the concrete codes they cover, then allow overrides are
+/// added and deny overrides removed. Deny overrides apply inside organizations too.
+const RESOLVE_ACCESS_SQL: &str = r#"
+WITH user_role_codes AS (
+    SELECT u.role::text AS code FROM auth.users u WHERE u.id = $1
+    UNION
+    SELECT r.code FROM auth.user_roles ur
+    JOIN auth.roles r ON r.id = ur.role_id
+    WHERE ur.user_id = $1
+      AND (ur.valid_from IS NULL OR ur.valid_from <= now())
+      AND (ur.valid_until IS NULL OR ur.valid_until > now())
+),
+org_role_codes AS (
+    SELECT r.code FROM auth.organization_member_roles omr
+    JOIN auth.roles r ON r.id = omr.role_id
+    WHERE omr.user_id = $1 AND omr.organization_id = $2
+),
+base_roles AS (
+    SELECT r.id, r.priority FROM auth.roles r
+    WHERE r.code = ANY (enum_range(NULL::public.user_role)::text[])
+),
+effective_roles AS (
+    SELECT r.id FROM auth.roles r
+    JOIN user_role_codes urc ON urc.code = r.code
+    UNION
+    SELECT b.id FROM base_roles b
+    WHERE b.priority < (
+        SELECT max(r.priority) FROM auth.roles r
+        JOIN user_role_codes urc ON urc.code = r.code
+    )
+),
+org_effective_roles AS (
+    SELECT r.id FROM auth.roles r
+    JOIN org_role_codes orc ON orc.code = r.code
+    UNION
+    SELECT b.id FROM base_roles b
+    WHERE b.priority < (
+        SELECT max(r.priority) FROM auth.roles r
+        JOIN org_role_codes orc ON orc.code = r.code
+    )
+),
+granted AS (
+    SELECT p.code FROM effective_roles er
+    JOIN auth.role_permissions rp ON rp.role_id = er.id
+    JOIN auth.permissions p ON p.id = rp.permission_id
+    UNION
+    SELECT p.code FROM auth.user_permission_overrides o
+    JOIN auth.permissions p ON p.id = o.permission_id
+    WHERE o.user_id = $1 AND o.allow
+      AND (o.valid_from IS NULL OR o.valid_from <= now())
+      AND (o.valid_until IS NULL OR o.valid_until > now())
+),
+org_granted AS (
+    SELECT p.code FROM org_effective_roles oer
+    JOIN auth.role_permissions rp ON rp.role_id = oer.id
+    JOIN auth.permissions p ON p.id = rp.permission_id
+),
+denied AS (
+    SELECT p.code FROM auth.user_permission_overrides o
+    JOIN auth.permissions p ON p.id = o.permission_id
+    WHERE o.user_id = $1 AND NOT o.allow
+      AND (o.valid_from IS NULL OR o.valid_from <= now())
+      AND (o.valid_until IS NULL OR o.valid_until > now())
+),
+concrete AS (
+    SELECT p.code FROM auth.permissions p
+    WHERE p.code <> '*' AND right(p.code, 2) <> '.*'
+),
+concrete_denied AS (
+    SELECT c.code FROM concrete c JOIN denied d ON
+        d.code = '*' OR d.code = c.code
+        OR (right(d.code, 2) = '.*' AND left(c.code, length(d.code) - 1) = left(d.code, -1))
+)
+SELECT 'role' AS kind, code FROM user_role_codes
+UNION ALL
+SELECT 'org_role' AS kind, code FROM org_role_codes
+UNION ALL
+SELECT 'org' AS kind, om.organization_id::text AS code FROM auth.organization_members om
+WHERE om.user_id = $1
+UNION ALL
+SELECT 'perm' AS kind, code FROM (
+    SELECT c.code FROM concrete c JOIN granted g ON
+        g.code = '*' OR g.code = c.code
+        OR (right(g.code, 2) = '.*' AND left(c.code, length(g.code) - 1) = left(g.code, -1))
+    EXCEPT
+    SELECT code FROM concrete_denied
+) effective
+UNION ALL
+SELECT 'org_perm' AS kind, code FROM (
+    SELECT c.code FROM concrete c JOIN org_granted g ON
+        g.code = '*' OR g.code = c.code
+        OR (right(g.code, 2) = '.*' AND left(c.code, length(g.code) - 1) = left(g.code, -1))
+    EXCEPT
+    SELECT code FROM concrete_denied
+) org_effective
+ORDER BY kind, code
+"#;
+
+/// What an access token carries: roles and permissions, and the organizations behind them
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ResolvedAccess {
+    pub roles: Vec<String>,
+    pub perms: Vec<String>,
+    /// Roles held in the active organization, and what they grant there
+    pub org_roles: Vec<String>,
+    pub org_perms: Vec<String>,
+    /// `None` when no organization was asked for or the user is not a member of it
+    pub org_id: Option<Uuid>,
+    pub orgs: Vec<Uuid>,
+}
+
+/// Access of the user with `org_id` active, served from `PERMISSION_CACHE` when
+/// possible, otherwise resolved in one query.
 pub async fn compute_roles_and_permissions(
     db: &DatabaseConnection,
     user_id: Uuid,
-) -> Result<(Vec<String>, Vec<String>), sea_orm::DbErr> {
-    let mut roles_set: HashSet<String> = HashSet::new();
-    let mut perms_set: HashSet<String> = HashSet::new();
-
-    // Base role from users.role
-    if let Some(user) = users::Entity::find_by_id(user_id).one(db).await? {
-        roles_set.insert(user.role.as_str().to_string());
-    }
+    org_id: Option<Uuid>,
+) -> Result<ResolvedAccess, sea_orm::DbErr> {
+    let generation = match PERMISSION_CACHE.get(user_id, org_id) {
+        Ok(cached) => return Ok(cached),
+        Err(generation) => generation,
+    };
 
-    // Roles via user_roles
-    let assigned_roles = roles::Entity::find()
-        .join(JoinType::InnerJoin, roles::Relation::UserRoles.def())
-        .filter(user_roles::Column::UserId.eq(user_id))
-        .all(db)
-        .await?;
-    for r in assigned_roles {
-        roles_set.insert(r.code);
-    }
+    let access = resolve_access(db, user_id, org_id).await?;
+    PERMISSION_CACHE.insert(user_id, org_id, generation, &access);
+    Ok(access)
+}
 
-    // Permissions via user_roles -> role_permissions -> permissions
-    let perms_models_via_user_roles = permissions::Entity::find()
-        .join(JoinType::InnerJoin, permissions::Relation::RolePermissions.def())
-        .join(JoinType::InnerJoin, role_permissions::Relation::Roles.def())
-        .join(JoinType::InnerJoin, roles::Relation::UserRoles.def())
-        .filter(user_roles::Column::UserId.eq(user_id))
-        .all(db)
+/// Access of the user with `org_id` active, straight from the database
+async fn resolve_access(
+    db: &DatabaseConnection,
+    user_id: Uuid,
+    org_id: Option<Uuid>,
+) -> Result<ResolvedAccess, sea_orm::DbErr> {
+    let rows = db
+        .query_all(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            RESOLVE_ACCESS_SQL,
+            [user_id.into(), org_id.into()],
+        ))
         .await?;
-    for p in perms_models_via_user_roles {
-        perms_set.insert(p.code);
-    }
 
-    // Permissions via base role (if mapped in roles table)
-    for base_role in roles_set.clone().into_iter() {
-        let base_perm_models = permissions::Entity::find()
-            .join(JoinType::InnerJoin, permissions::Relation::RolePermissions.def())
-            .join(JoinType::InnerJoin, role_permissions::Relation::Roles.def())
-            .filter(roles::Column::Code.eq(base_role))
-            .all(db)
-            .await?;
-        for p in base_perm_models {
-            perms_set.insert(p.code);
+    let mut access = ResolvedAccess::default();
+    for row in rows {
+        let kind: String = row.try_get("", "kind")?;
+        let code: String = row.try_get("", "code")?;
+        match kind.as_str() {
+            "role" => access.roles.push(code),
+            "org_role" => access.org_roles.push(code),
+            "org_perm" => access.org_perms.push(code),
+            "org" => access.orgs.extend(Uuid::parse_str(&code).ok()),
+            _ => access.perms.push(code),
         }
     }
-
-    // Overrides: allow
-    let allow_models = permissions::Entity::find()
-        .join(
-            JoinType::InnerJoin,
-            permissions::Relation::UserPermissionOverrides.def(),
-        )
-        .filter(user_permission_overrides::Column::UserId.eq(user_id))
-        .filter(user_permission_overrides::Column::Allow.eq(true))
-        .all(db)
-        .await?;
-    for p in allow_models {
-        perms_set.insert(p.code);
-    }
-
-    // Overrides: deny
-    let deny_models = permissions::Entity::find()
-        .join(
-            JoinType::InnerJoin,
-            permissions::Relation::UserPermissionOverrides.def(),
-        )
-        .filter(user_permission_overrides::Column::UserId.eq(user_id))
-        .filter(user_permission_overrides::Column::Allow.eq(false))
-        .all(db)
-        .await?;
-    for p in deny_models {
-        perms_set.remove(&p.code);
-    }
-
-    let mut roles: Vec<String> = roles_set.into_iter().collect();
-    roles.sort();
-    let mut perms: Vec<String> = perms_set.into_iter().collect();
-    perms.sort();
-
-    Ok((roles, perms))
+    access.org_id = org_id.filter(|org_id| access.orgs.contains(org_id));
+    Ok(access)
 }
 
 pub fn generate_jwt_token(
-    user_id: Uuid,
+    subject: TokenSubject,
     ttl: i64,
-    private_key: String,
-    perms: Vec<String>,
-    roles: Vec<String>,
+    key_ring: &KeyRing,
+    access: ResolvedAccess,
     email: String,
 ) -> Result<TokenDetails, jsonwebtoken::errors::Error> {
-    let bytes_private_key = general_purpose::STANDARD.decode(private_key).unwrap();
-    let decoded_private_key = String::from_utf8(bytes_private_key).unwrap();
-
     let now = chrono::Utc::now();
     let mut token_details = TokenDetails {
-        user_id,
+        subject,
         token_uuid: Uuid::new_v4(),
         expires_in: Some((now + chrono::Duration::minutes(ttl)).timestamp()),
         token: None,
+        perms: access.perms.clone(),
     };
 
+    let config = config_service();
     let claims = TokenClaims {
-        sub: token_details.user_id.to_string(),
+        iss: config.jwt_issuer,
+        aud: config.jwt_audience,
+        sub: token_details.subject.sub(),
         token_uuid: token_details.token_uuid.to_string(),
         email,
-        perms,
-        roles,
+        client_id: match &token_details.subject {
+            TokenSubject::Client(client_id) => Some(client_id.clone()),
+            TokenSubject::User(_) => None,
+        },
+        org_id: access.org_id.map(|org_id| org_id.to_string()),
+        orgs: access.orgs.iter().map(Uuid::to_string).collect(),
+        org_roles: access.org_roles,
+        org_perms: access.org_perms,
+        perms: access.perms,
+        roles: access.roles,
         exp: token_details.expires_in.unwrap(),
         iat: now.timestamp(),
         nbf: now.timestamp(),
     };
 
-    let header = Header::new(Algorithm::RS256);
-    let token = jsonwebtoken::encode(
-        &header,
-        &claims,
-        &EncodingKey::from_rsa_pem(decoded_private_key.as_bytes())?,
-    )?;
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(key_ring.signing_kid().to_string());
+    let token = jsonwebtoken::encode(&header, &claims, key_ring.encoding_key())?;
     token_details.token = Some(token);
     Ok(token_details)
 }
 
-pub fn verify_jwt_token(
-    public_key: String,
+/// Claims of an OpenID Connect ID token (OpenID Connect Core 1.0, section 2), with the
+/// standard user claims of userinfo.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    /// The client the ID token was issued to
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub user: UserInfoResponse,
+}
+
+pub fn generate_id_token(
+    user: &users::Model,
+    client_id: &str,
+    nonce: Option<String>,
+    ttl: i64,
+    key_ring: &KeyRing,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = chrono::Utc::now();
+    let claims = IdTokenClaims {
+        iss: config_service().jwt_issuer,
+        aud: client_id.to_string(),
+        exp: (now + chrono::Duration::minutes(ttl)).timestamp(),
+        iat: now.timestamp(),
+        nonce,
+        user: UserInfoResponse::from(user.clone()),
+    };
+
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(key_ring.signing_kid().to_string());
+    jsonwebtoken::encode(&header, &claims, key_ring.encoding_key())
+}
+
+/// Validates signature, issuer and audience and returns the raw claims.
+pub fn decode_jwt_claims(
+    key_ring: &KeyRing,
     token: &str,
-) -> Result<TokenDetails, jsonwebtoken::errors::Error> {
-    let bytes_public_key = general_purpose::STANDARD.decode(public_key)?;
-    let decoded_public_key = String::from_utf8(bytes_public_key)?;
+) -> Result<TokenClaims, jsonwebtoken::errors::Error> {
+    let header = jsonwebtoken::decode_header(token)?;
+    let decoding_key = key_ring
+        .decoding_key(header.kid.as_deref())
+        .ok_or(ErrorKind::InvalidKeyFormat)?;
 
-    let validation = jsonwebtoken::Validation::new(Algorithm::RS256);
+    let config = config_service();
+    let mut validation = jsonwebtoken::Validation::new(Algorithm::RS256);
+    validation.set_issuer(&[config.jwt_issuer]);
+    validation.set_audience(&[config.jwt_audience]);
 
-    let decoded = jsonwebtoken::decode::<TokenClaims>(
-        token,
-        &jsonwebtoken::DecodingKey::from_rsa_pem(decoded_public_key.as_bytes())?,
-        &validation,
-    )?;
+    Ok(jsonwebtoken::decode::<TokenClaims>(token, decoding_key, &validation)?.claims)
+}
 
-    let user_id = Uuid::parse_str(decoded.claims.sub.as_str()).unwrap();
-    let token_uuid = Uuid::parse_str(decoded.claims.token_uuid.as_str()).unwrap();
+impl TokenClaims {
+    pub fn subject(&self) -> Result<TokenSubject, jsonwebtoken::errors::Error> {
+        match &self.client_id {
+            Some(client_id) => Ok(TokenSubject::Client(client_id.clone())),
+            None => Uuid::parse_str(self.sub.as_str())
+                .map(TokenSubject::User)
+                .map_err(|_| ErrorKind::InvalidSubject.into()),
+        }
+    }
+}
 
-    Ok(TokenDetails {
-        token: None,
-        token_uuid,
-        user_id,
-        expires_in: None,
-    })
+impl TryFrom<&TokenClaims> for TokenDetails {
+    type Error = jsonwebtoken::errors::Error;
+
+    fn try_from(claims: &TokenClaims) -> Result<Self, Self::Error> {
+        let subject = claims.subject()?;
+        let token_uuid =
+            Uuid::parse_str(claims.token_uuid.as_str()).map_err(|_| ErrorKind::InvalidToken)?;
+
+        Ok(TokenDetails {
+            token: None,
+            token_uuid,
+            subject,
+            expires_in: Some(claims.exp),
+            perms: claims.perms.clone(),
+        })
+    }
+}
+
+/// Validates the token and returns both the raw claims and the parsed details.
+pub fn verify_jwt_token(
+    key_ring: &KeyRing,
+    token: &str,
+) -> Result<(TokenClaims, TokenDetails), jsonwebtoken::errors::Error> {
+    let claims = decode_jwt_claims(key_ring, token)?;
+    let details = TokenDetails::try_from(&claims)?;
+    Ok((claims, details))
 }
 // helper: generate opaque refresh (raw + hash)
 pub fn hash_refresh(raw: &str) -> String {
@@ -195,3 +400,200 @@ pub fn generate_opaque_refresh() -> (String, String) {
     let hash = hash_refresh(&raw);
     (raw, hash)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::auth::functions::KEY_RING;
+    use crate::utils::test_db;
+    use sea_orm::EntityTrait;
+
+    async fn insert_role(db: &DatabaseConnection, priority: i32) -> Uuid {
+        let role_id = Uuid::new_v4();
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            INSERT INTO auth.roles (id, code, priority, created_at, updated_at)
+            VALUES ($1, $2, $3, now(), now())
+            "#,
+            [role_id.into(), format!("TEST_{role_id}").into(), priority.into()],
+        ))
+        .await
+        .unwrap();
+        role_id
+    }
+
+    async fn insert_granted_permission(db: &DatabaseConnection, role_id: Uuid) -> String {
+        let code = format!("test.{role_id}");
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            WITH permission AS (
+                INSERT INTO auth.permissions (id, code, created_at, updated_at)
+                VALUES (gen_random_uuid(), $1, now(), now())
+                RETURNING id
+            )
+            INSERT INTO auth.role_permissions (role_id, permission_id)
+            SELECT $2, id FROM permission
+            "#,
+            [code.clone().into(), role_id.into()],
+        ))
+        .await
+        .unwrap();
+        code
+    }
+
+    async fn cleanup(db: &DatabaseConnection, role_ids: &[Uuid]) {
+        for role_id in role_ids {
+            db.execute(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "DELETE FROM auth.permissions WHERE code = $1",
+                [format!("test.{role_id}").into()],
+            ))
+            .await
+            .unwrap();
+            db.execute(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "DELETE FROM auth.roles WHERE id = $1",
+                [(*role_id).into()],
+            ))
+            .await
+            .unwrap();
+        }
+    }
+
+    #[actix_rt::test]
+    async fn custom_role_is_not_inherited_by_base_roles() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        // Priority 0 is below every base role but GUEST
+        let custom_role = insert_role(&db, 0).await;
+        let custom_code = insert_granted_permission(&db, custom_role).await;
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let admin_id = test_db::insert_user(&db, "ADMIN").await;
+
+        let user_access = resolve_access(&db, user_id, None).await.unwrap();
+        let admin_access = resolve_access(&db, admin_id, None).await.unwrap();
+
+        test_db::delete_user(&db, user_id).await;
+        test_db::delete_user(&db, admin_id).await;
+        cleanup(&db, &[custom_role]).await;
+        assert!(!user_access.perms.contains(&custom_code));
+        assert!(!admin_access.perms.contains(&custom_code));
+        assert_eq!(user_access.roles, vec!["USER".to_string()]);
+    }
+
+    #[actix_rt::test]
+    async fn role_inherits_base_roles_of_lower_priority() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        // Between USER (10) and OPERATOR (30)
+        let custom_role = insert_role(&db, 20).await;
+        let guest_id = test_db::insert_user(&db, "GUEST").await;
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let operator_id = test_db::insert_user(&db, "OPERATOR").await;
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "INSERT INTO auth.user_roles (user_id, role_id) VALUES ($1, $2)",
+            [guest_id.into(), custom_role.into()],
+        ))
+        .await
+        .unwrap();
+
+        let access = resolve_access(&db, guest_id, None).await.unwrap();
+        let user_access = resolve_access(&db, user_id, None).await.unwrap();
+        let operator_access = resolve_access(&db, operator_id, None).await.unwrap();
+
+        for id in [guest_id, user_id, operator_id] {
+            test_db::delete_user(&db, id).await;
+        }
+        cleanup(&db, &[custom_role]).await;
+        assert!(user_access.perms.iter().all(|perm| access.perms.contains(perm)));
+        assert!(operator_access.perms.iter().any(|perm| !access.perms.contains(perm)));
+        // Inherited roles grant permissions but are not listed as the user's roles
+        assert_eq!(access.roles, vec!["GUEST".to_string(), format!("TEST_{custom_role}")]);
+    }
+
+    #[actix_rt::test]
+    async fn organization_roles_grant_only_org_perms() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        let org_role = insert_role(&db, 0).await;
+        let org_code = insert_granted_permission(&db, org_role).await;
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let organization_id = Uuid::new_v4();
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "INSERT INTO auth.organizations (id, slug, name) VALUES ($1, $1::text, 'Test')",
+            [organization_id.into()],
+        ))
+        .await
+        .unwrap();
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "INSERT INTO auth.organization_members (organization_id, user_id) VALUES ($1, $2)",
+            [organization_id.into(), user_id.into()],
+        ))
+        .await
+        .unwrap();
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            INSERT INTO auth.organization_member_roles (organization_id, user_id, role_id)
+            VALUES ($1, $2, $3)
+            "#,
+            [organization_id.into(), user_id.into(), org_role.into()],
+        ))
+        .await
+        .unwrap();
+
+        let access = resolve_access(&db, user_id, Some(organization_id)).await.unwrap();
+
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "DELETE FROM auth.organizations WHERE id = $1",
+            [organization_id.into()],
+        ))
+        .await
+        .unwrap();
+        test_db::delete_user(&db, user_id).await;
+        cleanup(&db, &[org_role]).await;
+        assert_eq!(access.org_id, Some(organization_id));
+        assert_eq!(access.org_roles, vec![format!("TEST_{org_role}")]);
+        assert_eq!(access.org_perms, vec![org_code.clone()]);
+        assert_eq!(access.roles, vec!["USER".to_string()]);
+        assert!(!access.perms.contains(&org_code));
+    }
+
+    #[actix_rt::test]
+    async fn id_token_is_signed_for_the_client() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        test_db::configure_env();
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let user = users::Entity::find_by_id(user_id).one(&db).await.unwrap().unwrap();
+
+        let id_token =
+            generate_id_token(&user, "dashboard-spa", Some("n-0S6".to_string()), 15, &KEY_RING)
+                .unwrap();
+        test_db::delete_user(&db, user_id).await;
+
+        let kid = jsonwebtoken::decode_header(&id_token).unwrap().kid;
+        let mut validation = jsonwebtoken::Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[config_service().jwt_issuer]);
+        validation.set_audience(&["dashboard-spa"]);
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(
+            &id_token,
+            KEY_RING.decoding_key(kid.as_deref()).unwrap(),
+            &validation,
+        )
+        .unwrap()
+        .claims;
+        assert_eq!(claims.user.sub, user_id.to_string());
+        assert_eq!(claims.nonce.as_deref(), Some("n-0S6"));
+    }
+}
@@ -1,15 +1,21 @@
 use super::services::AuthService;
-use crate::components::auth::functions::verify_jwt_token;
+use crate::components::auth::functions::{
+    clear_refresh_cookie, openid_configuration,
+    refresh_cookie, AuthUser, LoginOutcome, KEY_RING,
+};
 use crate::components::auth::local_enum::Info;
 use crate::components::config::ConfigService;
-use crate::config_service;
-use crate::entity::tokens::{IntrospectRequest, IntrospectResponse};
-use crate::entity::users::AuthRequestBody;
+use crate::components::sessions::client_info;
+use crate::components::oauth::{client_credentials, OAuthError, OAuthService};
+use crate::entity::organizations::SwitchOrganizationRequestBody;
+use crate::entity::tokens::IntrospectRequest;
+use crate::entity::user_mfa::{MfaVerifyRequestBody, TotpCodeRequestBody};
+use crate::entity::users::{AuthRequestBody, ForgotPasswordRequestBody, ResetPasswordRequestBody};
 use crate::http_response::error_handler::{CustomError, ValidatedJson};
 use crate::http_response::{http_response_builder, HttpCodeW};
-use actix_web::cookie::{time, Cookie, SameSite};
 use actix_web::dev::ConnectionInfo;
-use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use actix_web::http::header;
+use actix_web::{get, post, web, Either, HttpRequest, HttpResponse};
 use crate::http_response::prepared_response::check_response_ok_or_return_error;
 
 #[post("/auth/register")]
@@ -29,21 +35,15 @@ pub async fn register(
 pub async fn refresh(
     req: HttpRequest,
     service: web::Data<AuthService>,
-    _conn_info: ConnectionInfo,
 ) -> Result<HttpResponse, CustomError> {
-    let refresh = service.refresh(req.cookie("refresh_token"));
+    let refresh = service.refresh(req.cookie("refresh_token"), client_info(&req));
     match refresh.await {
         Ok(user) => {
             let user = user.unwrap();
             let response = http_response_builder::ok(user.body);
-            let refresh_cookie = Cookie::build("refresh_token", user.refresh_token)
-                .path("/")
-                .max_age(time::Duration::days(config_service().refresh_token_max_age))
-                .same_site(SameSite::None)
-                .http_only(true)
-                .secure(true)
-                .finish();
-            Ok(HttpResponse::Ok().cookie(refresh_cookie).json(response))
+            Ok(HttpResponse::Ok()
+                .cookie(refresh_cookie(user.refresh_token))
+                .json(response))
         }
         Err(err) => Err(err),
     }
@@ -51,28 +51,112 @@ pub async fn refresh(
 
 #[post("/auth/login")]
 pub async fn login(
+    req: HttpRequest,
     payload: ValidatedJson<AuthRequestBody>,
     service: web::Data<AuthService>,
-    _conn_info: ConnectionInfo,
 ) -> Result<HttpResponse, CustomError> {
-    let registration = service.login(payload.0, _conn_info).await;
+    let registration = service.login(payload.0, client_info(&req)).await;
     match registration {
-        Ok(payload_auth) => {
-            let payload_auth = payload_auth.unwrap();
+        Ok(Some(LoginOutcome::Authenticated(payload_auth))) => {
             let response = http_response_builder::ok(payload_auth.body);
-            let refresh_cookie = Cookie::build("refresh_token", payload_auth.refresh_token)
-                .path("/")
-                .max_age(time::Duration::days(config_service().refresh_token_max_age))
-                .same_site(SameSite::None)
-                .http_only(true)
-                .secure(true)
-                .finish();
-            Ok(HttpResponse::Ok().cookie(refresh_cookie).json(response))
+            Ok(HttpResponse::Ok()
+                .cookie(refresh_cookie(payload_auth.refresh_token))
+                .json(response))
+        }
+        Ok(Some(LoginOutcome::MfaRequired(challenge))) => {
+            Ok(HttpResponse::Ok().json(http_response_builder::ok(challenge)))
         }
+        Ok(None) => Err(CustomError::new(
+            HttpCodeW::InternalServerError,
+            "Login produced no result".to_string(),
+        )),
         Err(err) => Err(err),
     }
 }
 
+#[post("/auth/mfa/verify")]
+pub async fn mfa_verify(
+    req: HttpRequest,
+    payload: ValidatedJson<MfaVerifyRequestBody>,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    let payload_auth = service.mfa_verify(payload.0, client_info(&req)).await?;
+    Ok(HttpResponse::Ok()
+        .cookie(refresh_cookie(payload_auth.refresh_token))
+        .json(http_response_builder::ok(payload_auth.body)))
+}
+
+#[post("/auth/mfa/totp/enroll")]
+pub async fn totp_enroll(
+    auth: AuthUser,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    check_response_ok_or_return_error(service.enroll_totp(user_id).await)
+}
+
+#[post("/auth/mfa/totp/enable")]
+pub async fn totp_enable(
+    auth: AuthUser,
+    payload: ValidatedJson<TotpCodeRequestBody>,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    check_response_ok_or_return_error(service.enable_totp(user_id, &payload.0.code).await)
+}
+
+#[post("/auth/mfa/totp/disable")]
+pub async fn totp_disable(
+    auth: AuthUser,
+    payload: ValidatedJson<TotpCodeRequestBody>,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    check_response_ok_or_return_error(service.disable_totp(user_id, &payload.0.code).await)
+}
+
+#[post("/auth/logout")]
+pub async fn logout(
+    req: HttpRequest,
+    auth: Option<AuthUser>,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    let refresh_token = req.cookie("refresh_token").map(|c| c.value().to_string());
+    let access_token = auth.map(|auth| auth.details);
+    let message = service
+        .logout(refresh_token, access_token, client_info(&req))
+        .await?;
+    Ok(HttpResponse::Ok()
+        .cookie(clear_refresh_cookie())
+        .json(http_response_builder::ok(message)))
+}
+
+#[post("/auth/logout-all")]
+pub async fn logout_all(
+    req: HttpRequest,
+    auth: AuthUser,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    let message = service.logout_all(auth.details, client_info(&req)).await?;
+    Ok(HttpResponse::Ok()
+        .cookie(clear_refresh_cookie())
+        .json(http_response_builder::ok(message)))
+}
+
+#[post("/auth/switch-organization")]
+pub async fn switch_organization(
+    req: HttpRequest,
+    auth: AuthUser,
+    payload: ValidatedJson<SwitchOrganizationRequestBody>,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    let refresh_token = req.cookie("refresh_token").map(|c| c.value().to_string());
+    let switched = service
+        .switch_organization(auth.details, refresh_token, payload.0.organization_id)
+        .await;
+    check_response_ok_or_return_error(switched)
+}
+
 #[get("/auth/verify/{token}")]
 pub async fn verify_email(
     info: web::Path<Info>,
@@ -86,29 +170,80 @@ pub async fn verify_email(
     check_response_ok_or_return_error(verified)
 }
 
+#[post("/auth/password/forgot")]
+pub async fn forgot_password(
+    payload: ValidatedJson<ForgotPasswordRequestBody>,
+    service: web::Data<AuthService>,
+    service_config: web::Data<ConfigService>,
+) -> Result<HttpResponse, CustomError> {
+    let requested = service.forgot_password(payload.0, &service_config).await;
+    check_response_ok_or_return_error(requested)
+}
+
+#[post("/auth/password/reset")]
+pub async fn reset_password(
+    payload: ValidatedJson<ResetPasswordRequestBody>,
+    service: web::Data<AuthService>,
+    conn_info: ConnectionInfo,
+) -> Result<HttpResponse, CustomError> {
+    let reset = service.reset_password(payload.0, conn_info).await;
+    check_response_ok_or_return_error(reset)
+}
+
+/// RFC 7662: always 200, inactive tokens are `{"active": false}`
 #[post("/auth/introspect")]
 pub async fn introspect(
-    payload: web::Json<IntrospectRequest>,
+    req: HttpRequest,
+    payload: Either<web::Form<IntrospectRequest>, web::Json<IntrospectRequest>>,
+    service: web::Data<AuthService>,
+    oauth_service: web::Data<OAuthService>,
+) -> Result<HttpResponse, OAuthError> {
+    let payload = match payload {
+        Either::Left(form) => form.into_inner(),
+        Either::Right(json) => json.into_inner(),
+    };
+    let (client_id, client_secret) = client_credentials(
+        &req,
+        payload.client_id.as_deref(),
+        payload.client_secret.as_deref(),
+    )
+    .ok_or_else(|| OAuthError::invalid_client("Missing client credentials"))?;
+    oauth_service
+        .authenticate_confidential_client(&client_id, client_secret.as_deref())
+        .await?;
+
+    let response = service
+        .introspect(&payload.token, payload.token_type_hint.as_deref())
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(response))
+}
+
+#[get("/auth/userinfo")]
+pub async fn userinfo(
+    auth: AuthUser,
+    service: web::Data<AuthService>,
 ) -> Result<HttpResponse, CustomError> {
-    let public_key_b64 = config_service().access_token_public_key.clone();
-    match verify_jwt_token(public_key_b64, &payload.token) {
-        Ok(details) => Ok(HttpResponse::Ok().json(IntrospectResponse {
-            active: true,
-            sub: Some(details.user_id.to_string()),
-            token_uuid: Some(details.token_uuid.to_string()),
-        })),
-        Err(_) => Err(CustomError::new(
-            HttpCodeW::Unauthorized,
-            format!(
-                "{:?}",
-                IntrospectResponse {
-                    active: false,
-                    sub: None,
-                    token_uuid: None,
-                }
-            ),
-        )),
-    }
+    let user_id = auth.user_id()?;
+    let info = service.userinfo(user_id).await?;
+    Ok(HttpResponse::Ok().json(info))
+}
+
+#[get("/openid-configuration")]
+pub async fn openid_configuration_document(
+    service_config: web::Data<ConfigService>,
+) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(openid_configuration(&service_config))
+}
+
+#[get("/jwks.json")]
+pub async fn jwks() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(KEY_RING.jwks())
 }
 
 pub fn init_routes(config: &mut web::ServiceConfig) {
@@ -116,5 +251,20 @@ pub fn init_routes(config: &mut web::ServiceConfig) {
     config.service(login);
     config.service(verify_email);
     config.service(refresh);
+    config.service(logout);
+    config.service(logout_all);
+    config.service(switch_organization);
+    config.service(mfa_verify);
+    config.service(totp_enroll);
+    config.service(totp_enable);
+    config.service(totp_disable);
     config.service(introspect);
+    config.service(forgot_password);
+    config.service(reset_password);
+    config.service(userinfo);
+}
+
+pub fn init_well_known_routes(config: &mut web::ServiceConfig) {
+    config.service(jwks);
+    config.service(openid_configuration_document);
 }
@@ -1,15 +1,34 @@
-use crate::components::auth::functions::{login_logic, refresh_logic};
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::{
+    admin_create_user_logic, change_password_logic, confirm_email_change_logic,
+    disable_totp_logic, enable_totp_logic, enroll_totp_logic, force_password_reset_logic,
+    forgot_password_logic, introspect_logic, login_logic, logout_all_logic, logout_logic,
+    mfa_verify_logic, refresh_logic, request_email_change_logic, reset_password_logic,
+    switch_organization_logic, update_profile_logic, update_user_status_logic, LoginOutcome,
+    TokenDetails,
+};
 use crate::components::config::ConfigService;
 use crate::components::mail_send::MailSendService;
 use crate::components::tokens::TokensService;
 use crate::components::users::enums::SearchValue;
 use crate::components::users::UsersService;
-use crate::entity::users::{AuthRequestBody, AuthResponseBody, RegisterResponseBody};
+use crate::entity::enums::AuditEventType;
+use crate::entity::sessions::ClientInfo;
+use crate::entity::tokens::IntrospectResponse;
+use crate::entity::user_mfa::{MfaVerifyRequestBody, RecoveryCodesResponse, TotpEnrollmentResponse};
+use crate::entity::users::{
+    AdminCreateUserRequestBody, AdminUserResponse, AuthRequestBody, AuthResponseBody, BodyToken, ChangeEmailRequestBody, ChangePasswordRequestBody,
+    ForgotPasswordRequestBody, ProfileResponse, RegisterResponseBody, ResetPasswordRequestBody,
+    UpdateProfileRequestBody, UpdateUserStatusRequestBody, UserInfoResponse,
+};
 use crate::http_response::error_handler::CustomError;
 use crate::http_response::HttpCodeW;
+use crate::utils::helpers::validate_password;
 use actix_web::cookie::Cookie;
 use actix_web::dev::ConnectionInfo;
 use sea_orm::{ActiveEnum, DatabaseConnection};
+use serde_json::json;
+use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct AuthService {
@@ -36,12 +55,14 @@ impl AuthService {
     pub async fn refresh(
         &self,
         cookie_refresh_token: Option<Cookie<'_>>,
+        client: ClientInfo,
     ) -> Result<Option<AuthResponseBody>, CustomError> {
         refresh_logic(
             &self.tokens_service,
             &self.users_service,
             &self.conn,
             cookie_refresh_token,
+            client,
         )
         .await
     }
@@ -58,6 +79,10 @@ impl AuthService {
                 "Missing registration data".to_string(),
             )
         })?;
+        let ip_address = conn_info
+            .realip_remote_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
 
         // Check if user with this email already exists
         let existing_user = self
@@ -67,12 +92,23 @@ impl AuthService {
 
         match existing_user {
             // User exists - return conflict error
-            Ok(_) => Err(CustomError::new(
-                HttpCodeW::Conflict,
-                "User with this email already exists".to_string(),
-            )),
+            Ok(existing) => {
+                AuditService::record(
+                    NewAuditEvent::failure(AuditEventType::Register)
+                        .with_target(existing.id)
+                        .with_ip_address(&ip_address)
+                        .with_metadata(json!({ "reason": "Email already registered" })),
+                    &self.conn,
+                )
+                .await;
+                Err(CustomError::new(
+                    HttpCodeW::Conflict,
+                    "User with this email already exists".to_string(),
+                ))
+            }
             // User not found - good, we can create one
             Err(e) if e.error_status_code == HttpCodeW::NotFound => {
+                validate_password(&payload.password, &payload.email, payload.username.as_deref())?;
                 let user_creation_result = self.users_service.create(payload, conn_info).await;
 
                 // Then, process the result of user creation
@@ -85,6 +121,13 @@ impl AuthService {
                         // Now, handle the result of token creation
                         match token_creation_result {
                             Ok(_token) => {
+                                AuditService::record(
+                                    NewAuditEvent::success(AuditEventType::Register)
+                                        .with_user(model.id)
+                                        .with_ip_address(&ip_address),
+                                    &self.conn,
+                                )
+                                .await;
                                 let _ = self.mail_send_service.send_mail(
                                     model.email.clone(),
                                     _token.token.clone(),
@@ -112,18 +155,275 @@ impl AuthService {
     pub async fn login(
         &self,
         payload: AuthRequestBody,
-        conn_info: ConnectionInfo,
-    ) -> Result<Option<AuthResponseBody>, CustomError> {
+        client: ClientInfo,
+    ) -> Result<Option<LoginOutcome>, CustomError> {
         login_logic(
             &self.users_service,
             payload,
-            conn_info,
+            client,
             &self.conn,
             &self.tokens_service,
         )
         .await?
     }
 
+    pub async fn mfa_verify(
+        &self,
+        payload: MfaVerifyRequestBody,
+        client: ClientInfo,
+    ) -> Result<AuthResponseBody, CustomError> {
+        mfa_verify_logic(
+            &self.conn,
+            &self.users_service,
+            &self.tokens_service,
+            payload,
+            &client,
+        )
+        .await
+    }
+
+    pub async fn enroll_totp(&self, user_id: Uuid) -> Result<TotpEnrollmentResponse, CustomError> {
+        let user = self
+            .users_service
+            .find("id", SearchValue::Uuid(user_id))
+            .await?;
+        enroll_totp_logic(&self.conn, &user).await
+    }
+
+    pub async fn enable_totp(
+        &self,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<RecoveryCodesResponse, CustomError> {
+        enable_totp_logic(&self.conn, user_id, code).await
+    }
+
+    pub async fn disable_totp(&self, user_id: Uuid, code: &str) -> Result<String, CustomError> {
+        disable_totp_logic(&self.conn, user_id, code).await
+    }
+
+    pub async fn logout(
+        &self,
+        refresh_token: Option<String>,
+        access_token: Option<TokenDetails>,
+        client: ClientInfo,
+    ) -> Result<String, CustomError> {
+        logout_logic(
+            &self.tokens_service,
+            &self.conn,
+            refresh_token,
+            access_token,
+            &client,
+        )
+        .await
+    }
+
+    pub async fn logout_all(
+        &self,
+        access_token: TokenDetails,
+        client: ClientInfo,
+    ) -> Result<String, CustomError> {
+        logout_all_logic(&self.tokens_service, &self.conn, access_token, &client).await
+    }
+
+    pub async fn switch_organization(
+        &self,
+        caller: TokenDetails,
+        refresh_token: Option<String>,
+        organization_id: Option<Uuid>,
+    ) -> Result<BodyToken, CustomError> {
+        switch_organization_logic(
+            &self.users_service,
+            &self.tokens_service,
+            &self.conn,
+            caller,
+            refresh_token,
+            organization_id,
+        )
+        .await
+    }
+
+    pub async fn forgot_password(
+        &self,
+        payload: ForgotPasswordRequestBody,
+        service_config: &ConfigService,
+    ) -> Result<String, CustomError> {
+        forgot_password_logic(
+            &self.users_service,
+            &self.tokens_service,
+            &self.mail_send_service,
+            payload,
+            service_config,
+        )
+        .await
+    }
+
+    pub async fn reset_password(
+        &self,
+        payload: ResetPasswordRequestBody,
+        conn_info: ConnectionInfo,
+    ) -> Result<String, CustomError> {
+        let ip_address = conn_info
+            .realip_remote_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        reset_password_logic(
+            &self.users_service,
+            &self.tokens_service,
+            &self.conn,
+            payload,
+            ip_address,
+        )
+        .await
+    }
+
+    pub async fn userinfo(&self, user_id: Uuid) -> Result<UserInfoResponse, CustomError> {
+        let user = self
+            .users_service
+            .find("id", SearchValue::Uuid(user_id))
+            .await?;
+        Ok(UserInfoResponse::from(user))
+    }
+
+    pub async fn profile(&self, user_id: Uuid) -> Result<ProfileResponse, CustomError> {
+        let user = self
+            .users_service
+            .find("id", SearchValue::Uuid(user_id))
+            .await?;
+        Ok(ProfileResponse::from(user))
+    }
+
+    pub async fn update_profile(
+        &self,
+        user_id: Uuid,
+        payload: UpdateProfileRequestBody,
+    ) -> Result<ProfileResponse, CustomError> {
+        update_profile_logic(&self.users_service, &self.conn, user_id, payload).await
+    }
+
+    pub async fn change_password(
+        &self,
+        caller: TokenDetails,
+        refresh_token: Option<String>,
+        payload: ChangePasswordRequestBody,
+        ip_address: String,
+    ) -> Result<String, CustomError> {
+        change_password_logic(
+            &self.users_service,
+            &self.tokens_service,
+            &self.conn,
+            caller,
+            refresh_token,
+            payload,
+            ip_address,
+        )
+        .await
+    }
+
+    pub async fn request_email_change(
+        &self,
+        user_id: Uuid,
+        payload: ChangeEmailRequestBody,
+        service_config: &ConfigService,
+    ) -> Result<String, CustomError> {
+        request_email_change_logic(
+            &self.users_service,
+            &self.tokens_service,
+            &self.mail_send_service,
+            &self.conn,
+            user_id,
+            payload,
+            service_config,
+        )
+        .await
+    }
+
+    pub async fn confirm_email_change(
+        &self,
+        token: String,
+        ip_address: String,
+        service_config: &ConfigService,
+    ) -> Result<String, CustomError> {
+        confirm_email_change_logic(
+            &self.users_service,
+            &self.tokens_service,
+            &self.mail_send_service,
+            &self.conn,
+            token,
+            ip_address,
+            service_config,
+        )
+        .await
+    }
+
+    pub async fn admin_user(&self, user_id: Uuid) -> Result<AdminUserResponse, CustomError> {
+        let user = self
+            .users_service
+            .find("id", SearchValue::Uuid(user_id))
+            .await?;
+        Ok(AdminUserResponse::from(user))
+    }
+
+    pub async fn admin_create_user(
+        &self,
+        admin_id: Uuid,
+        payload: AdminCreateUserRequestBody,
+        conn_info: ConnectionInfo,
+    ) -> Result<AdminUserResponse, CustomError> {
+        admin_create_user_logic(&self.users_service, &self.conn, admin_id, payload, conn_info)
+            .await
+    }
+
+    pub async fn update_user_status(
+        &self,
+        admin_id: Uuid,
+        user_id: Uuid,
+        payload: UpdateUserStatusRequestBody,
+    ) -> Result<AdminUserResponse, CustomError> {
+        update_user_status_logic(
+            &self.users_service,
+            &self.tokens_service,
+            &self.conn,
+            admin_id,
+            user_id,
+            payload,
+        )
+        .await
+    }
+
+    pub async fn force_password_reset(
+        &self,
+        admin_id: Uuid,
+        user_id: Uuid,
+        service_config: &ConfigService,
+    ) -> Result<String, CustomError> {
+        force_password_reset_logic(
+            &self.users_service,
+            &self.tokens_service,
+            &self.mail_send_service,
+            &self.conn,
+            admin_id,
+            user_id,
+            service_config,
+        )
+        .await
+    }
+
+    pub async fn introspect(
+        &self,
+        token: &str,
+        token_type_hint: Option<&str>,
+    ) -> Result<IntrospectResponse, CustomError> {
+        introspect_logic(
+            &self.tokens_service,
+            &self.users_service,
+            &self.conn,
+            token,
+            token_type_hint,
+        )
+        .await
+    }
+
     pub async fn verify_email(
         &self,
         token: String,
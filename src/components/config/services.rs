@@ -2,12 +2,18 @@ fn get_env_var(var_name: &str) -> String {
     std::env::var(var_name).unwrap_or_else(|_| panic!("{} must be set", var_name))
 }
 
+fn get_env_var_or(var_name: &str, default: &str) -> String {
+    std::env::var(var_name).unwrap_or_else(|_| default.to_string())
+}
+
 #[derive(Debug, Clone)]
 pub struct ConfigService {
     pub database_url: String,
 
     pub access_token_private_key: String,
     pub access_token_public_key: String,
+    pub access_token_key_id: Option<String>,
+    pub access_token_verification_keys: String,
     pub access_token_expires_in: String,
     pub access_token_max_age: i64,
     pub refresh_token_expires_in: String,
@@ -27,6 +33,33 @@ pub struct ConfigService {
     pub smtp_password: String,
     pub smtp_transport: String,
     pub port_host: String,
+    pub password_reset_url: String,
+    pub jwt_issuer: String,
+    pub jwt_audience: String,
+    pub oauth_login_url: Option<String>,
+    pub totp_issuer: String,
+    pub webauthn_rp_id: Option<String>,
+    pub webauthn_rp_name: String,
+    pub webauthn_rp_origins: String,
+    pub login_max_failed_attempts: i32,
+    pub login_lockout_seconds: i64,
+    pub login_lockout_max_seconds: i64,
+    pub password_min_length: usize,
+    pub password_max_length: usize,
+    pub password_require_uppercase: bool,
+    pub password_require_lowercase: bool,
+    pub password_require_digit: bool,
+    pub password_require_symbol: bool,
+    pub password_disallow_personal_info: bool,
+    pub password_check_breached: bool,
+    pub password_breached_list_path: Option<String>,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    pub password_pepper: Option<String>,
+    pub password_pepper_id: String,
+    pub permission_cache_ttl_seconds: u64,
+    pub grant_sweep_interval_seconds: u64,
 }
 
 impl ConfigService {
@@ -35,6 +68,10 @@ impl ConfigService {
 
         let access_token_private_key = get_env_var("ACCESS_TOKEN_PRIVATE_KEY");
         let access_token_public_key = get_env_var("ACCESS_TOKEN_PUBLIC_KEY");
+        // Optional kid of the signing key, defaults to its RFC 7638 thumbprint
+        let access_token_key_id = std::env::var("ACCESS_TOKEN_KEY_ID").ok();
+        // Retired public keys still accepted, comma separated `kid:base64_pem`
+        let access_token_verification_keys = get_env_var_or("ACCESS_TOKEN_VERIFICATION_KEYS", "");
         let access_token_expires_in = get_env_var("ACCESS_TOKEN_EXPIRED_IN");
         let access_token_max_age = get_env_var("ACCESS_TOKEN_MAXAGE");
         let refresh_token_expires_in = get_env_var("REFRESH_TOKEN_EXPIRED_IN");
@@ -53,11 +90,58 @@ impl ConfigService {
         let smtp_password = get_env_var("SMTP_PASSWORD");
         let smtp_transport = get_env_var("SMTP_TRANSPORT");
         let port_host = get_env_var("PORT_HOST");
+        // `iss` / `aud` of access tokens, advertised through OIDC discovery
+        let jwt_issuer = get_env_var_or("JWT_ISSUER", port_host.as_str());
+        let jwt_audience = get_env_var_or("JWT_AUDIENCE", jwt_issuer.as_str());
+        // Login page /oauth/authorize sends users to when they have no session yet
+        let oauth_login_url = std::env::var("OAUTH_LOGIN_URL").ok();
+        // Front-end page that receives `?token=` and posts it to /v1/auth/password/reset.
+        // No default: the API endpoint itself only accepts POST, a mailed link to it is dead.
+        let password_reset_url = get_env_var("PASSWORD_RESET_URL");
+
+        // Account name shown by authenticator apps
+        let totp_issuer = get_env_var_or("TOTP_ISSUER", "NsdHSO Auth");
+        // Passkey relying party: the domain passkeys are bound to (defaults to the host of the
+        // first origin) and the comma separated origins of the pages running the ceremonies
+        let webauthn_rp_id = std::env::var("WEBAUTHN_RP_ID").ok();
+        let webauthn_rp_name = get_env_var_or("WEBAUTHN_RP_NAME", totp_issuer.as_str());
+        let webauthn_rp_origins = get_env_var_or("WEBAUTHN_RP_ORIGINS", port_host.as_str());
+        // Failed passwords before the account is locked. Each consecutive lock doubles
+        // LOGIN_LOCKOUT_SECONDS, capped at LOGIN_LOCKOUT_MAX_SECONDS
+        let login_max_failed_attempts = get_env_var_or("LOGIN_MAX_FAILED_ATTEMPTS", "5");
+        let login_lockout_seconds = get_env_var_or("LOGIN_LOCKOUT_SECONDS", "900");
+        let login_lockout_max_seconds = get_env_var_or("LOGIN_LOCKOUT_MAX_SECONDS", "86400");
+        // Password policy applied on register and password change
+        let password_min_length = get_env_var_or("PASSWORD_MIN_LENGTH", "8");
+        let password_max_length = get_env_var_or("PASSWORD_MAX_LENGTH", "128");
+        let password_require_uppercase = get_env_var_or("PASSWORD_REQUIRE_UPPERCASE", "true");
+        let password_require_lowercase = get_env_var_or("PASSWORD_REQUIRE_LOWERCASE", "true");
+        let password_require_digit = get_env_var_or("PASSWORD_REQUIRE_DIGIT", "true");
+        let password_require_symbol = get_env_var_or("PASSWORD_REQUIRE_SYMBOL", "false");
+        let password_disallow_personal_info =
+            get_env_var_or("PASSWORD_DISALLOW_PERSONAL_INFO", "true");
+        let password_check_breached = get_env_var_or("PASSWORD_CHECK_BREACHED", "true");
+        // SHA-1 list (`HASH` or `HASH:COUNT` per line), the bundled list is used when unset
+        let password_breached_list_path = std::env::var("PASSWORD_BREACHED_LIST_PATH").ok();
+        // Argon2id cost of new hashes, older hashes are upgraded on the next login
+        let argon2_memory_kib = get_env_var_or("ARGON2_MEMORY_KIB", "19456");
+        let argon2_iterations = get_env_var_or("ARGON2_ITERATIONS", "2");
+        let argon2_parallelism = get_env_var_or("ARGON2_PARALLELISM", "1");
+        // Server-side secret mixed into every hash; the id (at most 8 bytes) is stored
+        // in the hash so a changed pepper is detected
+        let password_pepper = std::env::var("PASSWORD_PEPPER").ok();
+        let password_pepper_id = get_env_var_or("PASSWORD_PEPPER_ID", "1");
+        // How long resolved roles and permissions are reused; 0 resolves on every issue
+        let permission_cache_ttl_seconds = get_env_var_or("PERMISSION_CACHE_TTL_SECONDS", "300");
+        // How often expired time-bound role assignments and overrides are removed
+        let grant_sweep_interval_seconds = get_env_var_or("GRANT_SWEEP_INTERVAL_SECONDS", "60");
 
         ConfigService {
             database_url,
             access_token_private_key,
             access_token_public_key,
+            access_token_key_id,
+            access_token_verification_keys,
             access_token_expires_in,
             access_token_max_age: access_token_max_age.parse::<i64>().unwrap(),
             refresh_token_max_age: refresh_token_max_age.parse::<i64>().unwrap(),
@@ -75,6 +159,39 @@ impl ConfigService {
             smtp_password,
             smtp_transport,
             port_host,
+            password_reset_url,
+            jwt_issuer,
+            jwt_audience,
+            oauth_login_url,
+            totp_issuer,
+            webauthn_rp_id,
+            webauthn_rp_name,
+            webauthn_rp_origins,
+            login_max_failed_attempts: login_max_failed_attempts.parse::<i32>().unwrap(),
+            login_lockout_seconds: login_lockout_seconds.parse::<i64>().unwrap(),
+            login_lockout_max_seconds: login_lockout_max_seconds.parse::<i64>().unwrap(),
+            password_min_length: password_min_length.parse::<usize>().unwrap(),
+            password_max_length: password_max_length.parse::<usize>().unwrap(),
+            password_require_uppercase: password_require_uppercase.parse::<bool>().unwrap(),
+            password_require_lowercase: password_require_lowercase.parse::<bool>().unwrap(),
+            password_require_digit: password_require_digit.parse::<bool>().unwrap(),
+            password_require_symbol: password_require_symbol.parse::<bool>().unwrap(),
+            password_disallow_personal_info: password_disallow_personal_info
+                .parse::<bool>()
+                .unwrap(),
+            password_check_breached: password_check_breached.parse::<bool>().unwrap(),
+            password_breached_list_path,
+            argon2_memory_kib: argon2_memory_kib.parse::<u32>().unwrap(),
+            argon2_iterations: argon2_iterations.parse::<u32>().unwrap(),
+            argon2_parallelism: argon2_parallelism.parse::<u32>().unwrap(),
+            password_pepper,
+            password_pepper_id,
+            permission_cache_ttl_seconds: permission_cache_ttl_seconds.parse::<u64>().unwrap(),
+            // `tokio::time::interval` panics on a zero period
+            grant_sweep_interval_seconds: grant_sweep_interval_seconds
+                .parse::<u64>()
+                .unwrap()
+                .max(1),
         }
     }
 }
@@ -43,6 +43,123 @@ impl MailSendService {
             ))
             .unwrap();
 
+        Self::deliver(&email_message, config_service)
+    }
+
+    pub fn send_password_reset_mail(
+        &self,
+        email: String,
+        token: String,
+        config_service: &ConfigService,
+    ) -> Result<(), lettre::transport::smtp::Error> {
+        let reset_link = format!("{}?token={}", config_service.password_reset_url, token);
+
+        let email_message = Message::builder()
+            .from(Mailbox::new(
+                Option::from("Password reset no replay".to_owned()),
+                config_service.email_address.parse().unwrap(),
+            ))
+            .to(email.parse().unwrap())
+            .subject("Reset your password")
+            .header(ContentType::TEXT_PLAIN)
+            .body(format!(
+                "We received a request to reset your password. \
+                 The link is valid for one hour and can be used once: {}\n\n\
+                 If you did not ask for this, you can ignore this email.",
+                reset_link
+            ))
+            .unwrap();
+
+        Self::deliver(&email_message, config_service)
+    }
+
+    /// Sent when an administrator forces a reset, the old password no longer works.
+    pub fn send_forced_password_reset_mail(
+        &self,
+        email: String,
+        token: String,
+        config_service: &ConfigService,
+    ) -> Result<(), lettre::transport::smtp::Error> {
+        let reset_link = format!("{}?token={}", config_service.password_reset_url, token);
+
+        let email_message = Message::builder()
+            .from(Mailbox::new(
+                Option::from("Password reset no replay".to_owned()),
+                config_service.email_address.parse().unwrap(),
+            ))
+            .to(email.parse().unwrap())
+            .subject("Your password must be reset")
+            .header(ContentType::TEXT_PLAIN)
+            .body(format!(
+                "An administrator reset the password of your account and signed you out \
+                 everywhere. Choose a new password with the following link, it is valid \
+                 for one hour and can be used once: {}",
+                reset_link
+            ))
+            .unwrap();
+
+        Self::deliver(&email_message, config_service)
+    }
+
+    pub fn send_email_change_mail(
+        &self,
+        email: String,
+        token: String,
+        config_service: &ConfigService,
+    ) -> Result<(), lettre::transport::smtp::Error> {
+        let confirm_link = format!(
+            "{}/v1/users/email/confirm/{}",
+            config_service.port_host, token
+        );
+
+        let email_message = Message::builder()
+            .from(Mailbox::new(
+                Option::from("Email change no replay".to_owned()),
+                config_service.email_address.parse().unwrap(),
+            ))
+            .to(email.parse().unwrap())
+            .subject("Confirm your new email address")
+            .header(ContentType::TEXT_PLAIN)
+            .body(format!(
+                "Please click the following link to use this address for your account. \
+                 The link is valid for 24 hours and can be used once: {}\n\n\
+                 If you did not ask for this, you can ignore this email.",
+                confirm_link
+            ))
+            .unwrap();
+
+        Self::deliver(&email_message, config_service)
+    }
+
+    /// Tells the previous address that the account moved, in case it was not its owner.
+    pub fn send_email_changed_notice(
+        &self,
+        old_email: String,
+        new_email: String,
+        config_service: &ConfigService,
+    ) -> Result<(), lettre::transport::smtp::Error> {
+        let email_message = Message::builder()
+            .from(Mailbox::new(
+                Option::from("Email change no replay".to_owned()),
+                config_service.email_address.parse().unwrap(),
+            ))
+            .to(old_email.parse().unwrap())
+            .subject("Your email address was changed")
+            .header(ContentType::TEXT_PLAIN)
+            .body(format!(
+                "The email address of your account was changed to {}.\n\n\
+                 If you did not make this change, reset your password and contact support.",
+                new_email
+            ))
+            .unwrap();
+
+        Self::deliver(&email_message, config_service)
+    }
+
+    fn deliver(
+        email_message: &Message,
+        config_service: &ConfigService,
+    ) -> Result<(), lettre::transport::smtp::Error> {
         let creds = Credentials::new(config_service.email_address.to_owned(), config_service.smtp_password.to_owned());
 
         let mailer = SmtpTransport::relay(config_service.smtp_transport.as_str())
@@ -51,6 +168,6 @@ impl MailSendService {
             .build();
 
         // Send the email and return the result.
-        mailer.send(&email_message).map(|_| ())
+        mailer.send(email_message).map(|_| ())
     }
 }
@@ -3,3 +3,10 @@ pub mod auth;
 pub mod mail_send;
 pub mod tokens;
 pub mod config;
+pub mod oauth;
+pub mod sessions;
+pub mod webauthn;
+pub mod rate_limit;
+pub mod rbac;
+pub mod organizations;
+pub mod audit;
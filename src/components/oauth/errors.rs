@@ -0,0 +1,80 @@
+use crate::http_response::error_handler::CustomError;
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+
+/// Error body mandated by RFC 6749, section 5.2. OAuth clients parse this shape,
+/// so the token endpoint answers with it instead of the usual `ResponseObject`.
+#[derive(Debug, Serialize)]
+pub struct OAuthError {
+    pub error: &'static str,
+    pub error_description: String,
+    #[serde(skip)]
+    status: StatusCode,
+}
+
+impl OAuthError {
+    fn new(error: &'static str, error_description: &str, status: StatusCode) -> Self {
+        OAuthError {
+            error,
+            error_description: error_description.to_string(),
+            status,
+        }
+    }
+
+    pub fn invalid_request(description: &str) -> Self {
+        Self::new("invalid_request", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn invalid_client(description: &str) -> Self {
+        Self::new("invalid_client", description, StatusCode::UNAUTHORIZED)
+    }
+
+    pub fn invalid_grant(description: &str) -> Self {
+        Self::new("invalid_grant", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn unauthorized_client(description: &str) -> Self {
+        Self::new("unauthorized_client", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn invalid_scope(description: &str) -> Self {
+        Self::new("invalid_scope", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn unsupported_grant_type(description: &str) -> Self {
+        Self::new("unsupported_grant_type", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn server_error(description: &str) -> Self {
+        Self::new("server_error", description, StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.error, self.error_description)
+    }
+}
+
+impl From<CustomError> for OAuthError {
+    fn from(error: CustomError) -> Self {
+        OAuthError::server_error(error.error_message.as_str())
+    }
+}
+
+impl ResponseError for OAuthError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status);
+        response.insert_header((header::CACHE_CONTROL, "no-store"));
+        if self.status == StatusCode::UNAUTHORIZED {
+            response.insert_header((header::WWW_AUTHENTICATE, "Basic"));
+        }
+        response.json(self)
+    }
+}
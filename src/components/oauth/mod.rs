@@ -0,0 +1,7 @@
+mod routes;
+mod services;
+mod errors;
+
+pub use routes::*;
+pub use services::*;
+pub use errors::*;
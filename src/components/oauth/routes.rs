@@ -0,0 +1,168 @@
+use super::services::{client_credentials, OAuthService};
+use crate::components::auth::functions::refresh_cookie;
+use crate::components::config::ConfigService;
+use crate::components::sessions::client_info;
+use crate::components::oauth::OAuthError;
+use crate::config_service;
+use crate::entity::oauth_clients::{AuthorizeQuery, TokenRequest, TokenResponse};
+use actix_web::http::header;
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use url::Url;
+
+fn redirect(location: &str) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+/// Errors after the redirect URI has been validated go back to the client (RFC 6749 4.1.2.1)
+fn redirect_with_error(redirect_uri: &Url, error: &str, state: Option<&str>) -> HttpResponse {
+    let mut location = redirect_uri.clone();
+    location.query_pairs_mut().append_pair("error", error);
+    if let Some(state) = state {
+        location.query_pairs_mut().append_pair("state", state);
+    }
+    redirect(location.as_str())
+}
+
+#[get("/authorize")]
+pub async fn authorize(
+    req: HttpRequest,
+    query: web::Query<AuthorizeQuery>,
+    service: web::Data<OAuthService>,
+    service_config: web::Data<ConfigService>,
+) -> Result<HttpResponse, OAuthError> {
+    let query = query.into_inner();
+
+    // Until client and redirect URI are trusted, errors must not be redirected anywhere
+    let client_id = query
+        .client_id
+        .as_deref()
+        .ok_or_else(|| OAuthError::invalid_request("Missing client_id"))?;
+    let client = service
+        .find_active_client(client_id)
+        .await?
+        .ok_or_else(|| OAuthError::invalid_request("Unknown client_id"))?;
+    let redirect_uri = query
+        .redirect_uri
+        .as_deref()
+        .filter(|uri| client.allows_redirect_uri(uri))
+        .ok_or_else(|| OAuthError::invalid_request("redirect_uri is not registered"))?;
+    let mut redirect_url = Url::parse(redirect_uri)
+        .map_err(|_| OAuthError::invalid_request("Invalid redirect_uri"))?;
+    let state = query.state.as_deref();
+
+    if query.response_type.as_deref() != Some("code") {
+        return Ok(redirect_with_error(&redirect_url, "unsupported_response_type", state));
+    }
+    if !client.allows_grant_type("authorization_code") {
+        return Ok(redirect_with_error(&redirect_url, "unauthorized_client", state));
+    }
+    let code_challenge = match (
+        query.code_challenge.as_deref(),
+        query.code_challenge_method.as_deref(),
+    ) {
+        (Some(challenge), Some("S256")) if !challenge.is_empty() => challenge,
+        _ => return Ok(redirect_with_error(&redirect_url, "invalid_request", state)),
+    };
+
+    let user = match req.cookie("refresh_token") {
+        Some(cookie) => service.user_from_refresh_token(cookie.value()).await?,
+        None => None,
+    };
+    let user = match user {
+        Some(user) => user,
+        None => {
+            return Ok(match &service_config.oauth_login_url {
+                // The login page signs the user in and sends them back here
+                Some(login_url) => {
+                    let mut login = Url::parse(login_url)
+                        .map_err(|_| OAuthError::server_error("Invalid OAUTH_LOGIN_URL"))?;
+                    let return_to = format!(
+                        "{}{}",
+                        service_config.port_host.trim_end_matches('/'),
+                        req.uri()
+                    );
+                    login.query_pairs_mut().append_pair("return_to", &return_to);
+                    redirect(login.as_str())
+                }
+                None => redirect_with_error(&redirect_url, "login_required", state),
+            });
+        }
+    };
+
+    let code = service
+        .create_authorization_code(
+            &client,
+            user.id,
+            redirect_uri,
+            code_challenge,
+            query.scope.clone(),
+            query.nonce.clone(),
+        )
+        .await?;
+
+    redirect_url.query_pairs_mut().append_pair("code", &code);
+    if let Some(state) = state {
+        redirect_url.query_pairs_mut().append_pair("state", state);
+    }
+    Ok(redirect(redirect_url.as_str()))
+}
+
+#[post("/token")]
+pub async fn token(
+    req: HttpRequest,
+    payload: web::Form<TokenRequest>,
+    service: web::Data<OAuthService>,
+) -> Result<HttpResponse, OAuthError> {
+    let payload = payload.into_inner();
+    let (client_id, client_secret) = client_credentials(
+        &req,
+        payload.client_id.as_deref(),
+        payload.client_secret.as_deref(),
+    )
+        .ok_or_else(|| OAuthError::invalid_client("Missing client credentials"))?;
+    let client = service
+        .authenticate_client(&client_id, client_secret.as_deref())
+        .await?;
+
+    match payload.grant_type.as_str() {
+        "authorization_code" => {
+            let (tokens, id_token) = service
+                .exchange_authorization_code(&client, &payload, &client_info(&req))
+                .await?;
+            Ok(HttpResponse::Ok()
+                .insert_header((header::CACHE_CONTROL, "no-store"))
+                .cookie(refresh_cookie(tokens.refresh_token))
+                .json(TokenResponse {
+                    access_token: tokens.body.access_token,
+                    token_type: "Bearer".to_string(),
+                    expires_in: config_service().access_token_max_age * 60,
+                    // The token is not narrowed to the requested scope, so none is claimed
+                    scope: None,
+                    id_token,
+                }))
+        }
+        "client_credentials" => {
+            // No refresh token here: the client simply authenticates again (RFC 6749 4.4.3)
+            let (token_details, scope) = service.issue_client_token(&client, &payload).await?;
+            Ok(HttpResponse::Ok()
+                .insert_header((header::CACHE_CONTROL, "no-store"))
+                .json(TokenResponse {
+                    access_token: token_details.token.unwrap_or_default(),
+                    token_type: "Bearer".to_string(),
+                    expires_in: config_service().access_token_max_age * 60,
+                    scope: (!scope.is_empty()).then_some(scope),
+                    id_token: None,
+                }))
+        }
+        _ => Err(OAuthError::unsupported_grant_type(
+            "Supported grant types are authorization_code and client_credentials",
+        )),
+    }
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(authorize);
+    config.service(token);
+}
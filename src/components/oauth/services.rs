@@ -0,0 +1,434 @@
+use crate::components::auth::functions::{
+    generate_id_token, generate_jwt_token, generate_opaque_refresh, hash_refresh,
+    issue_tokens_for_user, ResolvedAccess, TokenDetails, TokenSubject, KEY_RING,
+};
+use crate::components::oauth::OAuthError;
+use crate::components::tokens::TokensService;
+use crate::components::users::enums::SearchValue;
+use crate::components::users::UsersService;
+use crate::entity::{authorization_codes, permissions};
+use crate::entity::sessions::ClientInfo;
+use crate::entity::oauth_clients::{Column, Entity, Model, TokenRequest};
+use crate::entity::users::AuthResponseBody;
+use crate::http_response::error_handler::CustomError;
+use crate::config_service;
+use crate::http_response::HttpCodeW;
+use crate::utils::helpers::{now_date_time_utc, verify_password};
+use auth::permission_matching::{has_permission, is_wildcard};
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chrono::Duration;
+use percent_encoding::percent_decode_str;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+    TransactionTrait,
+};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// Authorization codes are exchanged immediately by the client, one minute is plenty
+const AUTHORIZATION_CODE_TTL_SECONDS: i64 = 60;
+
+#[derive(Clone)]
+pub struct OAuthService {
+    conn: DatabaseConnection,
+    users_service: UsersService,
+    tokens_service: TokensService,
+}
+
+impl OAuthService {
+    pub fn new(
+        conn: &DatabaseConnection,
+        users_service: &UsersService,
+        tokens_service: &TokensService,
+    ) -> Self {
+        Self {
+            conn: conn.clone(),
+            users_service: users_service.clone(),
+            tokens_service: tokens_service.clone(),
+        }
+    }
+
+    pub async fn find_active_client(&self, client_id: &str) -> Result<Option<Model>, CustomError> {
+        Entity::find()
+            .filter(Column::ClientId.eq(client_id))
+            .filter(Column::IsActive.eq(true))
+            .one(&self.conn)
+            .await
+            .map_err(CustomError::from)
+    }
+
+    /// Confidential clients must present their secret, public clients are identified only.
+    pub async fn authenticate_client(
+        &self,
+        client_id: &str,
+        client_secret: Option<&str>,
+    ) -> Result<Model, OAuthError> {
+        let client = self
+            .find_active_client(client_id)
+            .await?
+            .ok_or_else(|| OAuthError::invalid_client("Unknown client"))?;
+
+        if let Some(secret_hash) = &client.client_secret_hash {
+            let secret = client_secret
+                .ok_or_else(|| OAuthError::invalid_client("Client authentication required"))?;
+            match verify_password(secret, secret_hash) {
+                Ok(true) => {}
+                _ => return Err(OAuthError::invalid_client("Invalid client credentials")),
+            }
+        }
+
+        Ok(client)
+    }
+
+    /// Resource servers calling /introspect must hold a secret (RFC 7662 section 2.1)
+    pub async fn authenticate_confidential_client(
+        &self,
+        client_id: &str,
+        client_secret: Option<&str>,
+    ) -> Result<Model, OAuthError> {
+        let client = self.authenticate_client(client_id, client_secret).await?;
+        if client.client_secret_hash.is_none() {
+            return Err(OAuthError::invalid_client(
+                "Public clients cannot use this endpoint",
+            ));
+        }
+        Ok(client)
+    }
+
+    /// The user signed in to this service, identified by the first-party refresh cookie.
+    pub async fn user_from_refresh_token(
+        &self,
+        raw_refresh_token: &str,
+    ) -> Result<Option<crate::entity::users::Model>, CustomError> {
+        let token = match self
+            .tokens_service
+            .find_refresh_by_raw(raw_refresh_token, &self.conn)
+            .await?
+        {
+            Some(token) if token.is_valid() => token,
+            _ => return Ok(None),
+        };
+
+        let user = self
+            .users_service
+            .find("id", SearchValue::Uuid(token.user_id))
+            .await?;
+        Ok(user.can_login().then_some(user))
+    }
+
+    pub async fn create_authorization_code(
+        &self,
+        client: &Model,
+        user_id: Uuid,
+        redirect_uri: &str,
+        code_challenge: &str,
+        scope: Option<String>,
+        nonce: Option<String>,
+    ) -> Result<String, CustomError> {
+        let (raw, hash) = generate_opaque_refresh();
+        let expires_at = now_date_time_utc() + Duration::seconds(AUTHORIZATION_CODE_TTL_SECONDS);
+
+        authorization_codes::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            code_hash: Set(hash),
+            client_id: Set(client.id),
+            user_id: Set(user_id),
+            redirect_uri: Set(redirect_uri.to_string()),
+            code_challenge: Set(code_challenge.to_string()),
+            code_challenge_method: Set("S256".to_string()),
+            scope: Set(scope),
+            nonce: Set(nonce),
+            expires_at: Set(DateTimeWithTimeZone::from(expires_at)),
+            is_used: Set(false),
+            created_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+        }
+        .insert(&self.conn)
+        .await
+        .map_err(|e| {
+            CustomError::new(
+                HttpCodeW::InternalServerError,
+                format!("Failed to create authorization code: {e}"),
+            )
+        })?;
+
+        Ok(raw)
+    }
+
+    /// Redeems a code for tokens (RFC 6749 section 4.1.3 with RFC 7636 PKCE). The tokens
+    /// carry the user's own roles and permissions, the requested `scope` does not narrow them.
+    /// With `openid` in the scope an ID token for the client comes along.
+    pub async fn exchange_authorization_code(
+        &self,
+        client: &Model,
+        payload: &TokenRequest,
+        client_info: &ClientInfo,
+    ) -> Result<(AuthResponseBody, Option<String>), OAuthError> {
+        if !client.allows_grant_type("authorization_code") {
+            return Err(OAuthError::unauthorized_client(
+                "Client is not allowed to use authorization_code",
+            ));
+        }
+        let code = payload
+            .code
+            .as_deref()
+            .ok_or_else(|| OAuthError::invalid_request("Missing code"))?;
+        let redirect_uri = payload
+            .redirect_uri
+            .as_deref()
+            .ok_or_else(|| OAuthError::invalid_request("Missing redirect_uri"))?;
+        let code_verifier = payload
+            .code_verifier
+            .as_deref()
+            .ok_or_else(|| OAuthError::invalid_request("Missing code_verifier"))?;
+
+        let txn = self
+            .conn
+            .begin()
+            .await
+            .map_err(|e| OAuthError::server_error(format!("Txn begin error: {e}").as_str()))?;
+
+        let code_model = authorization_codes::Entity::find()
+            .filter(authorization_codes::Column::CodeHash.eq(hash_refresh(code)))
+            .one(&txn)
+            .await
+            .map_err(CustomError::from)?
+            .ok_or_else(|| OAuthError::invalid_grant("Invalid authorization code"))?;
+
+        if code_model.is_used || code_model.is_expired() {
+            return Err(OAuthError::invalid_grant(
+                "Authorization code expired or already used",
+            ));
+        }
+        if code_model.client_id != client.id || code_model.redirect_uri != redirect_uri {
+            return Err(OAuthError::invalid_grant(
+                "Authorization code was issued to another client or redirect_uri",
+            ));
+        }
+        if !verify_pkce(code_verifier, &code_model.code_challenge) {
+            return Err(OAuthError::invalid_grant("PKCE verification failed"));
+        }
+
+        // Only one of two concurrent exchanges of the same code gets to mark it used
+        let marked = authorization_codes::Entity::update_many()
+            .col_expr(authorization_codes::Column::IsUsed, Expr::value(true))
+            .filter(authorization_codes::Column::Id.eq(code_model.id))
+            .filter(authorization_codes::Column::IsUsed.eq(false))
+            .exec(&txn)
+            .await
+            .map_err(CustomError::from)?;
+        if marked.rows_affected != 1 {
+            return Err(OAuthError::invalid_grant(
+                "Authorization code expired or already used",
+            ));
+        }
+        let user_id = code_model.user_id;
+        let openid = code_model
+            .scope
+            .as_deref()
+            .is_some_and(|scope| scope.split_whitespace().any(|value| value == "openid"));
+        let nonce = code_model.nonce;
+        txn.commit()
+            .await
+            .map_err(|e| OAuthError::server_error(format!("Txn commit error: {e}").as_str()))?;
+
+        let user = self
+            .users_service
+            .find("id", SearchValue::Uuid(user_id))
+            .await?;
+        if !user.can_login() {
+            return Err(OAuthError::invalid_grant("User cannot sign in"));
+        }
+
+        let id_token = if openid {
+            let id_token = generate_id_token(
+                &user,
+                &client.client_id,
+                nonce,
+                config_service().access_token_max_age,
+                &KEY_RING,
+            )
+            .map_err(|e| OAuthError::server_error(format!("JWT generation error: {e}").as_str()))?;
+            Some(id_token)
+        } else {
+            None
+        };
+
+        let tokens = issue_tokens_for_user(&self.conn, &self.tokens_service, &user, client_info).await?;
+        Ok((tokens, id_token))
+    }
+
+    /// Service token for a confidential client (RFC 6749 section 4.4). The token's
+    /// permissions are the requested scope, or every permission the client is allowed.
+    /// Wildcards follow the rules of user grants: `emergency.*` allows asking for
+    /// `emergency.read`, and the token carries the concrete codes they cover.
+    pub async fn issue_client_token(
+        &self,
+        client: &Model,
+        payload: &TokenRequest,
+    ) -> Result<(TokenDetails, String), OAuthError> {
+        if client.client_secret_hash.is_none() {
+            return Err(OAuthError::unauthorized_client(
+                "client_credentials requires a confidential client",
+            ));
+        }
+        if !client.allows_grant_type("client_credentials") {
+            return Err(OAuthError::unauthorized_client(
+                "Client is not allowed to use client_credentials",
+            ));
+        }
+
+        let allowed = client.allowed_permissions();
+        let perms: Vec<String> = match payload.scope.as_deref().map(str::trim) {
+            Some(scope) if !scope.is_empty() => {
+                let requested: Vec<String> =
+                    scope.split_whitespace().map(str::to_string).collect();
+                if let Some(denied) = requested
+                    .iter()
+                    .find(|perm| !has_permission(&allowed, perm))
+                {
+                    return Err(OAuthError::invalid_scope(
+                        format!("Scope {denied} is not allowed for this client").as_str(),
+                    ));
+                }
+                requested
+            }
+            _ => allowed,
+        };
+        let perms: Vec<String> = permissions::Entity::find()
+            .order_by_asc(permissions::Column::Code)
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+            .into_iter()
+            .map(|permission| permission.code)
+            .filter(|code| !is_wildcard(code) && has_permission(&perms, code))
+            .collect();
+        let scope = perms.join(" ");
+
+        let token_details = generate_jwt_token(
+            TokenSubject::Client(client.client_id.clone()),
+            config_service().access_token_max_age,
+            &KEY_RING,
+            ResolvedAccess {
+                perms,
+                ..ResolvedAccess::default()
+            },
+            String::new(),
+        )
+        .map_err(|e| OAuthError::server_error(format!("JWT generation error: {e}").as_str()))?;
+
+        Ok((token_details, scope))
+    }
+}
+
+/// S256: BASE64URL(SHA256(code_verifier)) must equal the stored code_challenge
+pub fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    // RFC 7636 section 4.1: 43 to 128 characters
+    if !(43..=128).contains(&code_verifier.len()) {
+        return false;
+    }
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes())) == code_challenge
+}
+
+/// One half of the Basic credentials, which RFC 6749 section 2.3.1 has the client
+/// form-urlencode before joining them with `:`
+fn form_urldecode(value: &str) -> Option<String> {
+    percent_decode_str(&value.replace('+', " "))
+        .decode_utf8()
+        .ok()
+        .map(|decoded| decoded.into_owned())
+}
+
+/// Client id and secret from HTTP Basic auth, falling back to the form body.
+pub fn client_credentials(
+    req: &HttpRequest,
+    form_client_id: Option<&str>,
+    form_client_secret: Option<&str>,
+) -> Option<(String, Option<String>)> {
+    let basic = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|decoded| {
+            let (id, secret) = decoded.split_once(':')?;
+            Some((form_urldecode(id)?, Some(form_urldecode(secret)?)))
+        });
+
+    basic.or_else(|| {
+        form_client_id.map(|id| (id.to_string(), form_client_secret.map(str::to_string)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_db;
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    #[test]
+    fn basic_credentials_are_form_urldecoded() {
+        // id `svc:a` and secret `p%s+w:d x`, encoded as RFC 6749 section 2.3.1 asks
+        let encoded = STANDARD.encode("svc%3Aa:p%25s%2Bw%3Ad+x");
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, format!("Basic {encoded}")))
+            .to_http_request();
+
+        assert_eq!(
+            client_credentials(&req, None, None),
+            Some(("svc:a".to_string(), Some("p%s+w:d x".to_string())))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn client_scope_follows_wildcard_grants() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        test_db::configure_env();
+        let users_service = UsersService::new(&db);
+        let service = OAuthService::new(&db, &users_service, &TokensService::new(&db, &users_service));
+        let now = DateTimeWithTimeZone::from(now_date_time_utc());
+        let client = Model {
+            id: Uuid::new_v4(),
+            client_id: "appointments-service".to_string(),
+            name: "Appointments".to_string(),
+            client_secret_hash: Some("unused".to_string()),
+            redirect_uris: json!([]),
+            is_active: true,
+            grant_types: json!(["client_credentials"]),
+            allowed_permissions: json!(["appointment.*"]),
+            created_at: now,
+            updated_at: now,
+        };
+        let request = |scope: Option<&str>| TokenRequest {
+            grant_type: "client_credentials".to_string(),
+            scope: scope.map(str::to_string),
+            ..TokenRequest::default()
+        };
+
+        let (_, narrowed) = service
+            .issue_client_token(&client, &request(Some("appointment.read")))
+            .await
+            .unwrap();
+        let (_, everything) = service.issue_client_token(&client, &request(None)).await.unwrap();
+        let denied = service
+            .issue_client_token(&client, &request(Some("emergency.read")))
+            .await
+            .unwrap_err();
+
+        assert_eq!(narrowed, "appointment.read");
+        assert!(everything.split(' ').all(|code| code.starts_with("appointment.")));
+        assert!(everything.split(' ').any(|code| code == "appointment.update"));
+        assert!(!everything.contains('*'));
+        assert_eq!(denied.error, "invalid_scope");
+    }
+}
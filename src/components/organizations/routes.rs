@@ -0,0 +1,173 @@
+use super::services::OrganizationsService;
+use crate::components::auth::functions::AuthUser;
+use crate::components::sessions::client_info;
+use crate::entity::organizations::{CreateOrganizationRequestBody, UpdateOrganizationRequestBody};
+use crate::http_response::error_handler::{CustomError, ValidatedJson};
+use crate::http_response::prepared_response::check_response_ok_or_return_error;
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
+use uuid::Uuid;
+
+const ORG_READ: &str = "org.read";
+const ORG_WRITE: &str = "org.write";
+
+#[get("/organizations")]
+pub async fn list_organizations(
+    auth: AuthUser,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[ORG_READ])?;
+    check_response_ok_or_return_error(service.list_organizations().await)
+}
+
+#[get("/organizations/{id}")]
+pub async fn get_organization(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[ORG_READ])?;
+    check_response_ok_or_return_error(service.get_organization(path.into_inner()).await)
+}
+
+#[post("/organizations")]
+pub async fn create_organization(
+    auth: AuthUser,
+    payload: ValidatedJson<CreateOrganizationRequestBody>,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[ORG_WRITE])?;
+    check_response_ok_or_return_error(service.create_organization(payload.0).await)
+}
+
+#[patch("/organizations/{id}")]
+pub async fn update_organization(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    payload: ValidatedJson<UpdateOrganizationRequestBody>,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[ORG_WRITE])?;
+    check_response_ok_or_return_error(
+        service
+            .update_organization(path.into_inner(), payload.0)
+            .await,
+    )
+}
+
+#[delete("/organizations/{id}")]
+pub async fn delete_organization(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[ORG_WRITE])?;
+    check_response_ok_or_return_error(service.delete_organization(path.into_inner()).await)
+}
+
+#[get("/organizations/{id}/members")]
+pub async fn list_members(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[ORG_READ])?;
+    check_response_ok_or_return_error(service.list_members(path.into_inner()).await)
+}
+
+#[put("/organizations/{id}/members/{user_id}")]
+pub async fn add_member(
+    auth: AuthUser,
+    path: web::Path<(Uuid, Uuid)>,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[ORG_WRITE])?;
+    let (organization_id, user_id) = path.into_inner();
+    check_response_ok_or_return_error(service.add_member(organization_id, user_id).await)
+}
+
+#[delete("/organizations/{id}/members/{user_id}")]
+pub async fn remove_member(
+    auth: AuthUser,
+    path: web::Path<(Uuid, Uuid)>,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[ORG_WRITE])?;
+    let (organization_id, user_id) = path.into_inner();
+    check_response_ok_or_return_error(service.remove_member(organization_id, user_id).await)
+}
+
+#[put("/organizations/{id}/members/{user_id}/roles/{role_id}")]
+pub async fn assign_member_role(
+    req: HttpRequest,
+    auth: AuthUser,
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[ORG_WRITE])?;
+    let (organization_id, user_id, role_id) = path.into_inner();
+    check_response_ok_or_return_error(
+        service
+            .assign_member_role(
+                organization_id,
+                user_id,
+                role_id,
+                &auth.details,
+                client_info(&req),
+            )
+            .await,
+    )
+}
+
+#[delete("/organizations/{id}/members/{user_id}/roles/{role_id}")]
+pub async fn remove_member_role(
+    req: HttpRequest,
+    auth: AuthUser,
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[ORG_WRITE])?;
+    let (organization_id, user_id, role_id) = path.into_inner();
+    check_response_ok_or_return_error(
+        service
+            .remove_member_role(
+                organization_id,
+                user_id,
+                role_id,
+                &auth.details,
+                client_info(&req),
+            )
+            .await,
+    )
+}
+
+#[get("/users/me/organizations")]
+pub async fn my_organizations(
+    auth: AuthUser,
+    service: web::Data<OrganizationsService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    let active_organization_id = auth
+        .claims
+        .org_id
+        .as_deref()
+        .and_then(|org_id| Uuid::parse_str(org_id).ok());
+    check_response_ok_or_return_error(
+        service
+            .my_organizations(user_id, active_organization_id)
+            .await,
+    )
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(list_organizations);
+    config.service(get_organization);
+    config.service(create_organization);
+    config.service(update_organization);
+    config.service(delete_organization);
+    config.service(list_members);
+    config.service(add_member);
+    config.service(remove_member);
+    config.service(assign_member_role);
+    config.service(remove_member_role);
+    config.service(my_organizations);
+}
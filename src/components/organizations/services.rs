@@ -0,0 +1,497 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::{TokenDetails, PERMISSION_CACHE};
+use crate::entity::enums::AuditEventType;
+use crate::entity::organization_members::{MemberResponse, MembershipResponse};
+use crate::entity::organizations::{CreateOrganizationRequestBody, UpdateOrganizationRequestBody};
+use crate::entity::sessions::ClientInfo;
+use crate::entity::{organization_member_roles, organization_members, organizations, roles, users};
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use auth::permission_matching::permission_matches;
+use crate::utils::helpers::now_date_time_utc;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, DbErr,
+    EntityTrait, JoinType, ModelTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set,
+    SqlErr, Statement,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+const SLUG_MAX_LEN: usize = 100;
+
+/// Permissions guarding the administration APIs. A role that grants any of them, directly,
+/// through a wildcard or through the base roles it inherits, is a global role and cannot be
+/// held in an organization.
+const ADMIN_PERMISSIONS: [&str; 8] = [
+    "user.read",
+    "user.write",
+    "session.read",
+    "rbac.read",
+    "rbac.write",
+    "org.read",
+    "org.write",
+    "audit.read",
+];
+
+/// Permission codes granted by role `$1` and the base roles of lower priority it inherits
+const ROLE_GRANTS_SQL: &str = r#"
+WITH inherited AS (
+    SELECT $1::uuid AS id
+    UNION
+    SELECT b.id FROM auth.roles b
+    JOIN auth.roles r ON r.id = $1 AND b.priority < r.priority
+    WHERE b.code = ANY (enum_range(NULL::public.user_role)::text[])
+)
+SELECT DISTINCT p.code FROM inherited i
+JOIN auth.role_permissions rp ON rp.role_id = i.id
+JOIN auth.permissions p ON p.id = rp.permission_id
+"#;
+
+#[derive(Clone)]
+pub struct OrganizationsService {
+    conn: DatabaseConnection,
+}
+
+impl OrganizationsService {
+    pub fn new(conn: &DatabaseConnection) -> Self {
+        Self { conn: conn.clone() }
+    }
+
+    pub async fn list_organizations(&self) -> Result<Vec<organizations::Model>, CustomError> {
+        organizations::Entity::find()
+            .order_by_asc(organizations::Column::Slug)
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)
+    }
+
+    pub async fn get_organization(
+        &self,
+        organization_id: Uuid,
+    ) -> Result<organizations::Model, CustomError> {
+        self.find_organization(organization_id).await
+    }
+
+    pub async fn create_organization(
+        &self,
+        payload: CreateOrganizationRequestBody,
+    ) -> Result<organizations::Model, CustomError> {
+        let slug = validate_slug(&payload.slug)?;
+        let name = validate_name(&payload.name)?;
+        let now = DateTimeWithTimeZone::from(now_date_time_utc());
+        organizations::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            slug: Set(slug),
+            name: Set(name),
+            created_at: Set(now),
+            updated_at: Set(now),
+        }
+        .insert(&self.conn)
+        .await
+        .map_err(organization_db_error)
+    }
+
+    pub async fn update_organization(
+        &self,
+        organization_id: Uuid,
+        payload: UpdateOrganizationRequestBody,
+    ) -> Result<organizations::Model, CustomError> {
+        let name = validate_name(&payload.name)?;
+        let mut active_organization: organizations::ActiveModel =
+            self.find_organization(organization_id).await?.into();
+        active_organization.name = Set(name);
+        active_organization.updated_at = Set(DateTimeWithTimeZone::from(now_date_time_utc()));
+        active_organization
+            .update(&self.conn)
+            .await
+            .map_err(organization_db_error)
+    }
+
+    /// Members and their roles go with it (ON DELETE CASCADE), sessions that had it
+    /// active fall back to global roles on their next refresh
+    pub async fn delete_organization(&self, organization_id: Uuid) -> Result<String, CustomError> {
+        let organization = self.find_organization(organization_id).await?;
+        organization
+            .delete(&self.conn)
+            .await
+            .map_err(organization_db_error)?;
+        PERMISSION_CACHE.invalidate_all();
+        Ok("Organization deleted".to_string())
+    }
+
+    pub async fn list_members(
+        &self,
+        organization_id: Uuid,
+    ) -> Result<Vec<MemberResponse>, CustomError> {
+        self.find_organization(organization_id).await?;
+        let members = organization_members::Entity::find()
+            .filter(organization_members::Column::OrganizationId.eq(organization_id))
+            .find_also_related(users::Entity)
+            .order_by_asc(users::Column::Email)
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        let mut codes_by_user: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for (user_id, code) in organization_member_roles::Entity::find()
+            .join(JoinType::InnerJoin, organization_member_roles::Relation::Roles.def())
+            .filter(organization_member_roles::Column::OrganizationId.eq(organization_id))
+            .select_only()
+            .column(organization_member_roles::Column::UserId)
+            .column(roles::Column::Code)
+            .order_by_asc(roles::Column::Code)
+            .into_tuple::<(Uuid, String)>()
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+        {
+            codes_by_user.entry(user_id).or_default().push(code);
+        }
+
+        Ok(members
+            .into_iter()
+            .filter_map(|(member, user)| {
+                user.map(|user| MemberResponse {
+                    user_id: member.user_id,
+                    email: user.email,
+                    username: user.username,
+                    roles: codes_by_user.remove(&member.user_id).unwrap_or_default(),
+                    created_at: member.created_at,
+                })
+            })
+            .collect())
+    }
+
+    /// Adding twice is not an error
+    pub async fn add_member(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<String, CustomError> {
+        self.find_organization(organization_id).await?;
+        self.find_user(user_id).await?;
+        organization_members::Entity::insert(organization_members::ActiveModel {
+            organization_id: Set(organization_id),
+            user_id: Set(user_id),
+            created_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                organization_members::Column::OrganizationId,
+                organization_members::Column::UserId,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(&self.conn)
+        .await
+        .map_err(organization_db_error)?;
+        PERMISSION_CACHE.invalidate_user(user_id);
+        Ok("Member added".to_string())
+    }
+
+    /// The member's roles in the organization go with the membership
+    pub async fn remove_member(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<String, CustomError> {
+        let deleted = organization_members::Entity::delete_many()
+            .filter(organization_members::Column::OrganizationId.eq(organization_id))
+            .filter(organization_members::Column::UserId.eq(user_id))
+            .exec(&self.conn)
+            .await
+            .map_err(organization_db_error)?;
+
+        match deleted.rows_affected {
+            0 => Err(CustomError::new(
+                HttpCodeW::NotFound,
+                "User is not a member of this organization".to_string(),
+            )),
+            _ => {
+                PERMISSION_CACHE.invalidate_user(user_id);
+                Ok("Member removed".to_string())
+            }
+        }
+    }
+
+    /// Assigning twice is not an error
+    pub async fn assign_member_role(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+        role_id: Uuid,
+        caller: &TokenDetails,
+        client: ClientInfo,
+    ) -> Result<String, CustomError> {
+        self.find_member(organization_id, user_id).await?;
+        let role = roles::Entity::find_by_id(role_id)
+            .one(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+            .ok_or_else(|| CustomError::new(HttpCodeW::NotFound, "Role not found".to_string()))?;
+        if let Some(code) = self.admin_permission_of(role_id).await? {
+            return Err(CustomError::new(
+                HttpCodeW::Conflict,
+                format!(
+                    "Role {} grants {code} and cannot be assigned in an organization",
+                    role.code
+                ),
+            ));
+        }
+
+        organization_member_roles::Entity::insert(organization_member_roles::ActiveModel {
+            organization_id: Set(organization_id),
+            user_id: Set(user_id),
+            role_id: Set(role_id),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                organization_member_roles::Column::OrganizationId,
+                organization_member_roles::Column::UserId,
+                organization_member_roles::Column::RoleId,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(&self.conn)
+        .await
+        .map_err(organization_db_error)?;
+        PERMISSION_CACHE.invalidate_user(user_id);
+        AuditService::record(
+            NewAuditEvent::success(AuditEventType::RoleAssign)
+                .with_caller(caller)
+                .with_target(user_id)
+                .with_client(&client)
+                .with_metadata(json!({ "role": role.code, "organization_id": organization_id })),
+            &self.conn,
+        )
+        .await;
+        Ok("Role assigned".to_string())
+    }
+
+    pub async fn remove_member_role(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+        role_id: Uuid,
+        caller: &TokenDetails,
+        client: ClientInfo,
+    ) -> Result<String, CustomError> {
+        let deleted = organization_member_roles::Entity::delete_many()
+            .filter(organization_member_roles::Column::OrganizationId.eq(organization_id))
+            .filter(organization_member_roles::Column::UserId.eq(user_id))
+            .filter(organization_member_roles::Column::RoleId.eq(role_id))
+            .exec(&self.conn)
+            .await
+            .map_err(organization_db_error)?;
+
+        match deleted.rows_affected {
+            0 => Err(CustomError::new(
+                HttpCodeW::NotFound,
+                "Role is not assigned to this member".to_string(),
+            )),
+            _ => {
+                PERMISSION_CACHE.invalidate_user(user_id);
+                AuditService::record(
+                    NewAuditEvent::success(AuditEventType::RoleRemove)
+                        .with_caller(caller)
+                        .with_target(user_id)
+                        .with_client(&client)
+                        .with_metadata(json!({
+                            "role_id": role_id,
+                            "organization_id": organization_id,
+                        })),
+                    &self.conn,
+                )
+                .await;
+                Ok("Role removed".to_string())
+            }
+        }
+    }
+
+    /// Organizations of the user with the roles held in each, `active_organization_id`
+    /// comes from the caller's access token
+    pub async fn my_organizations(
+        &self,
+        user_id: Uuid,
+        active_organization_id: Option<Uuid>,
+    ) -> Result<Vec<MembershipResponse>, CustomError> {
+        let memberships = organization_members::Entity::find()
+            .filter(organization_members::Column::UserId.eq(user_id))
+            .find_also_related(organizations::Entity)
+            .order_by_asc(organizations::Column::Slug)
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        let mut codes_by_organization: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for (organization_id, code) in organization_member_roles::Entity::find()
+            .join(JoinType::InnerJoin, organization_member_roles::Relation::Roles.def())
+            .filter(organization_member_roles::Column::UserId.eq(user_id))
+            .select_only()
+            .column(organization_member_roles::Column::OrganizationId)
+            .column(roles::Column::Code)
+            .order_by_asc(roles::Column::Code)
+            .into_tuple::<(Uuid, String)>()
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+        {
+            codes_by_organization
+                .entry(organization_id)
+                .or_default()
+                .push(code);
+        }
+
+        Ok(memberships
+            .into_iter()
+            .filter_map(|(_, organization)| organization)
+            .map(|organization| MembershipResponse {
+                roles: codes_by_organization
+                    .remove(&organization.id)
+                    .unwrap_or_default(),
+                active: active_organization_id == Some(organization.id),
+                organization,
+            })
+            .collect())
+    }
+
+    async fn find_organization(
+        &self,
+        organization_id: Uuid,
+    ) -> Result<organizations::Model, CustomError> {
+        organizations::Entity::find_by_id(organization_id)
+            .one(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+            .ok_or_else(|| {
+                CustomError::new(HttpCodeW::NotFound, "Organization not found".to_string())
+            })
+    }
+
+    /// The first administration permission the role grants, if any
+    async fn admin_permission_of(&self, role_id: Uuid) -> Result<Option<String>, CustomError> {
+        let rows = self
+            .conn
+            .query_all(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                ROLE_GRANTS_SQL,
+                [role_id.into()],
+            ))
+            .await
+            .map_err(CustomError::from)?;
+
+        for row in rows {
+            let granted: String = row.try_get("", "code").map_err(CustomError::from)?;
+            if let Some(code) = ADMIN_PERMISSIONS
+                .iter()
+                .find(|code| permission_matches(&granted, code))
+            {
+                return Ok(Some(code.to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn find_member(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<organization_members::Model, CustomError> {
+        organization_members::Entity::find_by_id((organization_id, user_id))
+            .one(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+            .ok_or_else(|| {
+                CustomError::new(
+                    HttpCodeW::NotFound,
+                    "User is not a member of this organization".to_string(),
+                )
+            })
+    }
+
+    async fn find_user(&self, user_id: Uuid) -> Result<users::Model, CustomError> {
+        users::Entity::find_by_id(user_id)
+            .one(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+            .ok_or_else(|| CustomError::new(HttpCodeW::NotFound, "User not found".to_string()))
+    }
+}
+
+/// Lowercase letters, digits and dashes, used in URLs and by clients to pick an organization
+fn validate_slug(slug: &str) -> Result<String, CustomError> {
+    let slug = slug.trim();
+    let valid_chars = slug
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if slug.is_empty() || slug.len() > SLUG_MAX_LEN || !valid_chars {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            format!("Slug must be 1 to {SLUG_MAX_LEN} lowercase letters, digits or dashes"),
+        ));
+    }
+    Ok(slug.to_string())
+}
+
+fn validate_name(name: &str) -> Result<String, CustomError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            "Name must not be empty".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn organization_db_error(e: DbErr) -> CustomError {
+    match e.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => {
+            CustomError::new(HttpCodeW::Conflict, "Slug is already in use".to_string())
+        }
+        Some(SqlErr::ForeignKeyConstraintViolation(_)) => CustomError::new(
+            HttpCodeW::NotFound,
+            "Referenced organization, user or role does not exist".to_string(),
+        ),
+        _ => CustomError::from(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_db;
+
+    async fn role_id(db: &DatabaseConnection, code: &str) -> Uuid {
+        roles::Entity::find()
+            .filter(roles::Column::Code.eq(code))
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .id
+    }
+
+    #[actix_rt::test]
+    async fn roles_granting_admin_permissions_are_global_only() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        let service = OrganizationsService::new(&db);
+
+        // MODERATOR grants `user.read` and `session.read`
+        for code in ["ADMIN", "MODERATOR"] {
+            let admin_permission = service.admin_permission_of(role_id(&db, code).await).await;
+            assert!(admin_permission.unwrap().is_some(), "{code} must be rejected");
+        }
+        for code in ["OPERATOR", "USER", "GUEST"] {
+            let admin_permission = service.admin_permission_of(role_id(&db, code).await).await;
+            assert_eq!(admin_permission.unwrap(), None, "{code} must be allowed");
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use super::services::{policy_for, RateLimiter};
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+
+/// Throttles requests per client IP with the policy of the requested route.
+/// Wrap a scope with `middleware::from_fn(rate_limit)`; needs `web::Data<RateLimiter>`.
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(limiter) = req.app_data::<web::Data<RateLimiter>>() {
+        let ip_address = req
+            .connection_info()
+            .realip_remote_addr()
+            .unwrap_or("unknown")
+            .to_string();
+        let policy = policy_for(req.method().as_str(), req.path());
+
+        if let Err(retry_after) = limiter.check(&ip_address, policy) {
+            return Err(CustomError::new(
+                HttpCodeW::TooManyRequests,
+                format!("Too many requests, retry in {retry_after} seconds"),
+            )
+            .with_retry_after(retry_after)
+            .into());
+        }
+    }
+
+    next.call(req).await
+}
@@ -0,0 +1,5 @@
+mod middleware;
+mod services;
+
+pub use middleware::*;
+pub use services::*;
\ No newline at end of file
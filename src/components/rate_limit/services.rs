@@ -0,0 +1,222 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Token bucket settings: `capacity` requests in a burst, refilled continuously.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitPolicy {
+    pub name: &'static str,
+    pub capacity: f64,
+    pub refill_per_second: f64,
+}
+
+/// Endpoints that send mail or create accounts: 5 requests per 15 minutes
+pub const STRICT_POLICY: RateLimitPolicy = RateLimitPolicy {
+    name: "strict",
+    capacity: 5.0,
+    refill_per_second: 5.0 / 900.0,
+};
+
+/// Endpoints that check a credential: 10 requests per minute
+pub const CREDENTIALS_POLICY: RateLimitPolicy = RateLimitPolicy {
+    name: "credentials",
+    capacity: 10.0,
+    refill_per_second: 10.0 / 60.0,
+};
+
+/// Everything else: bursts of 120, 2 requests per second sustained
+pub const DEFAULT_POLICY: RateLimitPolicy = RateLimitPolicy {
+    name: "default",
+    capacity: 120.0,
+    refill_per_second: 2.0,
+};
+
+/// Most buckets kept at once, the oldest is dropped to make room for a new client
+const MAX_BUCKETS: usize = 100_000;
+
+/// How often `run_sweeper` drops buckets that have refilled completely
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Picks the policy of a request from its method and full path.
+pub fn policy_for(method: &str, path: &str) -> RateLimitPolicy {
+    if method != "POST" {
+        return DEFAULT_POLICY;
+    }
+    match path {
+        "/v1/auth/register" | "/v1/auth/password/forgot" => STRICT_POLICY,
+        "/v1/auth/login"
+        | "/v1/auth/mfa/verify"
+        | "/v1/auth/password/reset"
+        | "/v1/users/me/password"
+        | "/v1/users/me/email"
+        | "/v1/auth/webauthn/login/start"
+        | "/v1/auth/webauthn/login/finish"
+        | "/oauth/token" => CREDENTIALS_POLICY,
+        _ => DEFAULT_POLICY,
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, policy: &RateLimitPolicy) {
+        let elapsed = self.updated_at.elapsed().as_secs_f64();
+        self.tokens = (self.tokens + elapsed * policy.refill_per_second).min(policy.capacity);
+        self.updated_at = Instant::now();
+    }
+}
+
+type BucketKey = (String, &'static str);
+
+#[derive(Default)]
+struct Buckets {
+    by_key: HashMap<BucketKey, Bucket>,
+    /// Keys in creation order, oldest first
+    order: VecDeque<BucketKey>,
+}
+
+impl Buckets {
+    /// Drops buckets, oldest first, until there is room for one more
+    fn make_room(&mut self, max_buckets: usize) {
+        while self.by_key.len() >= max_buckets {
+            match self.order.pop_front() {
+                Some(key) => {
+                    self.by_key.remove(&key);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// In-memory token buckets keyed by client IP and policy. Shared by every worker,
+/// limits are per instance.
+#[derive(Clone)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<Buckets>>,
+    max_buckets: usize,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            buckets: Arc::default(),
+            max_buckets: MAX_BUCKETS,
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a token from the client's bucket, or returns the seconds until one is available.
+    pub fn check(&self, ip_address: &str, policy: RateLimitPolicy) -> Result<(), u64> {
+        let Ok(mut buckets) = self.buckets.lock() else {
+            // A poisoned lock should not take the API down with it
+            return Ok(());
+        };
+
+        let key = (ip_address.to_string(), policy.name);
+        if !buckets.by_key.contains_key(&key) {
+            buckets.make_room(self.max_buckets);
+            buckets.order.push_back(key.clone());
+        }
+        let bucket = buckets.by_key.entry(key).or_insert_with(|| Bucket {
+            tokens: policy.capacity,
+            updated_at: Instant::now(),
+        });
+        bucket.refill(&policy);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / policy.refill_per_second).ceil() as u64)
+        }
+    }
+
+    /// Drops the buckets that have refilled completely, a new one would start the same.
+    pub fn sweep(&self) {
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return;
+        };
+        buckets.by_key.retain(|(_, name), bucket| {
+            let policy = policy_for_name(name);
+            bucket.refill(&policy);
+            bucket.tokens < policy.capacity
+        });
+        let Buckets { by_key, order } = &mut *buckets;
+        order.retain(|key| by_key.contains_key(key));
+    }
+
+    /// Runs `sweep` every `every` until the process stops.
+    pub async fn run_sweeper(self, every: Duration) {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+            self.sweep();
+        }
+    }
+}
+
+fn policy_for_name(name: &str) -> RateLimitPolicy {
+    match name {
+        "strict" => STRICT_POLICY,
+        "credentials" => CREDENTIALS_POLICY,
+        _ => DEFAULT_POLICY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_bucket_is_evicted_at_capacity() {
+        let limiter = RateLimiter {
+            max_buckets: 2,
+            ..RateLimiter::default()
+        };
+        for _ in 0..STRICT_POLICY.capacity as usize {
+            limiter.check("10.0.0.1", STRICT_POLICY).unwrap();
+        }
+        assert!(limiter.check("10.0.0.1", STRICT_POLICY).is_err());
+
+        limiter.check("10.0.0.2", STRICT_POLICY).unwrap();
+        limiter.check("10.0.0.3", STRICT_POLICY).unwrap();
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.by_key.len(), 2);
+        assert!(!buckets.by_key.contains_key(&("10.0.0.1".to_string(), "strict")));
+    }
+
+    #[test]
+    fn sweep_drops_only_full_buckets() {
+        let limiter = RateLimiter::new();
+        limiter.check("10.0.0.1", STRICT_POLICY).unwrap();
+        limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .by_key
+            .insert(
+                ("10.0.0.2".to_string(), "strict"),
+                Bucket {
+                    tokens: STRICT_POLICY.capacity,
+                    updated_at: Instant::now(),
+                },
+            );
+
+        limiter.sweep();
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.by_key.len(), 1);
+        assert_eq!(buckets.order.len(), 1);
+        assert!(buckets.by_key.contains_key(&("10.0.0.1".to_string(), "strict")));
+    }
+}
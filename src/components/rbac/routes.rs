@@ -0,0 +1,246 @@
+use super::services::RbacService;
+use crate::components::auth::functions::AuthUser;
+use crate::components::sessions::client_info;
+use crate::entity::permissions::{CreatePermissionRequestBody, UpdatePermissionRequestBody};
+use crate::entity::roles::{CreateRoleRequestBody, UpdateRoleRequestBody};
+use crate::entity::user_permission_overrides::SetOverrideRequestBody;
+use crate::entity::user_roles::GrantValidityQuery;
+use crate::http_response::error_handler::{CustomError, ValidatedJson};
+use crate::http_response::prepared_response::check_response_ok_or_return_error;
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
+use uuid::Uuid;
+
+const RBAC_READ: &str = "rbac.read";
+const RBAC_WRITE: &str = "rbac.write";
+
+#[get("/rbac/roles")]
+pub async fn list_roles(
+    auth: AuthUser,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_READ])?;
+    check_response_ok_or_return_error(service.list_roles().await)
+}
+
+#[get("/rbac/roles/{id}")]
+pub async fn get_role(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_READ])?;
+    check_response_ok_or_return_error(service.get_role(path.into_inner()).await)
+}
+
+#[post("/rbac/roles")]
+pub async fn create_role(
+    auth: AuthUser,
+    payload: ValidatedJson<CreateRoleRequestBody>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    check_response_ok_or_return_error(service.create_role(payload.0).await)
+}
+
+#[patch("/rbac/roles/{id}")]
+pub async fn update_role(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    payload: ValidatedJson<UpdateRoleRequestBody>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    check_response_ok_or_return_error(service.update_role(path.into_inner(), payload.0).await)
+}
+
+#[delete("/rbac/roles/{id}")]
+pub async fn delete_role(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    check_response_ok_or_return_error(service.delete_role(path.into_inner()).await)
+}
+
+#[put("/rbac/roles/{id}/permissions/{permission_id}")]
+pub async fn attach_permission(
+    auth: AuthUser,
+    path: web::Path<(Uuid, Uuid)>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    let (role_id, permission_id) = path.into_inner();
+    check_response_ok_or_return_error(service.attach_permission(role_id, permission_id).await)
+}
+
+#[delete("/rbac/roles/{id}/permissions/{permission_id}")]
+pub async fn detach_permission(
+    auth: AuthUser,
+    path: web::Path<(Uuid, Uuid)>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    let (role_id, permission_id) = path.into_inner();
+    check_response_ok_or_return_error(service.detach_permission(role_id, permission_id).await)
+}
+
+#[get("/rbac/permissions")]
+pub async fn list_permissions(
+    auth: AuthUser,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_READ])?;
+    check_response_ok_or_return_error(service.list_permissions().await)
+}
+
+#[post("/rbac/permissions")]
+pub async fn create_permission(
+    auth: AuthUser,
+    payload: ValidatedJson<CreatePermissionRequestBody>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    check_response_ok_or_return_error(service.create_permission(payload.0).await)
+}
+
+#[patch("/rbac/permissions/{id}")]
+pub async fn update_permission(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    payload: ValidatedJson<UpdatePermissionRequestBody>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    check_response_ok_or_return_error(
+        service.update_permission(path.into_inner(), payload.0).await,
+    )
+}
+
+#[delete("/rbac/permissions/{id}")]
+pub async fn delete_permission(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    check_response_ok_or_return_error(service.delete_permission(path.into_inner()).await)
+}
+
+#[get("/rbac/users/{id}/roles")]
+pub async fn list_user_roles(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_READ])?;
+    check_response_ok_or_return_error(service.list_user_roles(path.into_inner()).await)
+}
+
+#[put("/rbac/users/{id}/roles/{role_id}")]
+pub async fn assign_role(
+    req: HttpRequest,
+    auth: AuthUser,
+    path: web::Path<(Uuid, Uuid)>,
+    validity: web::Query<GrantValidityQuery>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    let (user_id, role_id) = path.into_inner();
+    check_response_ok_or_return_error(
+        service
+            .assign_role(
+                user_id,
+                role_id,
+                validity.into_inner(),
+                &auth.details,
+                client_info(&req),
+            )
+            .await,
+    )
+}
+
+#[delete("/rbac/users/{id}/roles/{role_id}")]
+pub async fn remove_role(
+    req: HttpRequest,
+    auth: AuthUser,
+    path: web::Path<(Uuid, Uuid)>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    let (user_id, role_id) = path.into_inner();
+    check_response_ok_or_return_error(
+        service
+            .remove_role(user_id, role_id, &auth.details, client_info(&req))
+            .await,
+    )
+}
+
+#[get("/rbac/users/{id}/overrides")]
+pub async fn list_overrides(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_READ])?;
+    check_response_ok_or_return_error(service.list_overrides(path.into_inner()).await)
+}
+
+#[put("/rbac/users/{id}/overrides/{permission_id}")]
+pub async fn set_override(
+    req: HttpRequest,
+    auth: AuthUser,
+    path: web::Path<(Uuid, Uuid)>,
+    payload: ValidatedJson<SetOverrideRequestBody>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    let (user_id, permission_id) = path.into_inner();
+    check_response_ok_or_return_error(
+        service
+            .set_override(
+                user_id,
+                permission_id,
+                payload.0,
+                &auth.details,
+                client_info(&req),
+            )
+            .await,
+    )
+}
+
+#[delete("/rbac/users/{id}/overrides/{permission_id}")]
+pub async fn remove_override(
+    req: HttpRequest,
+    auth: AuthUser,
+    path: web::Path<(Uuid, Uuid)>,
+    service: web::Data<RbacService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&[RBAC_WRITE])?;
+    let (user_id, permission_id) = path.into_inner();
+    check_response_ok_or_return_error(
+        service
+            .remove_override(user_id, permission_id, &auth.details, client_info(&req))
+            .await,
+    )
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(list_roles);
+    config.service(get_role);
+    config.service(create_role);
+    config.service(update_role);
+    config.service(delete_role);
+    config.service(attach_permission);
+    config.service(detach_permission);
+    config.service(list_permissions);
+    config.service(create_permission);
+    config.service(update_permission);
+    config.service(delete_permission);
+    config.service(list_user_roles);
+    config.service(assign_role);
+    config.service(remove_role);
+    config.service(list_overrides);
+    config.service(set_override);
+    config.service(remove_override);
+}
@@ -0,0 +1,646 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::{TokenDetails, PERMISSION_CACHE};
+use crate::entity::enums::{AuditEventType, UserRole};
+use crate::entity::sessions::ClientInfo;
+use crate::entity::permissions::{CreatePermissionRequestBody, UpdatePermissionRequestBody};
+use crate::entity::roles::{CreateRoleRequestBody, RoleResponse, UpdateRoleRequestBody};
+use crate::entity::user_permission_overrides::{OverrideResponse, SetOverrideRequestBody};
+use crate::entity::user_roles::{GrantValidityQuery, UserRoleResponse};
+use crate::entity::{
+    permissions, role_permissions, roles, user_permission_overrides, user_roles, users,
+};
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use auth::permission_matching::is_wildcard;
+use crate::utils::helpers::now_date_time_utc;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveEnum, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend,
+    DbErr, EntityTrait, Iterable, JoinType, ModelTrait, QueryFilter, QueryOrder, QuerySelect,
+    RelationTrait, RuntimeErr, Set, SqlErr, SqlxError, Statement,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+use uuid::Uuid;
+
+const CODE_MAX_LEN: usize = 100;
+
+/// Users whose grants changed since `$1`: expired ones are deleted, started ones listed.
+/// Base roles are never time-bound, so `sync_user_base_role` mappings are not touched.
+const SWEEP_GRANTS_SQL: &str = r#"
+WITH expired_roles AS (
+    DELETE FROM auth.user_roles WHERE valid_until <= now() RETURNING user_id
+),
+expired_overrides AS (
+    DELETE FROM auth.user_permission_overrides WHERE valid_until <= now() RETURNING user_id
+)
+SELECT user_id FROM expired_roles
+UNION
+SELECT user_id FROM expired_overrides
+UNION
+SELECT user_id FROM auth.user_roles WHERE valid_from > $1 AND valid_from <= now()
+UNION
+SELECT user_id FROM auth.user_permission_overrides WHERE valid_from > $1 AND valid_from <= now()
+"#;
+
+#[derive(Clone)]
+pub struct RbacService {
+    conn: DatabaseConnection,
+}
+
+impl RbacService {
+    pub fn new(conn: &DatabaseConnection) -> Self {
+        Self { conn: conn.clone() }
+    }
+
+    pub async fn list_roles(&self) -> Result<Vec<RoleResponse>, CustomError> {
+        let roles = roles::Entity::find()
+            .order_by_desc(roles::Column::Priority)
+            .order_by_asc(roles::Column::Code)
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        let mut codes_by_role: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for (role_id, code) in role_permissions::Entity::find()
+            .join(JoinType::InnerJoin, role_permissions::Relation::Permissions.def())
+            .select_only()
+            .column(role_permissions::Column::RoleId)
+            .column(permissions::Column::Code)
+            .order_by_asc(permissions::Column::Code)
+            .into_tuple::<(Uuid, String)>()
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+        {
+            codes_by_role.entry(role_id).or_default().push(code);
+        }
+
+        Ok(roles
+            .into_iter()
+            .map(|role| RoleResponse {
+                permissions: codes_by_role.remove(&role.id).unwrap_or_default(),
+                role,
+            })
+            .collect())
+    }
+
+    pub async fn get_role(&self, role_id: Uuid) -> Result<RoleResponse, CustomError> {
+        let role = self.find_role(role_id).await?;
+        let permissions = role_permissions::Entity::find()
+            .join(JoinType::InnerJoin, role_permissions::Relation::Permissions.def())
+            .filter(role_permissions::Column::RoleId.eq(role_id))
+            .select_only()
+            .column(permissions::Column::Code)
+            .order_by_asc(permissions::Column::Code)
+            .into_tuple::<String>()
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        Ok(RoleResponse { role, permissions })
+    }
+
+    pub async fn create_role(
+        &self,
+        payload: CreateRoleRequestBody,
+    ) -> Result<roles::Model, CustomError> {
+        let code = validate_code(&payload.code)?;
+        let now = DateTimeWithTimeZone::from(now_date_time_utc());
+        roles::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            code: Set(code),
+            description: Set(payload.description),
+            priority: Set(payload.priority.unwrap_or(0)),
+            created_at: Set(now),
+            updated_at: Set(now),
+        }
+        .insert(&self.conn)
+        .await
+        .map_err(rbac_db_error)
+    }
+
+    pub async fn update_role(
+        &self,
+        role_id: Uuid,
+        payload: UpdateRoleRequestBody,
+    ) -> Result<roles::Model, CustomError> {
+        let mut active_role: roles::ActiveModel = self.find_role(role_id).await?.into();
+        if let Some(description) = payload.description {
+            active_role.description = Set(Some(description));
+        }
+        if let Some(priority) = payload.priority {
+            active_role.priority = Set(priority);
+        }
+        active_role.updated_at = Set(DateTimeWithTimeZone::from(now_date_time_utc()));
+        let updated = active_role.update(&self.conn).await.map_err(rbac_db_error)?;
+        // Roles inherit by priority, so moving one changes what others grant
+        if payload.priority.is_some() {
+            PERMISSION_CACHE.invalidate_all();
+        }
+        Ok(updated)
+    }
+
+    /// Base roles back `users.role`; `sync_user_base_role` maps users to them by code.
+    pub async fn delete_role(&self, role_id: Uuid) -> Result<String, CustomError> {
+        let role = self.find_role(role_id).await?;
+        if is_base_role(&role.code) {
+            return Err(CustomError::new(
+                HttpCodeW::Conflict,
+                format!("Role {} is a base role and cannot be deleted", role.code),
+            ));
+        }
+        role.delete(&self.conn).await.map_err(rbac_db_error)?;
+        PERMISSION_CACHE.invalidate_all();
+        Ok("Role deleted".to_string())
+    }
+
+    pub async fn list_permissions(&self) -> Result<Vec<permissions::Model>, CustomError> {
+        permissions::Entity::find()
+            .order_by_asc(permissions::Column::Code)
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)
+    }
+
+    pub async fn create_permission(
+        &self,
+        payload: CreatePermissionRequestBody,
+    ) -> Result<permissions::Model, CustomError> {
+        let code = validate_permission_code(&payload.code)?;
+        let now = DateTimeWithTimeZone::from(now_date_time_utc());
+        let permission = permissions::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            code: Set(code),
+            description: Set(payload.description),
+            created_at: Set(now),
+            updated_at: Set(now),
+        }
+        .insert(&self.conn)
+        .await
+        .map_err(rbac_db_error)?;
+        // Wildcard grants expand to the concrete codes that exist, the new one included
+        PERMISSION_CACHE.invalidate_all();
+        Ok(permission)
+    }
+
+    pub async fn update_permission(
+        &self,
+        permission_id: Uuid,
+        payload: UpdatePermissionRequestBody,
+    ) -> Result<permissions::Model, CustomError> {
+        let mut active_permission: permissions::ActiveModel =
+            self.find_permission(permission_id).await?.into();
+        if let Some(description) = payload.description {
+            active_permission.description = Set(Some(description));
+        }
+        active_permission.updated_at = Set(DateTimeWithTimeZone::from(now_date_time_utc()));
+        active_permission
+            .update(&self.conn)
+            .await
+            .map_err(rbac_db_error)
+    }
+
+    /// Role mappings and user overrides of the permission go with it (ON DELETE CASCADE)
+    pub async fn delete_permission(&self, permission_id: Uuid) -> Result<String, CustomError> {
+        let permission = self.find_permission(permission_id).await?;
+        permission.delete(&self.conn).await.map_err(rbac_db_error)?;
+        PERMISSION_CACHE.invalidate_all();
+        Ok("Permission deleted".to_string())
+    }
+
+    /// Attaching twice is not an error
+    pub async fn attach_permission(
+        &self,
+        role_id: Uuid,
+        permission_id: Uuid,
+    ) -> Result<String, CustomError> {
+        self.find_role(role_id).await?;
+        self.find_permission(permission_id).await?;
+        role_permissions::Entity::insert(role_permissions::ActiveModel {
+            role_id: Set(role_id),
+            permission_id: Set(permission_id),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                role_permissions::Column::RoleId,
+                role_permissions::Column::PermissionId,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(&self.conn)
+        .await
+        .map_err(rbac_db_error)?;
+        PERMISSION_CACHE.invalidate_all();
+        Ok("Permission attached".to_string())
+    }
+
+    pub async fn detach_permission(
+        &self,
+        role_id: Uuid,
+        permission_id: Uuid,
+    ) -> Result<String, CustomError> {
+        let deleted = role_permissions::Entity::delete_many()
+            .filter(role_permissions::Column::RoleId.eq(role_id))
+            .filter(role_permissions::Column::PermissionId.eq(permission_id))
+            .exec(&self.conn)
+            .await
+            .map_err(rbac_db_error)?;
+
+        match deleted.rows_affected {
+            0 => Err(CustomError::new(
+                HttpCodeW::NotFound,
+                "Permission is not attached to this role".to_string(),
+            )),
+            _ => {
+                PERMISSION_CACHE.invalidate_all();
+                Ok("Permission detached".to_string())
+            }
+        }
+    }
+
+    pub async fn list_user_roles(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<UserRoleResponse>, CustomError> {
+        self.find_user(user_id).await?;
+        let assignments = user_roles::Entity::find()
+            .filter(user_roles::Column::UserId.eq(user_id))
+            .find_also_related(roles::Entity)
+            .order_by_desc(roles::Column::Priority)
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        Ok(assignments
+            .into_iter()
+            .filter_map(|(assignment, role)| {
+                role.map(|role| UserRoleResponse {
+                    role,
+                    valid_from: assignment.valid_from,
+                    valid_until: assignment.valid_until,
+                })
+            })
+            .collect())
+    }
+
+    /// Assigning again replaces the validity window. Where `enforce_base_only_user_roles`
+    /// is installed, roles other than the base one are rejected by it.
+    pub async fn assign_role(
+        &self,
+        user_id: Uuid,
+        role_id: Uuid,
+        validity: GrantValidityQuery,
+        caller: &TokenDetails,
+        client: ClientInfo,
+    ) -> Result<String, CustomError> {
+        validate_window(validity.valid_from, validity.valid_until)?;
+        let user = self.find_user(user_id).await?;
+        let role = self.find_role(role_id).await?;
+        // The sweeper would delete it, leaving `users.role` without its mapping
+        let time_bound = validity.valid_from.is_some() || validity.valid_until.is_some();
+        if time_bound && user.role.to_value() == role.code {
+            return Err(CustomError::new(
+                HttpCodeW::Conflict,
+                format!("{} is the base role of the user and cannot be time-bound", role.code),
+            ));
+        }
+
+        user_roles::Entity::insert(user_roles::ActiveModel {
+            user_id: Set(user_id),
+            role_id: Set(role_id),
+            valid_from: Set(validity.valid_from),
+            valid_until: Set(validity.valid_until),
+        })
+        .on_conflict(
+            OnConflict::columns([user_roles::Column::UserId, user_roles::Column::RoleId])
+                .update_columns([user_roles::Column::ValidFrom, user_roles::Column::ValidUntil])
+                .to_owned(),
+        )
+        .exec_without_returning(&self.conn)
+        .await
+        .map_err(rbac_db_error)?;
+        PERMISSION_CACHE.invalidate_user(user_id);
+        AuditService::record(
+            NewAuditEvent::success(AuditEventType::RoleAssign)
+                .with_caller(caller)
+                .with_target(user_id)
+                .with_client(&client)
+                .with_metadata(json!({
+                    "role": role.code,
+                    "valid_from": validity.valid_from,
+                    "valid_until": validity.valid_until,
+                })),
+            &self.conn,
+        )
+        .await;
+        Ok("Role assigned".to_string())
+    }
+
+    /// The base role follows `users.role` through `sync_user_base_role`,
+    /// removing it here would leave the two out of step.
+    pub async fn remove_role(
+        &self,
+        user_id: Uuid,
+        role_id: Uuid,
+        caller: &TokenDetails,
+        client: ClientInfo,
+    ) -> Result<String, CustomError> {
+        let user = self.find_user(user_id).await?;
+        let role = self.find_role(role_id).await?;
+        if user.role.to_value() == role.code {
+            return Err(CustomError::new(
+                HttpCodeW::Conflict,
+                format!(
+                    "{} is the base role of the user, change the user's role instead",
+                    role.code
+                ),
+            ));
+        }
+
+        let deleted = user_roles::Entity::delete_many()
+            .filter(user_roles::Column::UserId.eq(user_id))
+            .filter(user_roles::Column::RoleId.eq(role_id))
+            .exec(&self.conn)
+            .await
+            .map_err(rbac_db_error)?;
+
+        match deleted.rows_affected {
+            0 => Err(CustomError::new(
+                HttpCodeW::NotFound,
+                "Role is not assigned to this user".to_string(),
+            )),
+            _ => {
+                PERMISSION_CACHE.invalidate_user(user_id);
+                AuditService::record(
+                    NewAuditEvent::success(AuditEventType::RoleRemove)
+                        .with_caller(caller)
+                        .with_target(user_id)
+                        .with_client(&client)
+                        .with_metadata(json!({ "role": role.code })),
+                    &self.conn,
+                )
+                .await;
+                Ok("Role removed".to_string())
+            }
+        }
+    }
+
+    pub async fn list_overrides(&self, user_id: Uuid) -> Result<Vec<OverrideResponse>, CustomError> {
+        self.find_user(user_id).await?;
+        user_permission_overrides::Entity::find()
+            .join(
+                JoinType::InnerJoin,
+                user_permission_overrides::Relation::Permissions.def(),
+            )
+            .filter(user_permission_overrides::Column::UserId.eq(user_id))
+            .select_only()
+            .column(user_permission_overrides::Column::PermissionId)
+            .column(permissions::Column::Code)
+            .column(user_permission_overrides::Column::Allow)
+            .column(user_permission_overrides::Column::ValidFrom)
+            .column(user_permission_overrides::Column::ValidUntil)
+            .order_by_asc(permissions::Column::Code)
+            .into_model::<OverrideResponse>()
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)
+    }
+
+    /// Creates the override or replaces an existing one
+    pub async fn set_override(
+        &self,
+        user_id: Uuid,
+        permission_id: Uuid,
+        payload: SetOverrideRequestBody,
+        caller: &TokenDetails,
+        client: ClientInfo,
+    ) -> Result<OverrideResponse, CustomError> {
+        validate_window(payload.valid_from, payload.valid_until)?;
+        self.find_user(user_id).await?;
+        let permission = self.find_permission(permission_id).await?;
+        user_permission_overrides::Entity::insert(user_permission_overrides::ActiveModel {
+            user_id: Set(user_id),
+            permission_id: Set(permission_id),
+            allow: Set(payload.allow),
+            valid_from: Set(payload.valid_from),
+            valid_until: Set(payload.valid_until),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                user_permission_overrides::Column::UserId,
+                user_permission_overrides::Column::PermissionId,
+            ])
+            .update_columns([
+                user_permission_overrides::Column::Allow,
+                user_permission_overrides::Column::ValidFrom,
+                user_permission_overrides::Column::ValidUntil,
+            ])
+            .to_owned(),
+        )
+        .exec_without_returning(&self.conn)
+        .await
+        .map_err(rbac_db_error)?;
+        PERMISSION_CACHE.invalidate_user(user_id);
+        AuditService::record(
+            NewAuditEvent::success(AuditEventType::PermissionOverrideSet)
+                .with_caller(caller)
+                .with_target(user_id)
+                .with_client(&client)
+                .with_metadata(json!({
+                    "permission": permission.code,
+                    "allow": payload.allow,
+                    "valid_from": payload.valid_from,
+                    "valid_until": payload.valid_until,
+                })),
+            &self.conn,
+        )
+        .await;
+
+        Ok(OverrideResponse {
+            permission_id,
+            code: permission.code,
+            allow: payload.allow,
+            valid_from: payload.valid_from,
+            valid_until: payload.valid_until,
+        })
+    }
+
+    pub async fn remove_override(
+        &self,
+        user_id: Uuid,
+        permission_id: Uuid,
+        caller: &TokenDetails,
+        client: ClientInfo,
+    ) -> Result<String, CustomError> {
+        let deleted = user_permission_overrides::Entity::delete_many()
+            .filter(user_permission_overrides::Column::UserId.eq(user_id))
+            .filter(user_permission_overrides::Column::PermissionId.eq(permission_id))
+            .exec(&self.conn)
+            .await
+            .map_err(rbac_db_error)?;
+
+        match deleted.rows_affected {
+            0 => Err(CustomError::new(
+                HttpCodeW::NotFound,
+                "No override for this permission".to_string(),
+            )),
+            _ => {
+                PERMISSION_CACHE.invalidate_user(user_id);
+                AuditService::record(
+                    NewAuditEvent::success(AuditEventType::PermissionOverrideRemove)
+                        .with_caller(caller)
+                        .with_target(user_id)
+                        .with_client(&client)
+                        .with_metadata(json!({ "permission_id": permission_id })),
+                    &self.conn,
+                )
+                .await;
+                Ok("Override removed".to_string())
+            }
+        }
+    }
+
+    /// Deletes expired role assignments and overrides, and drops the cached access
+    /// of their users and of users whose grants started after `since`.
+    pub async fn sweep_grants(&self, since: DateTimeWithTimeZone) -> Result<usize, CustomError> {
+        let rows = self
+            .conn
+            .query_all(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                SWEEP_GRANTS_SQL,
+                [since.into()],
+            ))
+            .await
+            .map_err(CustomError::from)?;
+
+        for row in &rows {
+            let user_id: Uuid = row.try_get("", "user_id").map_err(CustomError::from)?;
+            PERMISSION_CACHE.invalidate_user(user_id);
+        }
+        Ok(rows.len())
+    }
+
+    /// Runs `sweep_grants` every `every` until the process stops.
+    pub async fn run_grant_sweeper(self, every: Duration) {
+        let mut interval = tokio::time::interval(every);
+        let mut since = DateTimeWithTimeZone::from(now_date_time_utc());
+        loop {
+            interval.tick().await;
+            let started_at = DateTimeWithTimeZone::from(now_date_time_utc());
+            match self.sweep_grants(since).await {
+                Ok(_) => since = started_at,
+                Err(e) => log::error!("Grant sweeper error: {:?}", e),
+            }
+        }
+    }
+
+    async fn find_role(&self, role_id: Uuid) -> Result<roles::Model, CustomError> {
+        roles::Entity::find_by_id(role_id)
+            .one(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+            .ok_or_else(|| CustomError::new(HttpCodeW::NotFound, "Role not found".to_string()))
+    }
+
+    async fn find_permission(&self, permission_id: Uuid) -> Result<permissions::Model, CustomError> {
+        permissions::Entity::find_by_id(permission_id)
+            .one(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+            .ok_or_else(|| {
+                CustomError::new(HttpCodeW::NotFound, "Permission not found".to_string())
+            })
+    }
+
+    async fn find_user(&self, user_id: Uuid) -> Result<users::Model, CustomError> {
+        users::Entity::find_by_id(user_id)
+            .one(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+            .ok_or_else(|| CustomError::new(HttpCodeW::NotFound, "User not found".to_string()))
+    }
+}
+
+fn validate_window(
+    valid_from: Option<DateTimeWithTimeZone>,
+    valid_until: Option<DateTimeWithTimeZone>,
+) -> Result<(), CustomError> {
+    let Some(valid_until) = valid_until else {
+        return Ok(());
+    };
+    if valid_until <= DateTimeWithTimeZone::from(now_date_time_utc()) {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            "valid_until must be in the future".to_string(),
+        ));
+    }
+    if valid_from.is_some_and(|valid_from| valid_from >= valid_until) {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            "valid_from must be before valid_until".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_code(code: &str) -> Result<String, CustomError> {
+    let code = code.trim();
+    if code.is_empty() || code.len() > CODE_MAX_LEN || code.chars().any(char::is_whitespace) {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            format!("Code must be 1 to {CODE_MAX_LEN} characters without spaces"),
+        ));
+    }
+    Ok(code.to_string())
+}
+
+/// Wildcards are allowed only as a whole segment at the end: `*` or `resource.*`
+fn validate_permission_code(code: &str) -> Result<String, CustomError> {
+    let code = validate_code(code)?;
+    let stem = if is_wildcard(&code) {
+        code.strip_suffix('*').unwrap_or_default()
+    } else {
+        code.as_str()
+    };
+    if stem.contains('*') {
+        return Err(CustomError::new(
+            HttpCodeW::BadRequest,
+            "Wildcards must be `*` or end a code as `resource.*`".to_string(),
+        ));
+    }
+    Ok(code)
+}
+
+fn is_base_role(code: &str) -> bool {
+    UserRole::iter().any(|role| role.to_value() == code)
+}
+
+/// Triggers reject a change with `RAISE EXCEPTION` (SQLSTATE P0001), their
+/// message is passed on so the caller knows which rule was broken.
+fn rbac_db_error(e: DbErr) -> CustomError {
+    if let DbErr::Exec(RuntimeErr::SqlxError(SqlxError::Database(db_err)))
+    | DbErr::Query(RuntimeErr::SqlxError(SqlxError::Database(db_err))) = &e
+    {
+        if db_err.code().as_deref() == Some("P0001") {
+            return CustomError::new(
+                HttpCodeW::Conflict,
+                format!("Rejected by database rule: {}", db_err.message()),
+            );
+        }
+    }
+
+    match e.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => {
+            CustomError::new(HttpCodeW::Conflict, "Code is already in use".to_string())
+        }
+        Some(SqlErr::ForeignKeyConstraintViolation(_)) => CustomError::new(
+            HttpCodeW::NotFound,
+            "Referenced role, permission or user does not exist".to_string(),
+        ),
+        _ => CustomError::from(e),
+    }
+}
@@ -0,0 +1,5 @@
+mod routes;
+mod services;
+
+pub use routes::*;
+pub use services::*;
@@ -0,0 +1,39 @@
+use super::services::{client_info, SessionsService};
+use crate::components::auth::functions::AuthUser;
+use crate::entity::sessions::SessionsQuery;
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::prepared_response::check_response_ok_or_return_error;
+use actix_web::{delete, get, web, HttpRequest, HttpResponse};
+use uuid::Uuid;
+
+#[get("/auth/sessions")]
+pub async fn list_sessions(
+    req: HttpRequest,
+    auth: AuthUser,
+    query: web::Query<SessionsQuery>,
+    service: web::Data<SessionsService>,
+) -> Result<HttpResponse, CustomError> {
+    let refresh_cookie = req.cookie("refresh_token").map(|c| c.value().to_string());
+    let sessions = service
+        .list_active(&auth.details, query.user_id, refresh_cookie)
+        .await;
+    check_response_ok_or_return_error(sessions)
+}
+
+#[delete("/auth/sessions/{id}")]
+pub async fn terminate_session(
+    req: HttpRequest,
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<SessionsService>,
+) -> Result<HttpResponse, CustomError> {
+    let terminated = service
+        .terminate(&auth.details, path.into_inner(), client_info(&req))
+        .await;
+    check_response_ok_or_return_error(terminated)
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(list_sessions);
+    config.service(terminate_session);
+}
@@ -0,0 +1,343 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::TokenDetails;
+use crate::components::tokens::TokensService;
+use crate::entity::sessions::{ActiveModel, ClientInfo, Column, Entity, Model, SessionResponse};
+use crate::entity::enums::AuditEventType;
+use crate::entity::tokens;
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use crate::utils::helpers::now_date_time_utc;
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct SessionsService {
+    conn: DatabaseConnection,
+    tokens_service: TokensService,
+}
+
+impl SessionsService {
+    pub fn new(conn: &DatabaseConnection, tokens_service: &TokensService) -> Self {
+        Self {
+            conn: conn.clone(),
+            tokens_service: tokens_service.clone(),
+        }
+    }
+
+    /// One session per refresh token family: created at sign-in, kept up to date on rotation.
+    pub async fn upsert_for_refresh_token<C: ConnectionTrait>(
+        refresh: &tokens::Model,
+        client: &ClientInfo,
+        conn: &C,
+    ) -> Result<Model, CustomError> {
+        let session_token = refresh.family().to_string();
+        let existing = Entity::find()
+            .filter(Column::SessionToken.eq(session_token.clone()))
+            .one(conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        let now = DateTimeWithTimeZone::from(now_date_time_utc());
+        let result = match existing {
+            Some(session) => {
+                let mut active: ActiveModel = session.into();
+                active.ip_address = Set(Some(client.ip_address.clone()));
+                active.user_agent = Set(client.user_agent.clone());
+                active.expires_at = Set(refresh.expires_at);
+                active.updated_at = Set(now);
+                active.update(conn).await
+            }
+            None => {
+                ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    user_id: Set(refresh.user_id),
+                    session_token: Set(session_token),
+                    ip_address: Set(Some(client.ip_address.clone())),
+                    user_agent: Set(client.user_agent.clone()),
+                    expires_at: Set(refresh.expires_at),
+                    is_active: Set(true),
+                    active_organization_id: Set(None),
+                    created_at: Set(now),
+                    updated_at: Set(now),
+                }
+                .insert(conn)
+                .await
+            }
+        };
+
+        result.map_err(|e| {
+            CustomError::new(
+                HttpCodeW::InternalServerError,
+                format!("Failed to save session: {e}"),
+            )
+        })
+    }
+
+    pub async fn end_session_for_family<C: ConnectionTrait>(
+        family_id: Uuid,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        Self::deactivate(Column::SessionToken.eq(family_id.to_string()), conn).await
+    }
+
+    pub async fn end_all_sessions_for_user<C: ConnectionTrait>(
+        user_id: Uuid,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        Self::deactivate(Column::UserId.eq(user_id), conn).await
+    }
+
+    /// Ends every session of the user except the one of `keep_family`.
+    pub async fn end_other_sessions_for_user<C: ConnectionTrait>(
+        user_id: Uuid,
+        keep_family: Uuid,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        Self::deactivate(
+            Column::UserId
+                .eq(user_id)
+                .and(Column::SessionToken.ne(keep_family.to_string())),
+            conn,
+        )
+        .await
+    }
+
+    /// Organization later refreshes of the family are issued for, `None` for global roles only
+    pub async fn set_active_organization<C: ConnectionTrait>(
+        family_id: Uuid,
+        organization_id: Option<Uuid>,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        Entity::update_many()
+            .col_expr(Column::ActiveOrganizationId, Expr::value(organization_id))
+            .col_expr(
+                Column::UpdatedAt,
+                Expr::value(DateTimeWithTimeZone::from(now_date_time_utc())),
+            )
+            .filter(Column::SessionToken.eq(family_id.to_string()))
+            .filter(Column::IsActive.eq(true))
+            .exec(conn)
+            .await
+            .map(|res| res.rows_affected)
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Failed to update session: {e}"),
+                )
+            })
+    }
+
+    async fn deactivate<C: ConnectionTrait>(
+        filter: sea_orm::sea_query::SimpleExpr,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        Entity::update_many()
+            .col_expr(Column::IsActive, Expr::value(false))
+            .col_expr(
+                Column::UpdatedAt,
+                Expr::value(DateTimeWithTimeZone::from(now_date_time_utc())),
+            )
+            .filter(filter)
+            .filter(Column::IsActive.eq(true))
+            .exec(conn)
+            .await
+            .map(|res| res.rows_affected)
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Failed to end sessions: {e}"),
+                )
+            })
+    }
+
+    /// Active sessions of `user_id`, newest activity first. Other users need `session.read`.
+    pub async fn list_active(
+        &self,
+        caller: &TokenDetails,
+        user_id: Option<Uuid>,
+        refresh_cookie: Option<String>,
+    ) -> Result<Vec<SessionResponse>, CustomError> {
+        let caller_id = Self::caller_id(caller)?;
+        let user_id = user_id.unwrap_or(caller_id);
+        if user_id != caller_id && !caller.has_permission("session.read") {
+            return Err(CustomError::new(
+                HttpCodeW::Forbidden,
+                "Missing permission session.read".to_string(),
+            ));
+        }
+
+        let current_family = match refresh_cookie {
+            Some(raw) => self
+                .tokens_service
+                .find_refresh_by_raw(&raw, &self.conn)
+                .await?
+                .map(|token| token.family().to_string()),
+            None => None,
+        };
+
+        let sessions = Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::IsActive.eq(true))
+            .filter(Column::ExpiresAt.gt(DateTimeWithTimeZone::from(now_date_time_utc())))
+            .order_by_desc(Column::UpdatedAt)
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        Ok(sessions
+            .into_iter()
+            .map(|session| SessionResponse::new(session, current_family.as_deref()))
+            .collect())
+    }
+
+    /// Ends a session and revokes its refresh tokens. Other users' sessions need `session.terminate`.
+    pub async fn terminate(
+        &self,
+        caller: &TokenDetails,
+        session_id: Uuid,
+        client: ClientInfo,
+    ) -> Result<String, CustomError> {
+        let caller_id = Self::caller_id(caller)?;
+        let not_found = || CustomError::new(HttpCodeW::NotFound, "Session not found".to_string());
+
+        let txn = self.conn.begin().await.map_err(|e| {
+            CustomError::new(
+                HttpCodeW::InternalServerError,
+                format!("Txn begin error: {e}"),
+            )
+        })?;
+
+        let session = Entity::find_by_id(session_id)
+            .one(&txn)
+            .await
+            .map_err(CustomError::from)?
+            .filter(|session| session.is_active)
+            .ok_or_else(not_found)?;
+        if session.user_id != caller_id && !caller.has_permission("session.terminate") {
+            return Err(not_found());
+        }
+
+        let user_id = session.user_id;
+        let family_id = Uuid::parse_str(&session.session_token).ok();
+        if let Some(family_id) = family_id {
+            TokensService::revoke_token_family(family_id, &txn).await?;
+        }
+        let mut active: ActiveModel = session.into();
+        active.is_active = Set(false);
+        active.updated_at = Set(DateTimeWithTimeZone::from(now_date_time_utc()));
+        active.update(&txn).await.map_err(CustomError::from)?;
+
+        txn.commit().await.map_err(|e| {
+            CustomError::new(
+                HttpCodeW::InternalServerError,
+                format!("Txn commit error: {e}"),
+            )
+        })?;
+        // Access tokens of the session stop working now instead of at `exp`
+        if let Some(family_id) = family_id {
+            self.tokens_service
+                .revoke_access_tokens_for_family(family_id, &self.conn)
+                .await?;
+        }
+
+        AuditService::record(
+            NewAuditEvent::success(AuditEventType::TokenRevoke)
+                .with_caller(caller)
+                .with_target(user_id)
+                .with_client(&client)
+                .with_metadata(json!({ "reason": "Session terminated", "session_id": session_id })),
+            &self.conn,
+        )
+        .await;
+        Ok("Session terminated".to_string())
+    }
+
+    fn caller_id(caller: &TokenDetails) -> Result<Uuid, CustomError> {
+        caller.subject.user_id().ok_or_else(|| {
+            CustomError::new(
+                HttpCodeW::Forbidden,
+                "Sessions require a user access token".to_string(),
+            )
+        })
+    }
+}
+
+pub fn client_info(req: &HttpRequest) -> ClientInfo {
+    ClientInfo {
+        ip_address: req
+            .connection_info()
+            .realip_remote_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|| "unknown".to_string()),
+        user_agent: req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::auth::functions::TokenSubject;
+    use crate::components::users::UsersService;
+    use crate::utils::test_db;
+
+    #[actix_rt::test]
+    async fn terminate_revokes_the_access_tokens_of_the_session() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        let tokens_service = TokensService::new(&db, &UsersService::new(&db));
+        let service = SessionsService::new(&db, &tokens_service);
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let client = ClientInfo {
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: None,
+        };
+        let expires_at = (chrono::Utc::now() + chrono::Duration::minutes(15)).timestamp();
+
+        let mut access_tokens = Vec::new();
+        let mut sessions = Vec::new();
+        for _ in 0..2 {
+            let (_, refresh) = tokens_service
+                .create_refresh_token_for_user(user_id, 60, &db)
+                .await
+                .unwrap();
+            let session = SessionsService::upsert_for_refresh_token(&refresh, &client, &db)
+                .await
+                .unwrap();
+            sessions.push(session);
+            let token_uuid = Uuid::new_v4();
+            tokens_service
+                .record_access_token(user_id, token_uuid, refresh.family(), expires_at, &db)
+                .await
+                .unwrap();
+            access_tokens.push(token_uuid);
+        }
+        let caller = TokenDetails {
+            subject: TokenSubject::User(user_id),
+            token_uuid: access_tokens[1],
+            expires_in: Some(expires_at),
+            token: None,
+            perms: Vec::new(),
+        };
+
+        service.terminate(&caller, sessions[0].id, client).await.unwrap();
+        let terminated = tokens_service.is_access_token_revoked(access_tokens[0]).await;
+        let other = tokens_service.is_access_token_revoked(access_tokens[1]).await;
+
+        test_db::delete_user(&db, user_id).await;
+        assert!(terminated.unwrap());
+        assert!(!other.unwrap());
+    }
+}
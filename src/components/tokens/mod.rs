@@ -1,3 +1,5 @@
+mod revocation_cache;
 mod services;
 
+pub use revocation_cache::*;
 pub use services::*;
\ No newline at end of file
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How long a revocation lookup is trusted before asking the database again.
+/// Revocations made by this instance are visible immediately, other instances
+/// pick them up within this window.
+const ENTRY_TTL: Duration = Duration::from_secs(30);
+
+/// Expired entries are swept once the map grows past this size
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// In-memory cache of access token revocation state keyed by `token_uuid`.
+/// Shared by every clone of `TokensService`.
+#[derive(Clone, Default)]
+pub struct RevocationCache {
+    entries: Arc<RwLock<HashMap<Uuid, (bool, Instant)>>>,
+}
+
+impl RevocationCache {
+    pub fn get(&self, token_uuid: &Uuid) -> Option<bool> {
+        let entries = self.entries.read().ok()?;
+        entries
+            .get(token_uuid)
+            .filter(|(_, cached_at)| cached_at.elapsed() < ENTRY_TTL)
+            .map(|(revoked, _)| *revoked)
+    }
+
+    pub fn insert(&self, token_uuid: Uuid, revoked: bool) {
+        if let Ok(mut entries) = self.entries.write() {
+            if entries.len() >= SWEEP_THRESHOLD {
+                entries.retain(|_, (_, cached_at)| cached_at.elapsed() < ENTRY_TTL);
+            }
+            entries.insert(token_uuid, (revoked, Instant::now()));
+        }
+    }
+
+    /// Bulk revocations don't know which tokens are cached, so everything is dropped
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.clear();
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
 // For a specific base64 engine
 use crate::components::auth::functions::{generate_opaque_refresh, hash_refresh};
 // For base64 encoding
@@ -5,7 +6,10 @@ use crate::components::users::enums::SearchValue;
 use crate::components::users::UsersService;
 use crate::entity;
 use crate::entity::tokens::{ActiveModel, Column, Entity, Model, ValueFilterBy};
-use crate::entity::TokenType::{EmailVerification, Refresh};
+use crate::components::tokens::RevocationCache;
+use crate::entity::TokenType::{Access, EmailChange, EmailVerification, Refresh, ResetPassword};
+use crate::entity::TokenType;
+use crate::entity::AuditEventType;
 use crate::http_response::error_handler::CustomError;
 use crate::http_response::HttpCodeW;
 use crate::utils::helpers::now_date_time_utc;
@@ -15,16 +19,19 @@ use chrono::Duration;
 use general_purpose::URL_SAFE_NO_PAD;
 use rand::random;
 use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
 use sea_orm::{
-    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction,
-    DbErr, EntityTrait, IntoActiveModel, QueryFilter, Set,
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection,
+    DatabaseTransaction, DbErr, EntityTrait, IntoActiveModel, QueryFilter, QuerySelect, Set,
 };
+use serde_json::json;
 use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct TokensService {
     conn: DatabaseConnection,
     users_service: UsersService,
+    revocation_cache: RevocationCache,
 }
 
 impl TokensService {
@@ -32,12 +39,175 @@ impl TokensService {
         Self {
             conn: conn.clone(),
             users_service: users_service.clone(),
+            revocation_cache: RevocationCache::default(),
         }
     }
-    pub async fn find_refresh_by_raw(
+
+    /// Stores an ACCESS row for an issued JWT so it can be revoked before `exp`.
+    /// `token` holds the JWT's `token_uuid`, the signed token itself is never stored.
+    /// `family_id` is the refresh token chain of the session the JWT belongs to.
+    pub async fn record_access_token<C: ConnectionTrait>(
+        &self,
+        user_id: Uuid,
+        token_uuid: Uuid,
+        family_id: Uuid,
+        expires_at: i64,
+        conn: &C,
+    ) -> Result<Model, CustomError> {
+        let expires_at = chrono::DateTime::from_timestamp(expires_at, 0)
+            .unwrap_or_else(now_date_time_utc);
+        ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            token: Set(token_uuid.to_string()),
+            refresh_token: Set(None),
+            parent_id: Set(None),
+            family_id: Set(Some(family_id)),
+            token_type: Set(Access),
+            expires_at: Set(DateTimeWithTimeZone::from(expires_at)),
+            is_revoked: Set(false),
+            created_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+            updated_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+        }
+        .insert(conn)
+        .await
+        .map_err(|e| {
+            CustomError::new(
+                HttpCodeW::InternalServerError,
+                format!("Failed to record access token: {e}"),
+            )
+        })
+    }
+
+    /// Denylist check for access tokens. Tokens without a row (client_credentials
+    /// tokens, tokens issued before rows were recorded) are not revoked.
+    pub async fn is_access_token_revoked(&self, token_uuid: Uuid) -> Result<bool, CustomError> {
+        if let Some(revoked) = self.revocation_cache.get(&token_uuid) {
+            return Ok(revoked);
+        }
+
+        let revoked = Entity::find()
+            .filter(Column::Token.eq(token_uuid.to_string()))
+            .filter(Column::TokenType.eq(Access))
+            .one(&self.conn)
+            .await
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Database error: {e}"),
+                )
+            })?
+            .is_some_and(|row| row.is_revoked);
+
+        self.revocation_cache.insert(token_uuid, revoked);
+        Ok(revoked)
+    }
+
+    pub async fn revoke_access_token<C: ConnectionTrait>(
+        &self,
+        token_uuid: Uuid,
+        conn: &C,
+    ) -> Result<(), CustomError> {
+        Entity::update_many()
+            .col_expr(Column::IsRevoked, Expr::value(true))
+            .col_expr(
+                Column::UpdatedAt,
+                Expr::value(DateTimeWithTimeZone::from(now_date_time_utc())),
+            )
+            .filter(Column::Token.eq(token_uuid.to_string()))
+            .filter(Column::TokenType.eq(Access))
+            .exec(conn)
+            .await
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Failed to revoke access token: {e}"),
+                )
+            })?;
+
+        self.revocation_cache.insert(token_uuid, true);
+        Ok(())
+    }
+
+    /// Denylists every access token of the user that has not expired yet.
+    pub async fn revoke_all_access_tokens_for_user<C: ConnectionTrait>(
+        &self,
+        user_id: Uuid,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        self.revoke_other_access_tokens_for_user(user_id, None, conn)
+            .await
+    }
+
+    /// Denylists the access tokens issued to one session, i.e. to refresh token chain `family_id`.
+    pub async fn revoke_access_tokens_for_family<C: ConnectionTrait>(
+        &self,
+        family_id: Uuid,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        let revoked = Entity::update_many()
+            .col_expr(Column::IsRevoked, Expr::value(true))
+            .col_expr(
+                Column::UpdatedAt,
+                Expr::value(DateTimeWithTimeZone::from(now_date_time_utc())),
+            )
+            .filter(Column::FamilyId.eq(family_id))
+            .filter(Column::TokenType.eq(Access))
+            .filter(Column::IsRevoked.eq(false))
+            .filter(Column::ExpiresAt.gt(DateTimeWithTimeZone::from(now_date_time_utc())))
+            .exec(conn)
+            .await
+            .map(|res| res.rows_affected)
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Failed to revoke access tokens: {e}"),
+                )
+            })?;
+
+        self.revocation_cache.clear();
+        Ok(revoked)
+    }
+
+    /// Like `revoke_all_access_tokens_for_user`, but `keep` (a `token_uuid`) stays valid.
+    pub async fn revoke_other_access_tokens_for_user<C: ConnectionTrait>(
+        &self,
+        user_id: Uuid,
+        keep: Option<Uuid>,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        let mut query = Entity::update_many()
+            .col_expr(Column::IsRevoked, Expr::value(true))
+            .col_expr(
+                Column::UpdatedAt,
+                Expr::value(DateTimeWithTimeZone::from(now_date_time_utc())),
+            )
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::TokenType.eq(Access))
+            .filter(Column::IsRevoked.eq(false))
+            .filter(Column::ExpiresAt.gt(DateTimeWithTimeZone::from(now_date_time_utc())));
+        if let Some(keep) = keep {
+            query = query.filter(Column::Token.ne(keep.to_string()));
+        }
+
+        let revoked = query
+            .exec(conn)
+            .await
+            .map(|res| res.rows_affected)
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Failed to revoke access tokens: {e}"),
+                )
+            })?;
+
+        self.revocation_cache.clear();
+        Ok(revoked)
+    }
+    pub async fn find_refresh_by_raw<C: ConnectionTrait>(
         &self,
         raw: &str,
-        txn: &DatabaseTransaction,
+        txn: &C,
     ) -> Result<Option<Model>, CustomError> {
         let hashed = hash_refresh(raw);
         Entity::find()
@@ -51,48 +221,258 @@ impl TokensService {
                 )
             })
     }
-    pub async fn is_token_available(
+    /// Like `find_refresh_by_raw`, but the row stays locked until `txn` ends, so
+    /// concurrent rotations of the same token run one after the other.
+    pub async fn lock_refresh_by_raw(
         &self,
-        raw_refresh_token: &String,
+        raw: &str,
         txn: &DatabaseTransaction,
     ) -> Result<Option<Model>, CustomError> {
-        let found = self.find_refresh_by_raw(&raw_refresh_token, &txn).await?;
-        let model = match found {
-            None => {
-                return Err(CustomError::new(
-                    HttpCodeW::Unauthorized,
-                    "Invalid refresh token".into(),
-                ))
-            }
-            Some(m) if m.is_revoked => {
-                return Err(CustomError::new(
-                    HttpCodeW::Unauthorized,
-                    "Refresh token revoked".into(),
-                ))
-            }
-            Some(m) if m.is_expired() => {
-                return Err(CustomError::new(
-                    HttpCodeW::Unauthorized,
-                    "Refresh token expired".into(),
-                ))
-            }
-            Some(m) => m,
-        };
-        Ok(Some(model))
+        let hashed = hash_refresh(raw);
+        Entity::find()
+            .filter(Column::RefreshToken.eq(hashed))
+            .lock_exclusive()
+            .one(txn)
+            .await
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Database error: {e}"),
+                )
+            })
+    }
+
+    /// Whether the refresh token was rotated out, i.e. a successor was issued for it.
+    /// Revoked tokens without one were ended by logout, a password change or a
+    /// terminated session, replaying them is not a sign of theft.
+    pub async fn was_rotated<C: ConnectionTrait>(
+        model: &Model,
+        conn: &C,
+    ) -> Result<bool, CustomError> {
+        Entity::find()
+            .filter(Column::ParentId.eq(model.id))
+            .one(conn)
+            .await
+            .map(|successor| successor.is_some())
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Database error: {e}"),
+                )
+            })
     }
+
+    /// Revokes the token unless it already is. `false` means another request
+    /// revoked it first, so single-use tokens must be rejected.
     pub async fn revoke_token(
         model: Model,
         txn: &DatabaseTransaction,
     ) -> Result<bool, CustomError> {
-        let mut active_model: ActiveModel = model.into();
-        active_model.is_revoked = Set(true);
+        Entity::update_many()
+            .col_expr(Column::IsRevoked, Expr::value(true))
+            .col_expr(
+                Column::UpdatedAt,
+                Expr::value(DateTimeWithTimeZone::from(now_date_time_utc())),
+            )
+            .filter(Column::Id.eq(model.id))
+            .filter(Column::IsRevoked.eq(false))
+            .exec(txn)
+            .await
+            .map(|res| res.rows_affected == 1)
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Failed to revoke token: {}", e),
+                )
+            })
+    }
 
-        active_model.update(txn).await.map(|_| true).map_err(|e| {
-            CustomError::new(
-                HttpCodeW::InternalServerError,
-                format!("Failed to revoke token: {}", e),
+    /// Revokes every refresh token of a rotation chain, used when a revoked member is replayed.
+    pub async fn revoke_token_family<C: ConnectionTrait>(
+        family_id: Uuid,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        Entity::update_many()
+            .col_expr(Column::IsRevoked, Expr::value(true))
+            .col_expr(
+                Column::UpdatedAt,
+                Expr::value(DateTimeWithTimeZone::from(now_date_time_utc())),
             )
-        })
+            .filter(
+                Condition::any()
+                    .add(Column::FamilyId.eq(family_id))
+                    .add(Column::Id.eq(family_id)),
+            )
+            .filter(Column::TokenType.eq(Refresh))
+            .filter(Column::IsRevoked.eq(false))
+            .exec(conn)
+            .await
+            .map(|res| res.rows_affected)
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Failed to revoke token family: {e}"),
+                )
+            })
+    }
+
+    /// Revokes every live refresh token of the user, e.g. after a password reset.
+    pub async fn revoke_all_refresh_tokens_for_user<C: ConnectionTrait>(
+        user_id: Uuid,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        Self::revoke_other_refresh_tokens_for_user(user_id, None, conn).await
+    }
+
+    /// Like `revoke_all_refresh_tokens_for_user`, but the chain `keep_family` stays signed in.
+    pub async fn revoke_other_refresh_tokens_for_user<C: ConnectionTrait>(
+        user_id: Uuid,
+        keep_family: Option<Uuid>,
+        conn: &C,
+    ) -> Result<u64, CustomError> {
+        let mut query = Entity::update_many()
+            .col_expr(Column::IsRevoked, Expr::value(true))
+            .col_expr(
+                Column::UpdatedAt,
+                Expr::value(DateTimeWithTimeZone::from(now_date_time_utc())),
+            )
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::TokenType.eq(Refresh))
+            .filter(Column::IsRevoked.eq(false));
+        if let Some(keep_family) = keep_family {
+            query = query.filter(
+                Condition::all()
+                    .add(
+                        Condition::any()
+                            .add(Column::FamilyId.is_null())
+                            .add(Column::FamilyId.ne(keep_family)),
+                    )
+                    .add(Column::Id.ne(keep_family)),
+            );
+        }
+
+        query
+            .exec(conn)
+            .await
+            .map(|res| res.rows_affected)
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Failed to revoke refresh tokens: {e}"),
+                )
+            })
+    }
+
+    /// Issues a single-use password reset token. Only the hash is stored, the raw
+    /// value is returned so it can be mailed. Older reset tokens of the user are revoked.
+    pub async fn create_reset_password_token_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<(String, Model), DbErr> {
+        self.create_single_use_token(user_id, ResetPassword, &self.conn)
+            .await
+    }
+
+    /// Same as the reset token, confirms the pending email change of the user. Run it in
+    /// the transaction that stores the new pending address, so no older link stays valid
+    /// once the address changes.
+    pub async fn create_email_change_token_for_user<C: ConnectionTrait>(
+        &self,
+        user_id: Uuid,
+        conn: &C,
+    ) -> Result<(String, Model), DbErr> {
+        self.create_single_use_token(user_id, EmailChange, conn).await
+    }
+
+    async fn create_single_use_token<C: ConnectionTrait>(
+        &self,
+        user_id: Uuid,
+        token_type: TokenType,
+        conn: &C,
+    ) -> Result<(String, Model), DbErr> {
+        Entity::update_many()
+            .col_expr(Column::IsRevoked, Expr::value(true))
+            .col_expr(
+                Column::UpdatedAt,
+                Expr::value(DateTimeWithTimeZone::from(now_date_time_utc())),
+            )
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::TokenType.eq(token_type.clone()))
+            .filter(Column::IsRevoked.eq(false))
+            .exec(conn)
+            .await?;
+
+        let (raw, hash) = generate_opaque_refresh();
+        let expires_at =
+            now_date_time_utc() + Duration::minutes(token_type.default_expiration_minutes());
+        let active_model = ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            token: Set(hash),
+            refresh_token: Set(None),
+            parent_id: Set(None),
+            family_id: Set(None),
+            token_type: Set(token_type),
+            expires_at: Set(DateTimeWithTimeZone::from(expires_at)),
+            is_revoked: Set(false),
+            created_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+            updated_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+        };
+
+        let model = active_model.insert(conn).await?;
+        Ok((raw, model))
+    }
+
+    pub async fn find_reset_password_token_by_raw(
+        &self,
+        raw: &str,
+        txn: &DatabaseTransaction,
+    ) -> Result<Model, CustomError> {
+        Self::find_single_use_token_by_raw(raw, ResetPassword, txn)
+            .await?
+            .ok_or_else(|| {
+                CustomError::new(
+                    HttpCodeW::BadRequest,
+                    "Invalid or expired reset token".to_string(),
+                )
+            })
+    }
+
+    pub async fn find_email_change_token_by_raw(
+        &self,
+        raw: &str,
+        txn: &DatabaseTransaction,
+    ) -> Result<Model, CustomError> {
+        Self::find_single_use_token_by_raw(raw, EmailChange, txn)
+            .await?
+            .ok_or_else(|| {
+                CustomError::new(
+                    HttpCodeW::BadRequest,
+                    "Invalid or expired email change token".to_string(),
+                )
+            })
+    }
+
+    async fn find_single_use_token_by_raw(
+        raw: &str,
+        token_type: TokenType,
+        txn: &DatabaseTransaction,
+    ) -> Result<Option<Model>, CustomError> {
+        // Locked until `txn` ends: a second redemption waits and then sees it revoked
+        let found = Entity::find()
+            .filter(Column::Token.eq(hash_refresh(raw)))
+            .filter(Column::TokenType.eq(token_type))
+            .lock_exclusive()
+            .one(txn)
+            .await
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Database error: {e}"),
+                )
+            })?;
+
+        Ok(found.filter(|m| m.is_valid()))
     }
 
     fn create_token(&self, user_id: Uuid) -> ActiveModel {
@@ -106,6 +486,8 @@ impl TokensService {
             user_id: Set(user_id),
             token: Set(token_string),
             refresh_token: Set(None),
+            parent_id: Set(None),
+            family_id: Set(None),
             token_type: Set(EmailVerification),
             expires_at: Set(DateTimeWithTimeZone::from(expires_at)),
             is_revoked: Set(false),
@@ -114,24 +496,30 @@ impl TokensService {
         }
     }
 
-    pub async fn create_refresh_token_for_user(
+    /// First refresh token of a new family, i.e. of a new session
+    pub async fn create_refresh_token_for_user<C: ConnectionTrait>(
         &self,
         user_id: Uuid,
         refresh_ttl_minutes: i64,
+        conn: &C,
     ) -> Result<(String, Model), DbErr> {
         let (raw, active_model) = Self::create_active_model_for_token(user_id, refresh_ttl_minutes);
 
-        let model = active_model.insert(&self.conn).await?;
+        let model = active_model.insert(conn).await?;
         Ok((raw, model))
     }
 
+    /// Rotation: the new refresh token joins the family of `parent`
     pub async fn create_refresh_token_for_user_txn(
         &self,
-        user_id: Uuid,
+        parent: &Model,
         refresh_ttl_minutes: i64,
         txn: &DatabaseTransaction,
     ) -> Result<(String, Model), DbErr> {
-        let (raw, active_model) = Self::create_active_model_for_token(user_id, refresh_ttl_minutes);
+        let (raw, mut active_model) =
+            Self::create_active_model_for_token(parent.user_id, refresh_ttl_minutes);
+        active_model.parent_id = Set(Some(parent.id));
+        active_model.family_id = Set(Some(parent.family()));
 
         let model = active_model.insert(txn).await?;
         Ok((raw, model))
@@ -144,9 +532,10 @@ impl TokensService {
         let (raw, hash) = generate_opaque_refresh();
         let expires_at = now_date_time_utc() + Duration::minutes(refresh_ttl_minutes);
         let unique_placeholder = Uuid::new_v4().to_string();
+        let id = Uuid::new_v4();
 
         let active_model = ActiveModel {
-            id: Set(Uuid::new_v4()),
+            id: Set(id),
             user_id: Set(user_id),
             token: Set(unique_placeholder),
             token_type: Set(Refresh),
@@ -155,6 +544,8 @@ impl TokensService {
             created_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
             updated_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
             refresh_token: Set(Some(hash)),
+            parent_id: Set(None),
+            family_id: Set(Some(id)),
         };
         (raw, active_model)
     }
@@ -196,6 +587,7 @@ impl TokensService {
         token: String,
         ip_address: String,
     ) -> Result<String, CustomError> {
+        let audit_ip_address = ip_address.clone();
         let query = Entity::find()
             .filter(Column::Token.like(token))
             .filter(Column::IsRevoked.eq(false));
@@ -203,6 +595,7 @@ impl TokensService {
 
         match token_wrapper {
             Ok(Some(response_model)) => {
+                let user_id = response_model.user_id;
                 let user_result = self
                     .users_service
                     .find("id", SearchValue::Uuid(response_model.user_id))
@@ -226,7 +619,16 @@ impl TokensService {
                             active_token_model.token_type = Set(Refresh);
 
                             match active_token_model.update(&self.conn).await {
-                                Ok(_) => Ok("Email verified successfully".to_string()),
+                                Ok(_) => {
+                                    AuditService::record(
+                                        NewAuditEvent::success(AuditEventType::EmailVerify)
+                                            .with_user(user_id)
+                                            .with_ip_address(&audit_ip_address),
+                                        &self.conn,
+                                    )
+                                    .await;
+                                    Ok("Email verified successfully".to_string())
+                                }
                                 Err(e) => Err(CustomError::new(
                                     HttpCodeW::InternalServerError,
                                     format!("Failed to revoke token: {}", e),
@@ -245,10 +647,19 @@ impl TokensService {
                     )),
                 }
             }
-            Ok(e) => Err(CustomError::new(
-                HttpCodeW::Forbidden,
-                format!("Token not found or {:?}", e).to_string(),
-            )),
+            Ok(e) => {
+                AuditService::record(
+                    NewAuditEvent::failure(AuditEventType::EmailVerify)
+                        .with_ip_address(&audit_ip_address)
+                        .with_metadata(json!({ "reason": "Unknown verification token" })),
+                    &self.conn,
+                )
+                .await;
+                Err(CustomError::new(
+                    HttpCodeW::Forbidden,
+                    format!("Token not found or {:?}", e).to_string(),
+                ))
+            }
             Err(e) => Err(CustomError::new(
                 HttpCodeW::NotFound,
                 format!("Database error: {}", e),
@@ -256,3 +667,95 @@ impl TokensService {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_db;
+    use sea_orm::TransactionTrait;
+
+    #[actix_rt::test]
+    async fn concurrent_rotation_sees_the_token_rotated() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        let service = TokensService::new(&db, &UsersService::new(&db));
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let (raw, _) = service.create_refresh_token_for_user(user_id, 60, &db).await.unwrap();
+
+        let first = db.begin().await.unwrap();
+        let locked = service.lock_refresh_by_raw(&raw, &first).await.unwrap().unwrap();
+
+        // Blocks on the row lock until the first rotation commits
+        let second = tokio::spawn({
+            let (db, service, raw) = (db.clone(), service.clone(), raw.clone());
+            async move {
+                let txn = db.begin().await.unwrap();
+                let seen = service.lock_refresh_by_raw(&raw, &txn).await.unwrap().unwrap();
+                let rotated = TokensService::was_rotated(&seen, &txn).await.unwrap();
+                let revoked_again = TokensService::revoke_token(seen.clone(), &txn).await.unwrap();
+                (seen.is_revoked, rotated, revoked_again)
+            }
+        });
+
+        service
+            .create_refresh_token_for_user_txn(&locked, 60, &first)
+            .await
+            .unwrap();
+        assert!(TokensService::revoke_token(locked, &first).await.unwrap());
+        first.commit().await.unwrap();
+
+        let (is_revoked, rotated, revoked_again) = second.await.unwrap();
+        test_db::delete_user(&db, user_id).await;
+        assert!(is_revoked);
+        assert!(rotated);
+        assert!(!revoked_again);
+    }
+
+    #[actix_rt::test]
+    async fn logged_out_token_is_not_rotated() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        let service = TokensService::new(&db, &UsersService::new(&db));
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let (_, model) = service.create_refresh_token_for_user(user_id, 60, &db).await.unwrap();
+
+        let txn = db.begin().await.unwrap();
+        assert!(TokensService::revoke_token(model.clone(), &txn).await.unwrap());
+        let rotated = TokensService::was_rotated(&model, &txn).await.unwrap();
+        txn.commit().await.unwrap();
+
+        test_db::delete_user(&db, user_id).await;
+        assert!(!rotated);
+    }
+
+    #[actix_rt::test]
+    async fn email_change_link_is_replaced_with_the_pending_address() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        let service = TokensService::new(&db, &UsersService::new(&db));
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let (first, _) = service.create_email_change_token_for_user(user_id, &db).await.unwrap();
+
+        // Rolled back with the pending address: the earlier link stays the valid one
+        let txn = db.begin().await.unwrap();
+        service.create_email_change_token_for_user(user_id, &txn).await.unwrap();
+        txn.rollback().await.unwrap();
+        let txn = db.begin().await.unwrap();
+        let kept = service.find_email_change_token_by_raw(&first, &txn).await.is_ok();
+        txn.commit().await.unwrap();
+
+        let txn = db.begin().await.unwrap();
+        service.create_email_change_token_for_user(user_id, &txn).await.unwrap();
+        txn.commit().await.unwrap();
+        let txn = db.begin().await.unwrap();
+        let replaced = service.find_email_change_token_by_raw(&first, &txn).await.is_err();
+        txn.commit().await.unwrap();
+
+        test_db::delete_user(&db, user_id).await;
+        assert!(kept);
+        assert!(replaced);
+    }
+}
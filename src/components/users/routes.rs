@@ -1,27 +1,182 @@
 use super::services::UsersService;
-use crate::entity::users::UserSearchBody;
+use crate::components::auth::functions::AuthUser;
+use crate::components::auth::AuthService;
+use crate::components::config::ConfigService;
+use crate::components::sessions::client_info;
+use crate::entity::users::{
+    AdminCreateUserRequestBody, ChangeEmailRequestBody, ChangePasswordRequestBody, UpdateProfileRequestBody, UpdateUserStatusRequestBody,
+    UserSearchBody,
+};
 use crate::http_response::error_handler::{CustomError, ValidatedJson};
 use crate::http_response::prepared_response::check_response_ok_or_return_error;
-use actix_web::{get, post, web, HttpResponse};
+use actix_web::{get, patch, post, web, HttpRequest, HttpResponse};
+use uuid::Uuid;
 
 #[post("/users")]
-pub async fn users(_service: web::Data<UsersService>) -> Result<HttpResponse, CustomError> {
+pub async fn users(
+    auth: AuthUser,
+    _service: web::Data<UsersService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&["user.write"])?;
     let _service_instance = _service.get_ref();
 
     Ok(HttpResponse::Ok().body(()))
 }
 #[get("/users")]
 pub async fn get_users(
+    auth: AuthUser,
     payload: ValidatedJson<UserSearchBody>,
     _service: web::Data<UsersService>,
 ) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&["user.read"])?;
     let service_instance = _service.get_ref();
 
     let fetched_users = service_instance.get_all(&payload.0).await;
     check_response_ok_or_return_error(fetched_users)
 }
 
+/// Lifts a failed-login lockout, needs `user.write`
+#[post("/users/{id}/unlock")]
+pub async fn unlock_user(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<UsersService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&["user.write"])?;
+    let caller_id = auth.user_id()?;
+    check_response_ok_or_return_error(service.unlock(path.into_inner(), caller_id).await)
+}
+
+#[get("/admin/users/{id}")]
+pub async fn admin_get_user(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&["user.write"])?;
+    check_response_ok_or_return_error(service.admin_user(path.into_inner()).await)
+}
+
+/// Creates an active, already verified account
+#[post("/admin/users")]
+pub async fn admin_create_user(
+    req: HttpRequest,
+    auth: AuthUser,
+    payload: ValidatedJson<AdminCreateUserRequestBody>,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&["user.write"])?;
+    let admin_id = auth.user_id()?;
+    let conn_info = req.connection_info().clone();
+    let created = service
+        .admin_create_user(admin_id, payload.0, conn_info)
+        .await;
+    check_response_ok_or_return_error(created)
+}
+
+/// Suspends, deactivates or reactivates an account
+#[patch("/admin/users/{id}/status")]
+pub async fn admin_update_user_status(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    payload: ValidatedJson<UpdateUserStatusRequestBody>,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&["user.write"])?;
+    let admin_id = auth.user_id()?;
+    let updated = service
+        .update_user_status(admin_id, path.into_inner(), payload.0)
+        .await;
+    check_response_ok_or_return_error(updated)
+}
+
+#[post("/admin/users/{id}/password-reset")]
+pub async fn admin_force_password_reset(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<AuthService>,
+    service_config: web::Data<ConfigService>,
+) -> Result<HttpResponse, CustomError> {
+    auth.require_permissions(&["user.write"])?;
+    let admin_id = auth.user_id()?;
+    let forced = service
+        .force_password_reset(admin_id, path.into_inner(), &service_config)
+        .await;
+    check_response_ok_or_return_error(forced)
+}
+
+#[get("/users/me")]
+pub async fn get_me(
+    auth: AuthUser,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    check_response_ok_or_return_error(service.profile(user_id).await)
+}
+
+#[patch("/users/me")]
+pub async fn update_me(
+    auth: AuthUser,
+    payload: ValidatedJson<UpdateProfileRequestBody>,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    check_response_ok_or_return_error(service.update_profile(user_id, payload.0).await)
+}
+
+#[post("/users/me/password")]
+pub async fn change_password(
+    req: HttpRequest,
+    auth: AuthUser,
+    payload: ValidatedJson<ChangePasswordRequestBody>,
+    service: web::Data<AuthService>,
+) -> Result<HttpResponse, CustomError> {
+    let refresh_token = req.cookie("refresh_token").map(|c| c.value().to_string());
+    let changed = service
+        .change_password(auth.details, refresh_token, payload.0, client_info(&req).ip_address)
+        .await;
+    check_response_ok_or_return_error(changed)
+}
+
+#[post("/users/me/email")]
+pub async fn change_email(
+    auth: AuthUser,
+    payload: ValidatedJson<ChangeEmailRequestBody>,
+    service: web::Data<AuthService>,
+    service_config: web::Data<ConfigService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    let requested = service
+        .request_email_change(user_id, payload.0, &service_config)
+        .await;
+    check_response_ok_or_return_error(requested)
+}
+
+/// Link mailed to the new address by `POST /users/me/email`
+#[get("/users/email/confirm/{token}")]
+pub async fn confirm_email_change(
+    req: HttpRequest,
+    path: web::Path<String>,
+    service: web::Data<AuthService>,
+    service_config: web::Data<ConfigService>,
+) -> Result<HttpResponse, CustomError> {
+    let confirmed = service
+        .confirm_email_change(path.into_inner(), client_info(&req).ip_address, &service_config)
+        .await;
+    check_response_ok_or_return_error(confirmed)
+}
+
 pub fn init_routes(config: &mut web::ServiceConfig) {
     config.service(users);
     config.service(get_users);
+    config.service(unlock_user);
+    config.service(admin_get_user);
+    config.service(admin_create_user);
+    config.service(admin_update_user_status);
+    config.service(admin_force_password_reset);
+    config.service(get_me);
+    config.service(update_me);
+    config.service(change_password);
+    config.service(change_email);
+    config.service(confirm_email_change);
 }
@@ -1,12 +1,17 @@
+use crate::components::config::ConfigService;
 use crate::components::users::enums::SearchValue;
+use crate::config_service;
 use crate::entity::users::{
     ActiveModel, AuthRequestBody, Column, Entity, Model, UserSearchBody, UserSearchResponseBody,
 };
 use crate::entity::UserStatus::{Active, PendingVerification};
 use crate::http_response::error_handler::CustomError;
 use crate::http_response::HttpCodeW;
-use crate::utils::helpers::{hash_password, now_date_time_utc, verify_password};
+use crate::utils::helpers::{
+    hash_password, now_date_time_utc, password_needs_rehash, verify_password,
+};
 use actix_web::dev::ConnectionInfo;
+use chrono::Duration;
 use sea_orm::prelude::DateTimeWithTimeZone;
 use sea_orm::sea_query::Expr;
 use sea_orm::{
@@ -159,7 +164,14 @@ impl UsersService {
             true => match verify_password(payload.password.as_str(), &user_model.password_hash) {
                 Ok(value) => match value {
                     true => {
-                        let active_user: ActiveModel = user_model.clone().into();
+                        let mut active_user: ActiveModel = user_model.clone().into();
+                        // Saved with the login update, so hashes move to the current
+                        // Argon2 settings without a reset
+                        if password_needs_rehash(&user_model.password_hash) {
+                            if let Ok(hashed) = hash_password(payload.password.as_str()) {
+                                active_user.password_hash = Set(hashed);
+                            }
+                        }
 
                         Ok(active_user)
                     }
@@ -204,10 +216,147 @@ impl UsersService {
                 "User needs email verification".to_string(),
             )));
         }
+        if let Err(e) = Self::ensure_not_locked(&user_model) {
+            return Ok(Err(e));
+        }
+
         let check_pass = self.check_credentials(payload, &user_model).await;
+        // Only a wrong password counts, disabled accounts fail before the hash is checked
+        if check_pass.is_err() && user_model.can_login() {
+            match self.record_failed_login(&user_model, ip_address).await {
+                Ok(Some(locked_until)) => return Ok(Err(Self::locked_error(locked_until))),
+                Ok(None) => {}
+                Err(e) => return Err(Err(e)),
+            }
+        }
         Ok(check_pass)
     }
 
+    pub fn locked_error(locked_until: DateTimeWithTimeZone) -> CustomError {
+        CustomError::new(
+            HttpCodeW::Locked,
+            format!(
+                "Account locked after too many failed login attempts, try again after {}",
+                locked_until.to_rfc3339()
+            ),
+        )
+    }
+
+    /// Rejects sign-in attempts while a lockout is running, whatever the credential.
+    pub fn ensure_not_locked(user_model: &Model) -> Result<(), CustomError> {
+        match user_model.locked_until {
+            Some(until) if user_model.is_locked() => Err(Self::locked_error(until)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Lock duration doubles with every consecutive lockout, up to the configured maximum.
+    fn lockout_duration(lockout_count: i32, config: &ConfigService) -> Duration {
+        let factor = 1i64 << lockout_count.clamp(0, 20);
+        Duration::seconds(
+            config
+                .login_lockout_seconds
+                .saturating_mul(factor)
+                .min(config.login_lockout_max_seconds),
+        )
+    }
+
+    fn append_login_history(entry: JsonValue) -> sea_orm::sea_query::SimpleExpr {
+        Expr::cust_with_values(
+            "login_history || $1::jsonb",
+            vec![sea_orm::Value::from(JsonValue::Array(vec![entry]))],
+        )
+    }
+
+    /// Counts a wrong password and locks the account once the threshold is reached.
+    /// The counter is incremented in SQL so concurrent attempts are not lost.
+    /// Returns the end of the lock when this attempt triggered it.
+    pub async fn record_failed_login(
+        &self,
+        user_model: &Model,
+        ip_address: &String,
+    ) -> Result<Option<DateTimeWithTimeZone>, CustomError> {
+        let config = config_service();
+        let attempt = json!({
+            "timestamp": now_date_time_utc(),
+            "notes": "Failed login attempt",
+            "ip_address": ip_address,
+        });
+        let updated = Entity::update_many()
+            .col_expr(
+                Column::FailedLoginAttempts,
+                Expr::col(Column::FailedLoginAttempts).add(1),
+            )
+            .col_expr(Column::LoginHistory, Self::append_login_history(attempt))
+            .filter(Column::Id.eq(user_model.id))
+            .exec_with_returning(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        let Some(updated) = updated.into_iter().next() else {
+            return Ok(None);
+        };
+        if updated.failed_login_attempts < config.login_max_failed_attempts {
+            return Ok(None);
+        }
+
+        let locked_until = DateTimeWithTimeZone::from(
+            now_date_time_utc() + Self::lockout_duration(updated.lockout_count, &config),
+        );
+        let lock = json!({
+            "timestamp": now_date_time_utc(),
+            "notes": "Account locked after too many failed login attempts",
+            "ip_address": ip_address,
+            "locked_until": locked_until,
+        });
+        // Guarded on the counter so concurrent failures lock the account only once
+        let locked = Entity::update_many()
+            .col_expr(Column::FailedLoginAttempts, Expr::value(0))
+            .col_expr(Column::LockoutCount, Expr::col(Column::LockoutCount).add(1))
+            .col_expr(Column::LockedUntil, Expr::value(locked_until))
+            .col_expr(Column::LoginHistory, Self::append_login_history(lock))
+            .filter(Column::Id.eq(user_model.id))
+            .filter(Column::FailedLoginAttempts.gte(config.login_max_failed_attempts))
+            .exec(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        Ok((locked.rows_affected > 0).then_some(locked_until))
+    }
+
+    /// Clears the counters after a successful sign-in.
+    pub fn reset_failed_logins(active_user: &mut ActiveModel) {
+        active_user.failed_login_attempts = Set(0);
+        active_user.lockout_count = Set(0);
+        active_user.locked_until = Set(None);
+    }
+
+    /// Lifts a lockout on behalf of an administrator.
+    pub async fn unlock(&self, user_id: Uuid, unlocked_by: Uuid) -> Result<String, CustomError> {
+        let entry = json!({
+            "timestamp": now_date_time_utc(),
+            "notes": "Account unlocked by administrator",
+            "unlocked_by": unlocked_by,
+        });
+        let unlocked = Entity::update_many()
+            .col_expr(Column::FailedLoginAttempts, Expr::value(0))
+            .col_expr(Column::LockoutCount, Expr::value(0))
+            .col_expr(Column::LockedUntil, Expr::value(Option::<DateTimeWithTimeZone>::None))
+            .col_expr(Column::LoginHistory, Self::append_login_history(entry))
+            .filter(Column::Id.eq(user_id))
+            .exec(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        match unlocked.rows_affected {
+            0 => Err(CustomError::new(
+                HttpCodeW::NotFound,
+                "User not found".to_string(),
+            )),
+            _ => Ok("Account unlocked".to_string()),
+        }
+    }
+
     pub fn add_details_login(active_user: &mut ActiveModel, new_login: JsonValue) {
         let mut login_history: Vec<JsonValue> = match &active_user.login_history {
             Unchanged(JsonValue::Array(array)) => array.clone(),
@@ -236,7 +385,12 @@ impl UsersService {
                 model.email_verified = Set(true);
                 model.status = Set(Active)
             }
-            (&_, _) => todo!(),
+            (field, _) => {
+                return Err(CustomError::new(
+                    HttpCodeW::BadRequest,
+                    format!("Field {field} cannot be updated"),
+                ))
+            }
         };
 
         model
@@ -273,6 +427,10 @@ impl UsersService {
             created_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
             updated_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
             login_history: Set(JsonValue::Array(vec![new_login])),
+            failed_login_attempts: Set(0),
+            lockout_count: Set(0),
+            locked_until: Set(None),
+            pending_email: Set(None),
         }
     }
 }
@@ -0,0 +1,79 @@
+use super::services::WebauthnService;
+use crate::components::auth::functions::{refresh_cookie, AuthUser};
+use crate::components::sessions::client_info;
+use crate::entity::webauthn_credentials::{
+    WebauthnLoginFinishRequestBody, WebauthnLoginStartRequestBody,
+    WebauthnRegisterFinishRequestBody,
+};
+use crate::http_response::error_handler::{CustomError, ValidatedJson};
+use crate::http_response::http_response_builder;
+use crate::http_response::prepared_response::check_response_ok_or_return_error;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use uuid::Uuid;
+
+#[post("/auth/webauthn/register/start")]
+pub async fn register_start(
+    auth: AuthUser,
+    service: web::Data<WebauthnService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    check_response_ok_or_return_error(service.start_registration(user_id).await)
+}
+
+#[post("/auth/webauthn/register/finish")]
+pub async fn register_finish(
+    auth: AuthUser,
+    payload: ValidatedJson<WebauthnRegisterFinishRequestBody>,
+    service: web::Data<WebauthnService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    check_response_ok_or_return_error(service.finish_registration(user_id, payload.0).await)
+}
+
+#[post("/auth/webauthn/login/start")]
+pub async fn login_start(
+    payload: ValidatedJson<WebauthnLoginStartRequestBody>,
+    service: web::Data<WebauthnService>,
+) -> Result<HttpResponse, CustomError> {
+    check_response_ok_or_return_error(service.start_login(payload.0).await)
+}
+
+#[post("/auth/webauthn/login/finish")]
+pub async fn login_finish(
+    req: HttpRequest,
+    payload: ValidatedJson<WebauthnLoginFinishRequestBody>,
+    service: web::Data<WebauthnService>,
+) -> Result<HttpResponse, CustomError> {
+    let payload_auth = service.finish_login(payload.0, client_info(&req)).await?;
+    Ok(HttpResponse::Ok()
+        .cookie(refresh_cookie(payload_auth.refresh_token))
+        .json(http_response_builder::ok(payload_auth.body)))
+}
+
+#[get("/auth/webauthn/credentials")]
+pub async fn list_credentials(
+    auth: AuthUser,
+    service: web::Data<WebauthnService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    check_response_ok_or_return_error(service.list_passkeys(user_id).await)
+}
+
+#[delete("/auth/webauthn/credentials/{id}")]
+pub async fn delete_credential(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    service: web::Data<WebauthnService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = auth.user_id()?;
+    check_response_ok_or_return_error(service.delete_passkey(user_id, path.into_inner()).await)
+}
+
+pub fn init_routes(config: &mut web::ServiceConfig) {
+    config.service(register_start);
+    config.service(register_finish);
+    config.service(login_start);
+    config.service(login_finish);
+    config.service(list_credentials);
+    config.service(delete_credential);
+}
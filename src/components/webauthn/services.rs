@@ -0,0 +1,461 @@
+use crate::components::audit::{AuditService, NewAuditEvent};
+use crate::components::auth::functions::issue_tokens_for_user;
+use crate::components::config::ConfigService;
+use crate::components::tokens::TokensService;
+use crate::components::users::enums::SearchValue;
+use crate::components::users::UsersService;
+use crate::entity::enums::AuditEventType;
+use crate::entity::sessions::ClientInfo;
+use crate::entity::users::AuthResponseBody;
+use crate::entity::webauthn_ceremonies;
+use crate::entity::webauthn_credentials::{
+    ActiveModel, Column, Entity, Model, PasskeyResponse, WebauthnChallengeResponse,
+    WebauthnLoginFinishRequestBody, WebauthnLoginStartRequestBody,
+    WebauthnRegisterFinishRequestBody,
+};
+use crate::entity::users;
+use crate::http_response::error_handler::CustomError;
+use crate::http_response::HttpCodeW;
+use crate::utils::helpers::now_date_time_utc;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Duration;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use url::Url;
+use uuid::Uuid;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, Passkey, PasskeyAuthentication, PasskeyRegistration,
+    RequestChallengeResponse,
+};
+use webauthn_rs::{Webauthn, WebauthnBuilder};
+
+/// Time the browser and the user have to complete a ceremony
+const CEREMONY_TTL_MINUTES: i64 = 5;
+const REGISTRATION: &str = "registration";
+const AUTHENTICATION: &str = "authentication";
+
+#[derive(Clone)]
+pub struct WebauthnService {
+    conn: DatabaseConnection,
+    users_service: UsersService,
+    tokens_service: TokensService,
+    webauthn: Arc<Webauthn>,
+}
+
+impl WebauthnService {
+    /// Panics on an invalid relying party configuration, like the key ring does at startup.
+    pub fn new(
+        conn: &DatabaseConnection,
+        users_service: &UsersService,
+        tokens_service: &TokensService,
+        config: &ConfigService,
+    ) -> Self {
+        let origins: Vec<Url> = config
+            .webauthn_rp_origins
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| Url::parse(origin).expect("WEBAUTHN_RP_ORIGINS must contain valid URLs"))
+            .collect();
+        let primary = origins
+            .first()
+            .expect("WEBAUTHN_RP_ORIGINS must contain at least one origin");
+        let rp_id = config
+            .webauthn_rp_id
+            .clone()
+            .or_else(|| primary.host_str().map(str::to_string))
+            .expect("WEBAUTHN_RP_ID must be set when the origin has no host");
+
+        let mut builder = WebauthnBuilder::new(&rp_id, primary)
+            .expect("Invalid WebAuthn relying party configuration")
+            .rp_name(&config.webauthn_rp_name);
+        for origin in origins.iter().skip(1) {
+            builder = builder.append_allowed_origin(origin);
+        }
+        let webauthn = builder
+            .build()
+            .expect("Invalid WebAuthn relying party configuration");
+
+        Self {
+            conn: conn.clone(),
+            users_service: users_service.clone(),
+            tokens_service: tokens_service.clone(),
+            webauthn: Arc::new(webauthn),
+        }
+    }
+
+    async fn find_passkeys(&self, user_id: Uuid) -> Result<Vec<Model>, CustomError> {
+        Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .order_by_asc(Column::CreatedAt)
+            .all(&self.conn)
+            .await
+            .map_err(CustomError::from)
+    }
+
+    async fn save_ceremony<T: Serialize>(
+        &self,
+        user_id: Uuid,
+        kind: &str,
+        state: &T,
+    ) -> Result<Uuid, CustomError> {
+        let state = serde_json::to_value(state).map_err(|e| {
+            CustomError::new(
+                HttpCodeW::InternalServerError,
+                format!("Failed to serialize WebAuthn state: {e}"),
+            )
+        })?;
+        let expires_at = now_date_time_utc() + Duration::minutes(CEREMONY_TTL_MINUTES);
+
+        let ceremony = webauthn_ceremonies::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            kind: Set(kind.to_string()),
+            state: Set(state),
+            expires_at: Set(DateTimeWithTimeZone::from(expires_at)),
+            created_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+        }
+        .insert(&self.conn)
+        .await
+        .map_err(CustomError::from)?;
+        Ok(ceremony.id)
+    }
+
+    /// Ceremonies are single use: the row is deleted whether or not the response verifies.
+    async fn take_ceremony<T: DeserializeOwned>(
+        &self,
+        ceremony_id: Uuid,
+        kind: &str,
+    ) -> Result<(Uuid, T), CustomError> {
+        let invalid = || {
+            CustomError::new(
+                HttpCodeW::BadRequest,
+                "Unknown or expired WebAuthn ceremony".to_string(),
+            )
+        };
+
+        // Deleted and read in one statement, so only one request can take the row
+        let ceremony = webauthn_ceremonies::Entity::delete_many()
+            .filter(webauthn_ceremonies::Column::Id.eq(ceremony_id))
+            .exec_with_returning(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+            .into_iter()
+            .next()
+            .ok_or_else(invalid)?;
+
+        if ceremony.kind != kind || ceremony.is_expired() {
+            return Err(invalid());
+        }
+        let state = serde_json::from_value(ceremony.state).map_err(|_| invalid())?;
+        Ok((ceremony.user_id, state))
+    }
+
+    pub async fn start_registration(
+        &self,
+        user_id: Uuid,
+    ) -> Result<WebauthnChallengeResponse<CreationChallengeResponse>, CustomError> {
+        let user = self
+            .users_service
+            .find("id", SearchValue::Uuid(user_id))
+            .await?;
+        // Authenticators refuse to register a second credential for the same account
+        let exclude = self
+            .find_passkeys(user_id)
+            .await?
+            .into_iter()
+            .filter_map(|credential| URL_SAFE_NO_PAD.decode(credential.credential_id).ok())
+            .map(Into::into)
+            .collect();
+
+        let (options, state) = self
+            .webauthn
+            .start_passkey_registration(user.id, &user.email, &user.username, Some(exclude))
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Failed to start passkey registration: {e}"),
+                )
+            })?;
+
+        let ceremony_id = self.save_ceremony(user_id, REGISTRATION, &state).await?;
+        Ok(WebauthnChallengeResponse {
+            ceremony_id,
+            options,
+        })
+    }
+
+    pub async fn finish_registration(
+        &self,
+        user_id: Uuid,
+        payload: WebauthnRegisterFinishRequestBody,
+    ) -> Result<PasskeyResponse, CustomError> {
+        let (ceremony_user_id, state): (Uuid, PasskeyRegistration) = self
+            .take_ceremony(payload.ceremony_id, REGISTRATION)
+            .await?;
+        if ceremony_user_id != user_id {
+            return Err(CustomError::new(
+                HttpCodeW::BadRequest,
+                "Unknown or expired WebAuthn ceremony".to_string(),
+            ));
+        }
+
+        let passkey = self
+            .webauthn
+            .finish_passkey_registration(&payload.credential, &state)
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::BadRequest,
+                    format!("Passkey registration failed: {e}"),
+                )
+            })?;
+
+        let model = ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            credential_id: Set(URL_SAFE_NO_PAD.encode(passkey.cred_id())),
+            public_key: Set(json!(passkey.get_public_key())),
+            sign_count: Set(0),
+            transports: Set(json!(payload
+                .credential
+                .response
+                .transports
+                .unwrap_or_default())),
+            passkey: Set(json!(passkey)),
+            name: Set(payload.name),
+            created_at: Set(DateTimeWithTimeZone::from(now_date_time_utc())),
+            last_used_at: Set(None),
+        }
+        .insert(&self.conn)
+        .await
+        .map_err(|e| {
+            CustomError::new(
+                HttpCodeW::Conflict,
+                format!("Failed to store passkey: {e}"),
+            )
+        })?;
+
+        Ok(PasskeyResponse::from(model))
+    }
+
+    pub async fn start_login(
+        &self,
+        payload: WebauthnLoginStartRequestBody,
+    ) -> Result<WebauthnChallengeResponse<RequestChallengeResponse>, CustomError> {
+        let unavailable = || {
+            CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Passkey sign-in is not available for this account".to_string(),
+            )
+        };
+
+        let user = self
+            .users_service
+            .find("email", SearchValue::String(payload.email))
+            .await
+            .map_err(|_| unavailable())?;
+        let passkeys: Vec<Passkey> = self
+            .find_passkeys(user.id)
+            .await?
+            .into_iter()
+            .filter_map(|credential| serde_json::from_value(credential.passkey).ok())
+            .collect();
+        if passkeys.is_empty() || !user.can_login() {
+            return Err(unavailable());
+        }
+        UsersService::ensure_not_locked(&user)?;
+
+        let (options, state) = self
+            .webauthn
+            .start_passkey_authentication(&passkeys)
+            .map_err(|e| {
+                CustomError::new(
+                    HttpCodeW::InternalServerError,
+                    format!("Failed to start passkey sign-in: {e}"),
+                )
+            })?;
+
+        let ceremony_id = self.save_ceremony(user.id, AUTHENTICATION, &state).await?;
+        Ok(WebauthnChallengeResponse {
+            ceremony_id,
+            options,
+        })
+    }
+
+    /// Verifies the assertion and signs the user in through the same path as a password login.
+    pub async fn finish_login(
+        &self,
+        payload: WebauthnLoginFinishRequestBody,
+        client: ClientInfo,
+    ) -> Result<AuthResponseBody, CustomError> {
+        let failed = || {
+            CustomError::new(
+                HttpCodeW::Unauthorized,
+                "Passkey sign-in failed".to_string(),
+            )
+        };
+
+        let (user_id, state): (Uuid, PasskeyAuthentication) = self
+            .take_ceremony(payload.ceremony_id, AUTHENTICATION)
+            .await?;
+        let result = match self
+            .webauthn
+            .finish_passkey_authentication(&payload.credential, &state)
+        {
+            Ok(result) => result,
+            Err(_) => {
+                AuditService::record(
+                    NewAuditEvent::failure(AuditEventType::Login)
+                        .with_target(user_id)
+                        .with_client(&client)
+                        .with_metadata(json!({ "method": "passkey", "reason": "Assertion rejected" })),
+                    &self.conn,
+                )
+                .await;
+                return Err(failed());
+            }
+        };
+
+        let credential = Entity::find()
+            .filter(Column::CredentialId.eq(URL_SAFE_NO_PAD.encode(result.cred_id())))
+            .filter(Column::UserId.eq(user_id))
+            .one(&self.conn)
+            .await
+            .map_err(CustomError::from)?
+            .ok_or_else(failed)?;
+        let mut passkey: Passkey =
+            serde_json::from_value(credential.passkey.clone()).map_err(|_| failed())?;
+        passkey.update_credential(&result);
+
+        let mut active: ActiveModel = credential.into();
+        active.passkey = Set(json!(passkey));
+        active.sign_count = Set(i64::from(result.counter()));
+        active.last_used_at = Set(Some(DateTimeWithTimeZone::from(now_date_time_utc())));
+        active.update(&self.conn).await.map_err(CustomError::from)?;
+
+        let user = self
+            .users_service
+            .find("id", SearchValue::Uuid(user_id))
+            .await?;
+        if !user.can_login() {
+            return Err(failed());
+        }
+        UsersService::ensure_not_locked(&user)?;
+
+        let mut active_user: users::ActiveModel = user.into();
+        UsersService::add_details_login(
+            &mut active_user,
+            json!({
+                "timestamp": now_date_time_utc(),
+                "notes": "User Logged (passkey)",
+                "ip_address": client.ip_address,
+            }),
+        );
+        UsersService::reset_failed_logins(&mut active_user);
+        let user = active_user.update(&self.conn).await.map_err(|_| {
+            CustomError::new(
+                HttpCodeW::InternalServerError,
+                "Failed to update user".to_string(),
+            )
+        })?;
+
+        issue_tokens_for_user(&self.conn, &self.tokens_service, &user, &client).await
+    }
+
+    pub async fn list_passkeys(&self, user_id: Uuid) -> Result<Vec<PasskeyResponse>, CustomError> {
+        Ok(self
+            .find_passkeys(user_id)
+            .await?
+            .into_iter()
+            .map(PasskeyResponse::from)
+            .collect())
+    }
+
+    pub async fn delete_passkey(&self, user_id: Uuid, id: Uuid) -> Result<String, CustomError> {
+        let deleted = Entity::delete_many()
+            .filter(Column::Id.eq(id))
+            .filter(Column::UserId.eq(user_id))
+            .exec(&self.conn)
+            .await
+            .map_err(CustomError::from)?;
+
+        match deleted.rows_affected {
+            0 => Err(CustomError::new(
+                HttpCodeW::NotFound,
+                "Passkey not found".to_string(),
+            )),
+            _ => Ok("Passkey removed".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_db;
+    use webauthn_authenticator_rs::softpasskey::SoftPasskey;
+    use webauthn_authenticator_rs::WebauthnAuthenticator;
+
+    #[actix_rt::test]
+    async fn passkey_registration_and_login_ceremonies() {
+        let Some(db) = test_db::connect().await else {
+            return;
+        };
+        test_db::configure_env();
+        let config = ConfigService::new();
+        let users_service = UsersService::new(&db);
+        let tokens_service = TokensService::new(&db, &users_service);
+        let service = WebauthnService::new(&db, &users_service, &tokens_service, &config);
+        let origin = Url::parse(config.webauthn_rp_origins.split(',').next().unwrap()).unwrap();
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        let user_id = test_db::insert_user(&db, "USER").await;
+        let client = ClientInfo {
+            ip_address: "127.0.0.1".to_string(),
+            user_agent: None,
+        };
+
+        let registration = service.start_registration(user_id).await.unwrap();
+        let credential = authenticator
+            .do_registration(origin.clone(), registration.options)
+            .unwrap();
+        let register = |credential| WebauthnRegisterFinishRequestBody {
+            ceremony_id: registration.ceremony_id,
+            name: Some("Soft passkey".to_string()),
+            credential,
+        };
+        let registered = service.finish_registration(user_id, register(credential.clone())).await;
+        let registration_replay = service.finish_registration(user_id, register(credential)).await;
+
+        let user = users_service.find("id", SearchValue::Uuid(user_id)).await.unwrap();
+        let login = service
+            .start_login(WebauthnLoginStartRequestBody { email: user.email })
+            .await
+            .unwrap();
+        let assertion = authenticator.do_authentication(origin, login.options).unwrap();
+        let sign_in = |credential| WebauthnLoginFinishRequestBody {
+            ceremony_id: login.ceremony_id,
+            credential,
+        };
+        let signed_in = service.finish_login(sign_in(assertion.clone()), client.clone()).await;
+        let login_replay = service.finish_login(sign_in(assertion), client).await;
+
+        test_db::delete_user(&db, user_id).await;
+        assert!(registered.is_ok(), "{:?}", registered.err());
+        assert_eq!(
+            registration_replay.unwrap_err().error_status_code,
+            HttpCodeW::BadRequest
+        );
+        let tokens = signed_in.unwrap();
+        assert!(!tokens.body.access_token.is_empty());
+        assert!(!tokens.refresh_token.is_empty());
+        assert_eq!(login_replay.unwrap_err().error_status_code, HttpCodeW::BadRequest);
+    }
+}
@@ -0,0 +1,59 @@
+use super::enums::{AuditEventType, AuditOutcome};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Append-only, the database rejects updates and deletes
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "audit_events", schema_name = "auth")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+
+    pub occurred_at: DateTimeWithTimeZone,
+
+    pub event_type: AuditEventType,
+
+    pub outcome: AuditOutcome,
+
+    /// Who did it, NULL for anonymous requests and service clients
+    pub actor_id: Option<Uuid>,
+
+    /// Whose account it concerns
+    pub target_user_id: Option<Uuid>,
+
+    pub ip_address: Option<String>,
+
+    pub user_agent: Option<String>,
+
+    #[sea_orm(column_type = "JsonBinary")]
+    pub metadata: Json,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+
+/// Filters of the admin query API, all optional
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEventsQuery {
+    /// Events where the user is the actor or the target
+    pub user_id: Option<Uuid>,
+    pub event_type: Option<AuditEventType>,
+    pub outcome: Option<AuditOutcome>,
+    /// Inclusive lower bound of `occurred_at`
+    pub from: Option<DateTimeWithTimeZone>,
+    /// Exclusive upper bound of `occurred_at`
+    pub until: Option<DateTimeWithTimeZone>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<u64>,
+}
+
+/// Newest first; `next_cursor` is absent on the last page
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEventPage {
+    pub events: Vec<Model>,
+    pub next_cursor: Option<String>,
+}
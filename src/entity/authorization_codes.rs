@@ -0,0 +1,74 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "authorization_codes", schema_name = "auth")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+
+    #[sea_orm(unique)]
+    pub code_hash: String,
+
+    pub client_id: Uuid,
+
+    pub user_id: Uuid,
+
+    pub redirect_uri: String,
+
+    pub code_challenge: String,
+
+    pub code_challenge_method: String,
+
+    pub scope: Option<String>,
+
+    pub nonce: Option<String>,
+
+    pub expires_at: DateTimeWithTimeZone,
+
+    pub is_used: bool,
+
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::oauth_clients::Entity",
+        from = "Column::ClientId",
+        to = "super::oauth_clients::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    OauthClients,
+
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Users,
+}
+
+impl Related<super::oauth_clients::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OauthClients.def()
+    }
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// Check if the code is expired
+    pub fn is_expired(&self) -> bool {
+        chrono::Utc::now() > self.expires_at.with_timezone(&chrono::Utc)
+    }
+}
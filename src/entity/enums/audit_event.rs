@@ -0,0 +1,52 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Stored as text rather than a database enum, so new event types need no migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(64))")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditEventType {
+    #[sea_orm(string_value = "REGISTER")]
+    Register,
+
+    #[sea_orm(string_value = "LOGIN")]
+    Login,
+
+    #[sea_orm(string_value = "EMAIL_VERIFY")]
+    EmailVerify,
+
+    #[sea_orm(string_value = "TOKEN_REFRESH")]
+    TokenRefresh,
+
+    #[sea_orm(string_value = "TOKEN_REVOKE")]
+    TokenRevoke,
+
+    #[sea_orm(string_value = "ROLE_ASSIGN")]
+    RoleAssign,
+
+    #[sea_orm(string_value = "ROLE_REMOVE")]
+    RoleRemove,
+
+    #[sea_orm(string_value = "PERMISSION_OVERRIDE_SET")]
+    PermissionOverrideSet,
+
+    #[sea_orm(string_value = "PERMISSION_OVERRIDE_REMOVE")]
+    PermissionOverrideRemove,
+
+    #[sea_orm(string_value = "PASSWORD_CHANGE")]
+    PasswordChange,
+
+    #[sea_orm(string_value = "PASSWORD_RESET")]
+    PasswordReset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(16))")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditOutcome {
+    #[sea_orm(string_value = "SUCCESS")]
+    Success,
+
+    #[sea_orm(string_value = "FAILURE")]
+    Failure,
+}
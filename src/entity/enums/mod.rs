@@ -3,6 +3,8 @@ pub mod user_status;
 
 pub use user_role::*;
 pub mod token_type;
+pub mod audit_event;
 
 pub use user_status::*;
 pub use token_type::*;
+pub use audit_event::*;
@@ -15,6 +15,9 @@ pub enum TokenType {
 
     #[sea_orm(string_value = "EMAIL_VERIFICATION")]
     EmailVerification,
+
+    #[sea_orm(string_value = "EMAIL_CHANGE")]
+    EmailChange,
 }
 
 #[allow(dead_code)]
@@ -25,6 +28,7 @@ impl TokenType {
             TokenType::Refresh => "REFRESH",
             TokenType::ResetPassword => "RESET_PASSWORD",
             TokenType::EmailVerification => "EMAIL_VERIFICATION",
+            TokenType::EmailChange => "EMAIL_CHANGE",
         }
     }
 
@@ -37,7 +41,10 @@ impl TokenType {
     }
 
     pub fn is_verification_token(&self) -> bool {
-        matches!(self, TokenType::EmailVerification | TokenType::ResetPassword)
+        matches!(
+            self,
+            TokenType::EmailVerification | TokenType::ResetPassword | TokenType::EmailChange
+        )
     }
 
     /// Get the default expiration time in minutes for each token type
@@ -47,6 +54,7 @@ impl TokenType {
             TokenType::Refresh => 10080,       // 7 days
             TokenType::ResetPassword => 60,    // 1 hour
             TokenType::EmailVerification => 1440, // 24 hours
+            TokenType::EmailChange => 1440,    // 24 hours
         }
     }
 }
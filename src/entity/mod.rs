@@ -8,6 +8,15 @@ pub mod permissions;
 pub mod role_permissions;
 pub mod user_roles;
 pub mod user_permission_overrides;
+pub mod oauth_clients;
+pub mod authorization_codes;
+pub mod user_mfa;
+pub mod webauthn_credentials;
+pub mod webauthn_ceremonies;
+pub mod organizations;
+pub mod organization_members;
+pub mod organization_member_roles;
+pub mod audit_events;
 
 #[allow(unused_imports)]
 pub use enums::*;
@@ -0,0 +1,125 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::prelude::async_trait::async_trait;
+use sea_orm::Set;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::helpers::now_date_time_utc;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "oauth_clients", schema_name = "auth")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+
+    #[sea_orm(unique)]
+    pub client_id: String,
+
+    pub name: String,
+
+    #[serde(skip_serializing)]
+    pub client_secret_hash: Option<String>,
+
+    #[sea_orm(column_type = "JsonBinary")]
+    pub redirect_uris: Json,
+
+    pub is_active: bool,
+
+    #[sea_orm(column_type = "JsonBinary")]
+    pub grant_types: Json,
+
+    #[sea_orm(column_type = "JsonBinary")]
+    pub allowed_permissions: Json,
+
+    pub created_at: DateTimeWithTimeZone,
+
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::authorization_codes::Entity")]
+    AuthorizationCodes,
+}
+
+impl Related<super::authorization_codes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuthorizationCodes.def()
+    }
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let mut active_model = self;
+        active_model.updated_at = Set(DateTimeWithTimeZone::from(now_date_time_utc()));
+        Ok(active_model)
+    }
+}
+
+impl Model {
+    /// Redirect URIs are compared by exact string match, as required by OAuth 2.1
+    pub fn allows_redirect_uri(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris
+            .as_array()
+            .is_some_and(|uris| uris.iter().any(|uri| uri.as_str() == Some(redirect_uri)))
+    }
+
+    pub fn allows_grant_type(&self, grant_type: &str) -> bool {
+        self.grant_types
+            .as_array()
+            .is_some_and(|grants| grants.iter().any(|grant| grant.as_str() == Some(grant_type)))
+    }
+
+    /// Permissions a client_credentials token of this client may carry
+    pub fn allowed_permissions(&self) -> Vec<String> {
+        self.allowed_permissions
+            .as_array()
+            .map(|perms| {
+                perms
+                    .iter()
+                    .filter_map(|perm| perm.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct AuthorizeQuery {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    /// OpenID Connect, returned in the ID token
+    pub nonce: Option<String>,
+}
+
+/// `application/x-www-form-urlencoded` body of POST /oauth/token (RFC 6749, section 4.1.3)
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub code_verifier: Option<String>,
+    pub scope: Option<String>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Only when the authorization request asked for `openid`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
@@ -0,0 +1,70 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "organization_members", schema_name = "auth")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub organization_id: Uuid,
+
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: Uuid,
+
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::organizations::Entity",
+        from = "Column::OrganizationId",
+        to = "super::organizations::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Organizations,
+
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Users,
+}
+
+impl Related<super::organizations::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Organizations.def()
+    }
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+
+/// A member of an organization and the codes of the roles held there
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MemberResponse {
+    pub user_id: Uuid,
+    pub email: String,
+    pub username: String,
+    pub roles: Vec<String>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+/// An organization the caller belongs to and the roles held there
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MembershipResponse {
+    #[serde(flatten)]
+    pub organization: super::organizations::Model,
+    pub roles: Vec<String>,
+    /// The organization the current access token was issued for
+    pub active: bool,
+}
@@ -0,0 +1,51 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "organizations", schema_name = "auth")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+
+    #[sea_orm(unique)]
+    pub slug: String,
+
+    pub name: String,
+
+    pub created_at: DateTimeWithTimeZone,
+
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::organization_members::Entity")]
+    OrganizationMembers,
+}
+
+impl Related<super::organization_members::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OrganizationMembers.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct CreateOrganizationRequestBody {
+    pub slug: String,
+    pub name: String,
+}
+
+/// The slug is fixed once created, clients refer to it
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateOrganizationRequestBody {
+    pub name: String,
+}
+
+/// `None` goes back to the global roles only
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct SwitchOrganizationRequestBody {
+    pub organization_id: Option<Uuid>,
+}
@@ -28,3 +28,15 @@ pub enum Relation {
 
 impl ActiveModelBehavior for ActiveModel {}
 
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct CreatePermissionRequestBody {
+    pub code: String,
+    pub description: Option<String>,
+}
+
+/// The code is fixed once created, routes check permissions by code
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct UpdatePermissionRequestBody {
+    pub description: Option<String>,
+}
@@ -21,3 +21,30 @@ pub use super::user_permission_overrides::{
     Entity as UserPermissionOverrides,
     Model as UserPermissionOverrideModel,
 };
+#[allow(unused_imports)]
+pub use super::oauth_clients::{Entity as OauthClients, Model as OauthClientModel};
+#[allow(unused_imports)]
+pub use super::authorization_codes::{
+    Entity as AuthorizationCodes,
+    Model as AuthorizationCodeModel,
+};
+#[allow(unused_imports)]
+pub use super::user_mfa::{Entity as UserMfa, Model as UserMfaModel};
+#[allow(unused_imports)]
+pub use super::webauthn_credentials::{Entity as WebauthnCredentials, Model as WebauthnCredentialModel};
+#[allow(unused_imports)]
+pub use super::webauthn_ceremonies::{Entity as WebauthnCeremonies, Model as WebauthnCeremonyModel};
+#[allow(unused_imports)]
+pub use super::organizations::{Entity as Organizations, Model as OrganizationModel};
+#[allow(unused_imports)]
+pub use super::organization_members::{
+    Entity as OrganizationMembers,
+    Model as OrganizationMemberModel,
+};
+#[allow(unused_imports)]
+pub use super::organization_member_roles::{
+    Entity as OrganizationMemberRoles,
+    Model as OrganizationMemberRoleModel,
+};
+#[allow(unused_imports)]
+pub use super::audit_events::{Entity as AuditEvents, Model as AuditEventModel};
\ No newline at end of file
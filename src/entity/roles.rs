@@ -30,3 +30,25 @@ pub enum Relation {
 
 impl ActiveModelBehavior for ActiveModel {}
 
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct CreateRoleRequestBody {
+    pub code: String,
+    pub description: Option<String>,
+    pub priority: Option<i32>,
+}
+
+/// The code is fixed once created, tokens and `users.role` refer to it
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateRoleRequestBody {
+    pub description: Option<String>,
+    pub priority: Option<i32>,
+}
+
+/// A role together with the codes of the permissions attached to it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoleResponse {
+    #[serde(flatten)]
+    pub role: Model,
+    pub permissions: Vec<String>,
+}
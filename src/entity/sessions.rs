@@ -9,6 +9,7 @@ pub struct Model {
 
     pub user_id: Uuid,
 
+    /// Refresh token family (see `tokens.family_id`) this session follows
     #[sea_orm(unique)]
     pub session_token: String,
 
@@ -20,6 +21,9 @@ pub struct Model {
 
     pub is_active: bool,
 
+    /// Organization the access tokens of this session are issued for
+    pub active_organization_id: Option<Uuid>,
+
     pub created_at: DateTimeWithTimeZone,
 
     pub updated_at: DateTimeWithTimeZone,
@@ -93,3 +97,42 @@ impl Model {
         (chrono::Utc::now() - self.created_at.with_timezone(&chrono::Utc)).num_minutes()
     }
 }
+
+/// Where a request came from, recorded on sessions and in the login history
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub ip_address: String,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionsQuery {
+    /// Another user's sessions, needs `session.read`
+    pub user_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+    pub last_seen_at: DateTimeWithTimeZone,
+    pub expires_at: DateTimeWithTimeZone,
+    /// The session of the refresh cookie sent with the request
+    pub current: bool,
+}
+
+impl SessionResponse {
+    pub fn new(model: Model, current_family: Option<&str>) -> Self {
+        SessionResponse {
+            id: model.id,
+            user_agent: model.safe_user_agent(),
+            current: current_family == Some(model.session_token.as_str()),
+            ip_address: model.ip_address,
+            created_at: model.created_at,
+            last_seen_at: model.updated_at,
+            expires_at: model.expires_at,
+        }
+    }
+}
@@ -19,6 +19,12 @@ pub struct Model {
 
     pub refresh_token: Option<String>,
 
+    /// Refresh token this one replaced on rotation
+    pub parent_id: Option<Uuid>,
+
+    /// First refresh token of the rotation chain
+    pub family_id: Option<Uuid>,
+
     pub token_type: TokenType,
 
     pub expires_at: DateTimeWithTimeZone,
@@ -91,6 +97,11 @@ impl Model {
         self.token_type.is_verification_token()
     }
 
+    /// Rotation chain of a refresh token; rows from before families existed are their own family
+    pub fn family(&self) -> Uuid {
+        self.family_id.unwrap_or(self.id)
+    }
+
     /// Get remaining validity time in seconds
     pub fn remaining_validity_seconds(&self) -> i64 {
         if self.is_expired() || self.is_revoked {
@@ -113,14 +124,58 @@ pub enum ValueFilterBy {
 }
 
 
+/// RFC 7662 section 2.1. `client_id`/`client_secret` authenticate the caller when
+/// HTTP Basic is not used.
 #[derive(Debug, Deserialize)]
 pub struct IntrospectRequest {
     pub token: String,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+/// RFC 7662 section 2.2. Inactive tokens carry nothing but `active: false`.
+#[derive(Debug, Default, Serialize)]
 pub struct IntrospectResponse {
     pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub sub: Option<String>,
-    pub token_uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub perms: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org_roles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org_perms: Option<Vec<String>>,
+}
+
+impl IntrospectResponse {
+    pub fn inactive() -> Self {
+        IntrospectResponse::default()
+    }
 }
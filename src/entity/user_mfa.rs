@@ -0,0 +1,119 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::prelude::async_trait::async_trait;
+use sea_orm::Set;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::helpers::now_date_time_utc;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "user_mfa", schema_name = "auth")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+
+    #[sea_orm(unique)]
+    pub user_id: Uuid,
+
+    /// Base32 shared secret
+    #[serde(skip_serializing)]
+    pub totp_secret: String,
+
+    pub enabled: bool,
+
+    pub enabled_at: Option<DateTimeWithTimeZone>,
+
+    pub last_used_step: Option<i64>,
+
+    /// Hashes of the recovery codes not used yet
+    #[serde(skip_serializing)]
+    #[sea_orm(column_type = "JsonBinary")]
+    pub recovery_codes: Json,
+
+    /// `jti` of the latest login challenge, cleared once it is used
+    #[serde(skip_serializing)]
+    pub challenge_jti: Option<Uuid>,
+
+    /// Codes tried against `challenge_jti`
+    #[serde(skip_serializing)]
+    pub challenge_attempts: i32,
+
+    pub created_at: DateTimeWithTimeZone,
+
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let mut active_model = self;
+        active_model.updated_at = Set(DateTimeWithTimeZone::from(now_date_time_utc()));
+        Ok(active_model)
+    }
+}
+
+impl Model {
+    pub fn recovery_code_hashes(&self) -> Vec<String> {
+        self.recovery_codes
+            .as_array()
+            .map(|codes| {
+                codes
+                    .iter()
+                    .filter_map(|code| code.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollmentResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    /// Shown once, only hashes are stored
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TotpCodeRequestBody {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MfaVerifyRequestBody {
+    pub mfa_token: String,
+    /// Current TOTP code or one of the recovery codes
+    pub code: String,
+}
+
+/// Login answer when the password was right but a second factor is still needed
+#[derive(Debug, Serialize)]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    pub expires_in: i64,
+}
@@ -1,4 +1,5 @@
 use sea_orm::entity::prelude::*;
+use sea_orm::FromQueryResult;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
@@ -11,6 +12,12 @@ pub struct Model {
     pub permission_id: Uuid,
 
     pub allow: bool,
+
+    /// Not in effect before this instant, NULL means immediately
+    pub valid_from: Option<DateTimeWithTimeZone>,
+
+    /// Expires at this instant, NULL means never
+    pub valid_until: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -48,3 +55,20 @@ impl Related<super::permissions::Entity> for Entity {
 
 impl ActiveModelBehavior for ActiveModel {}
 
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct SetOverrideRequestBody {
+    pub allow: bool,
+    pub valid_from: Option<DateTimeWithTimeZone>,
+    pub valid_until: Option<DateTimeWithTimeZone>,
+}
+
+/// `allow: false` denies the permission even when a role grants it
+#[derive(Debug, Serialize, Deserialize, Clone, FromQueryResult)]
+pub struct OverrideResponse {
+    pub permission_id: Uuid,
+    pub code: String,
+    pub allow: bool,
+    pub valid_from: Option<DateTimeWithTimeZone>,
+    pub valid_until: Option<DateTimeWithTimeZone>,
+}
@@ -9,6 +9,12 @@ pub struct Model {
 
     #[sea_orm(primary_key, auto_increment = false)]
     pub role_id: Uuid,
+
+    /// Not in effect before this instant, NULL means immediately
+    pub valid_from: Option<DateTimeWithTimeZone>,
+
+    /// Expires at this instant, NULL means never
+    pub valid_until: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -46,3 +52,19 @@ impl Related<super::roles::Entity> for Entity {
 
 impl ActiveModelBehavior for ActiveModel {}
 
+
+/// Optional validity window of an assignment, given as query parameters
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct GrantValidityQuery {
+    pub valid_from: Option<DateTimeWithTimeZone>,
+    pub valid_until: Option<DateTimeWithTimeZone>,
+}
+
+/// A role assigned to a user and the window it is valid in
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserRoleResponse {
+    #[serde(flatten)]
+    pub role: super::roles::Model,
+    pub valid_from: Option<DateTimeWithTimeZone>,
+    pub valid_until: Option<DateTimeWithTimeZone>,
+}
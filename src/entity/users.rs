@@ -42,6 +42,14 @@ pub struct Model {
 
     #[sea_orm(column_type = "JsonBinary")]
     pub login_history: Json,
+
+    pub failed_login_attempts: i32,
+
+    pub lockout_count: i32,
+
+    pub locked_until: Option<DateTimeWithTimeZone>,
+
+    pub pending_email: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -104,6 +112,12 @@ impl Model {
         self.status.is_active() && !self.status.is_suspended()
     }
 
+    /// Check if too many failed logins locked the account for now
+    pub fn is_locked(&self) -> bool {
+        self.locked_until
+            .is_some_and(|until| until > DateTimeWithTimeZone::from(now_date_time_utc()))
+    }
+
     /// Check if the user has admin privileges
     pub fn is_admin(&self) -> bool {
         self.role.is_admin()
@@ -161,6 +175,104 @@ pub struct RegisterResponseBody {
     pub status: String,
 }
 
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct ForgotPasswordRequestBody {
+    pub email: String,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct ResetPasswordRequestBody {
+    pub token: String,
+    pub password: String,
+}
+
+/// Own account as returned by `GET /users/me`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProfileResponse {
+    pub id: Uuid,
+    pub email: String,
+    pub username: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub role: UserRole,
+    pub status: UserStatus,
+    pub email_verified: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_email: Option<String>,
+    pub last_login: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+}
+impl From<Model> for ProfileResponse {
+    fn from(user: Model) -> Self {
+        ProfileResponse {
+            id: user.id,
+            email: user.email,
+            username: user.username,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            role: user.role,
+            status: user.status,
+            email_verified: user.email_verified,
+            pending_email: user.pending_email,
+            last_login: user.last_login,
+            created_at: user.created_at,
+        }
+    }
+}
+
+/// Fields left out are not changed
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateProfileRequestBody {
+    pub username: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct ChangePasswordRequestBody {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct ChangeEmailRequestBody {
+    pub new_email: String,
+    pub password: String,
+}
+
+/// Account as seen by `/admin/users`, with the lockout state on top of the profile
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AdminUserResponse {
+    #[serde(flatten)]
+    pub profile: ProfileResponse,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<DateTimeWithTimeZone>,
+}
+impl From<Model> for AdminUserResponse {
+    fn from(user: Model) -> Self {
+        AdminUserResponse {
+            failed_login_attempts: user.failed_login_attempts,
+            locked_until: user.locked_until,
+            profile: ProfileResponse::from(user),
+        }
+    }
+}
+
+/// Accounts created by an administrator are active right away, no verification mail is sent
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct AdminCreateUserRequestBody {
+    pub email: String,
+    pub username: String,
+    pub password: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateUserStatusRequestBody {
+    pub status: UserStatus,
+}
+
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct UserSearchBody {
     pub email: Option<String>,
@@ -186,3 +298,28 @@ impl From<Model> for UserSearchResponseBody {
         }
     }
 }
+
+/// Standard OIDC claims returned by the userinfo endpoint.
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct UserInfoResponse {
+    pub sub: String,
+    pub email: String,
+    pub email_verified: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+    pub preferred_username: String,
+}
+impl From<Model> for UserInfoResponse {
+    fn from(user: Model) -> Self {
+        UserInfoResponse {
+            sub: user.id.to_string(),
+            email: user.email,
+            email_verified: user.email_verified,
+            given_name: user.first_name,
+            family_name: user.last_name,
+            preferred_username: user.username,
+        }
+    }
+}
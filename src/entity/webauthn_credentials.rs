@@ -0,0 +1,104 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use webauthn_rs::prelude::{PublicKeyCredential, RegisterPublicKeyCredential};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "webauthn_credentials", schema_name = "auth")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+
+    pub user_id: Uuid,
+
+    /// base64url credential id
+    #[sea_orm(unique)]
+    pub credential_id: String,
+
+    /// COSE public key
+    #[serde(skip_serializing)]
+    #[sea_orm(column_type = "JsonBinary")]
+    pub public_key: Json,
+
+    pub sign_count: i64,
+
+    #[sea_orm(column_type = "JsonBinary")]
+    pub transports: Json,
+
+    /// Serialized `webauthn_rs::prelude::Passkey`
+    #[serde(skip_serializing)]
+    #[sea_orm(column_type = "JsonBinary")]
+    pub passkey: Json,
+
+    pub name: Option<String>,
+
+    pub created_at: DateTimeWithTimeZone,
+
+    pub last_used_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Options for `navigator.credentials.create()` / `.get()` and the id to finish the ceremony with
+#[derive(Debug, Serialize)]
+pub struct WebauthnChallengeResponse<T: Serialize> {
+    pub ceremony_id: Uuid,
+    pub options: T,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebauthnRegisterFinishRequestBody {
+    pub ceremony_id: Uuid,
+    /// Label shown in the credential list, e.g. "MacBook Touch ID"
+    pub name: Option<String>,
+    pub credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebauthnLoginStartRequestBody {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebauthnLoginFinishRequestBody {
+    pub ceremony_id: Uuid,
+    pub credential: PublicKeyCredential,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasskeyResponse {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub transports: Json,
+    pub created_at: DateTimeWithTimeZone,
+    pub last_used_at: Option<DateTimeWithTimeZone>,
+}
+
+impl From<Model> for PasskeyResponse {
+    fn from(model: Model) -> Self {
+        PasskeyResponse {
+            id: model.id,
+            name: model.name,
+            transports: model.transports,
+            created_at: model.created_at,
+            last_used_at: model.last_used_at,
+        }
+    }
+}
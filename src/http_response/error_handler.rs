@@ -6,7 +6,7 @@ use actix_web::{
     HttpRequest,
     HttpResponse,
     ResponseError,
-    http::StatusCode,
+    http::{header, StatusCode},
 };
 use futures_util::future::{LocalBoxFuture, ready};
 use sea_orm::DbErr;
@@ -43,6 +43,12 @@ use crate::http_response::{create_response, HttpCodeW};
 pub struct CustomError {
     pub error_status_code: HttpCodeW,
     pub error_message: String,
+    /// Seconds sent back in a `Retry-After` header
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
+    /// Structured information returned next to the message, e.g. validation failures
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub details: Option<serde_json::Value>,
 }
 
 impl CustomError {
@@ -50,8 +56,20 @@ impl CustomError {
         CustomError {
             error_status_code,
             error_message,
+            retry_after: None,
+            details: None,
         }
     }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> CustomError {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn with_retry_after(mut self, seconds: u64) -> CustomError {
+        self.retry_after = Some(seconds);
+        self
+    }
 }
 
 impl fmt::Display for CustomError {
@@ -124,7 +142,18 @@ impl ResponseError for CustomError {
         let status_code = StatusCode::from_u16(self.error_status_code as u16)
             .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
 
-        HttpResponse::build(status_code).json(response_object)
+        let mut builder = HttpResponse::build(status_code);
+        if let Some(seconds) = self.retry_after {
+            builder.insert_header((header::RETRY_AFTER, seconds.to_string()));
+        }
+        match &self.details {
+            Some(details) => builder.json(serde_json::json!({
+                "message": response_object.message,
+                "code": response_object.code,
+                "details": details,
+            })),
+            None => builder.json(response_object),
+        }
     }
 }
 
@@ -14,6 +14,8 @@ pub enum HttpCodeW {
     NotFound = 404,
     Conflict = 409,
     UnprocessableEntity = 422,
+    Locked = 423,
+    TooManyRequests = 429,
 
     // Server Errors
     InternalServerError = 500,
@@ -0,0 +1,3 @@
+//! Parts of the auth service that downstream services can depend on.
+
+pub mod permission_matching;
@@ -1,15 +1,23 @@
+use crate::components::audit::AuditService;
 use crate::components::auth::AuthService;
+use crate::components::oauth::OAuthService;
+use crate::components::organizations::OrganizationsService;
+use crate::components::rate_limit::{rate_limit, RateLimiter, SWEEP_INTERVAL};
+use crate::components::rbac::RbacService;
+use crate::components::sessions::SessionsService;
 use crate::components::tokens::TokensService;
 use crate::components::users::UsersService;
+use crate::components::webauthn::WebauthnService;
 use actix_cors::Cors;
 use actix_web::http::header;
-use actix_web::middleware::Logger;
+use actix_web::middleware::{from_fn, Logger};
 use actix_web::{web, App, HttpServer};
 use chrono::Local;
 use dotenv::dotenv;
 use env_logger::{Builder, Env};
 use listenfd::ListenFd;
 use std::env;
+use std::time::Duration;
 use crate::components::config::ConfigService;
 
 mod components;
@@ -24,6 +32,10 @@ fn config_service() -> ConfigService {
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
+    // Fail fast on a misconfigured key ring instead of on the first login
+    once_cell::sync::Lazy::force(&components::auth::functions::KEY_RING);
+    once_cell::sync::Lazy::force(&utils::password_policy::BREACHED_PASSWORDS);
+    once_cell::sync::Lazy::force(&utils::helpers::PASSWORD_HASHING);
     let conn: sea_orm::DatabaseConnection = db::config::init(config_service().database_url)
         .await
         .expect("Failed to initialize database connection"); // Initialize connection here
@@ -51,6 +63,30 @@ async fn main() -> std::io::Result<()> {
         &user_service.clone(),
         &token_service.clone(),
     );
+    let oauth_service = OAuthService::new(
+        &data_base_conn.clone(),
+        &user_service.clone(),
+        &token_service.clone(),
+    );
+
+    let sessions_service = SessionsService::new(&data_base_conn.clone(), &token_service.clone());
+    let webauthn_service = WebauthnService::new(
+        &data_base_conn.clone(),
+        &user_service.clone(),
+        &token_service.clone(),
+        &config_service(),
+    );
+    let rbac_service = RbacService::new(&data_base_conn.clone());
+    let organizations_service = OrganizationsService::new(&data_base_conn.clone());
+    let audit_service = AuditService::new(&data_base_conn.clone());
+    // Expires time-bound role assignments and overrides in the background
+    tokio::spawn(rbac_service.clone().run_grant_sweeper(Duration::from_secs(
+        config_service().grant_sweep_interval_seconds,
+    )));
+
+    // Created once so every worker shares the same buckets
+    let rate_limiter = RateLimiter::new();
+    tokio::spawn(rate_limiter.clone().run_sweeper(SWEEP_INTERVAL));
 
     let mut listened = ListenFd::from_env();
     let mut server = HttpServer::new(move || {
@@ -75,11 +111,32 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(user_service.clone()))
             .app_data(web::Data::new(auth_service.clone()))
             .app_data(web::Data::new(token_service.clone()))
+            .app_data(web::Data::new(oauth_service.clone()))
+            .app_data(web::Data::new(sessions_service.clone()))
+            .app_data(web::Data::new(webauthn_service.clone()))
+            .app_data(web::Data::new(rbac_service.clone()))
+            .app_data(web::Data::new(organizations_service.clone()))
+            .app_data(web::Data::new(audit_service.clone()))
+            .app_data(web::Data::new(rate_limiter.clone()))
             .wrap(Logger::default())
             .service(
                 web::scope("/v1")
+                    .wrap(from_fn(rate_limit))
                     .configure(components::users::init_routes)
-                    .configure(components::auth::init_routes),
+                    .configure(components::auth::init_routes)
+                    .configure(components::sessions::init_routes)
+                    .configure(components::webauthn::init_routes)
+                    .configure(components::rbac::init_routes)
+                    .configure(components::organizations::init_routes)
+                    .configure(components::audit::init_routes),
+            )
+            .service(
+                web::scope("/oauth")
+                    .wrap(from_fn(rate_limit))
+                    .configure(components::oauth::init_routes),
+            )
+            .service(
+                web::scope("/.well-known").configure(components::auth::init_well_known_routes),
             )
     });
 
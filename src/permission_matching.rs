@@ -0,0 +1,70 @@
+//! Matching of `resource.action` permission codes against grants that may be
+//! wildcards: `*` covers every code, `emergency.*` every code under `emergency.`.
+//! It depends on nothing else in the service, so other services can use it
+//! through the `auth` library to check the `perms` claim the same way.
+
+/// Whether a single grant covers the required code
+pub fn permission_matches(granted: &str, required: &str) -> bool {
+    if granted == "*" || granted == required {
+        return true;
+    }
+    match granted.strip_suffix(".*") {
+        Some(prefix) => required
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('.')),
+        None => false,
+    }
+}
+
+/// Whether any of the grants covers the required code
+pub fn has_permission<S: AsRef<str>>(granted: &[S], required: &str) -> bool {
+    granted
+        .iter()
+        .any(|grant| permission_matches(grant.as_ref(), required))
+}
+
+/// `*` or `resource.*`
+pub fn is_wildcard(code: &str) -> bool {
+    code == "*" || code.ends_with(".*")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_covers_every_code() {
+        assert!(permission_matches("*", "emergency.read"));
+        assert!(permission_matches("*", "a"));
+    }
+
+    #[test]
+    fn prefix_wildcard_needs_a_segment_under_the_prefix() {
+        assert!(permission_matches("a.*", "a.b"));
+        assert!(permission_matches("a.*", "a.b.c"));
+        assert!(!permission_matches("a.*", "a"));
+        assert!(!permission_matches("a.*", "ab.c"));
+    }
+
+    #[test]
+    fn exact_grant_covers_only_itself() {
+        assert!(permission_matches("a.read", "a.read"));
+        assert!(!permission_matches("a.read", "a.write"));
+        assert!(!permission_matches("a", "a.read"));
+    }
+
+    #[test]
+    fn any_grant_is_enough() {
+        assert!(has_permission(&["b.read", "a.*"], "a.write"));
+        assert!(!has_permission(&["b.read"], "a.write"));
+        assert!(!has_permission::<&str>(&[], "a.write"));
+    }
+
+    #[test]
+    fn wildcards_are_star_or_a_dot_star_suffix() {
+        assert!(is_wildcard("*"));
+        assert!(is_wildcard("a.*"));
+        assert!(!is_wildcard("a.read"));
+        assert!(!is_wildcard("a*"));
+    }
+}
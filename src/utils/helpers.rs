@@ -1,14 +1,17 @@
+use crate::config_service;
 use crate::http_response::{error_handler::CustomError, HttpCodeW};
+use crate::utils::password_policy::PasswordPolicy;
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
 use argon2::{
     self,
     password_hash::{PasswordHash, PasswordVerifier},
-    Argon2, PasswordHasher,
+    Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHasher, Version,
 };
 use chrono::{DateTime, NaiveDateTime, Utc};
 use chrono_tz::Europe;
 use nanoid::nanoid;
+use once_cell::sync::Lazy;
 use sea_orm::DbErr;
 
 /// Generates a random ID for authentication purposes
@@ -162,49 +165,105 @@ pub fn validate_email(email: &str) -> bool {
     email_regex.is_match(email)
 }
 
-/// Validates password strength
-#[allow(dead_code)]
-pub fn validate_password(password: &str) -> Result<(), CustomError> {
-    if password.len() < 8 {
-        return Err(CustomError::new(
-            HttpCodeW::BadRequest,
-            "Password must be at least 8 characters long".to_string(),
-        ));
+/// Validates a new password against the configured password policy.
+///
+/// Every broken rule is reported in the error `details`, so the client can show them all at once.
+///
+/// # Arguments
+///
+/// * `password`: The candidate password
+/// * `email`: Email of the account, its local part may not appear in the password
+/// * `username`: Username of the account, if any
+///
+/// returns: Result<(), CustomError> - 422 Unprocessable Entity listing the violations
+///
+/// # Examples
+///
+/// ```rust
+/// validate_password("Tr0ub4dor&3", "jane@example.com", Some("jane"))?;
+/// ```
+pub fn validate_password(
+    password: &str,
+    email: &str,
+    username: Option<&str>,
+) -> Result<(), CustomError> {
+    let violations = PasswordPolicy::from_config(&config_service()).check(password, email, username);
+    if violations.is_empty() {
+        return Ok(());
     }
 
-    if !password.chars().any(|c| c.is_uppercase()) {
-        return Err(CustomError::new(
-            HttpCodeW::BadRequest,
-            "Password must contain at least one uppercase letter".to_string(),
-        ));
-    }
+    Err(CustomError::new(
+        HttpCodeW::UnprocessableEntity,
+        "Password does not meet the password policy".to_string(),
+    )
+    .with_details(serde_json::json!(violations)))
+}
+
+/// Generates a salt for password hashing
+pub fn generate_salt() -> SaltString {
+    SaltString::generate(&mut OsRng)
+}
 
-    if !password.chars().any(|c| c.is_lowercase()) {
-        return Err(CustomError::new(
-            HttpCodeW::BadRequest,
-            "Password must contain at least one lowercase letter".to_string(),
-        ));
+/// Argon2id settings for new hashes, read once from config.
+/// Forced at startup so invalid parameters fail fast.
+pub static PASSWORD_HASHING: Lazy<PasswordHashing> = Lazy::new(|| {
+    let config = config_service();
+    let mut builder = ParamsBuilder::new();
+    builder
+        .m_cost(config.argon2_memory_kib)
+        .t_cost(config.argon2_iterations)
+        .p_cost(config.argon2_parallelism);
+
+    let pepper = config.password_pepper.map(String::into_bytes);
+    if pepper.is_some() {
+        let keyid = KeyId::new(config.password_pepper_id.as_bytes())
+            .expect("PASSWORD_PEPPER_ID must be at most 8 bytes");
+        builder.keyid(keyid);
     }
 
-    if !password.chars().any(|c| c.is_ascii_digit()) {
-        return Err(CustomError::new(
-            HttpCodeW::BadRequest,
-            "Password must contain at least one digit".to_string(),
-        ));
+    PasswordHashing {
+        params: builder.build().expect("Invalid Argon2 parameters"),
+        pepper,
     }
+});
 
-    Ok(())
+pub struct PasswordHashing {
+    params: Params,
+    pepper: Option<Vec<u8>>,
 }
 
-/// Generates a salt for password hashing
-pub fn generate_salt() -> SaltString {
-    SaltString::generate(&mut OsRng)
+impl PasswordHashing {
+    fn hasher(&self) -> Argon2<'_> {
+        match &self.pepper {
+            Some(pepper) => Argon2::new_with_secret(
+                pepper,
+                Algorithm::Argon2id,
+                Version::V0x13,
+                self.params.clone(),
+            )
+            .expect("Invalid password pepper"),
+            None => Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone()),
+        }
+    }
+
+    /// Hashes without a key id predate the pepper and are verified without it.
+    /// A key id that is not the current one means the pepper was rotated away.
+    fn verifier(&self, hash: &PasswordHash) -> Result<Argon2<'_>, argon2::password_hash::Error> {
+        let keyid = Params::try_from(hash)?.keyid().to_vec();
+        if keyid.is_empty() {
+            return Ok(Argon2::default());
+        }
+        if keyid != self.params.keyid() {
+            return Err(argon2::password_hash::Error::Password);
+        }
+        Ok(self.hasher())
+    }
 }
 
 pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
     let salt = generate_salt();
 
-    let argon2 = Argon2::default();
+    let argon2 = PASSWORD_HASHING.hasher();
 
     // Hash password with generated salt
     let password_hash = argon2.hash_password(password.as_bytes(), &salt)?;
@@ -217,9 +276,9 @@ pub fn verify_password(
     password: &str,
     password_hash: &str,
 ) -> Result<bool, argon2::password_hash::Error> {
-    let argon2 = Argon2::default();
-
     let parsed_has = PasswordHash::new(password_hash)?;
+    let argon2 = PASSWORD_HASHING.verifier(&parsed_has)?;
+
     match argon2.verify_password(password.as_bytes(), &parsed_has) {
         Ok(_) => Ok(true),
         Err(argon2::password_hash::Error::Password) => Ok(false),
@@ -227,3 +286,38 @@ pub fn verify_password(
         Err(e) => Err(e),
     }
 }
+
+/// Checks if a stored hash was made with other settings than the current ones.
+///
+/// Compares the algorithm, version, memory/time/parallelism costs and the pepper key id,
+/// so raising `ARGON2_*` or adding a pepper upgrades users as they log in.
+///
+/// # Arguments
+///
+/// * `password_hash`: The PHC string stored for the user
+///
+/// returns: bool - true when the password should be hashed again after a successful verify
+///
+/// # Examples
+///
+/// ```rust
+/// if verify_password(password, &user.password_hash)? && password_needs_rehash(&user.password_hash) {
+///     user.password_hash = Set(hash_password(password)?);
+/// }
+/// ```
+pub fn password_needs_rehash(password_hash: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(password_hash) else {
+        return true;
+    };
+    let Ok(params) = Params::try_from(&parsed) else {
+        return true;
+    };
+    let current = &PASSWORD_HASHING.params;
+
+    Algorithm::try_from(parsed.algorithm) != Ok(Algorithm::Argon2id)
+        || parsed.version != Some(Version::V0x13.into())
+        || params.m_cost() != current.m_cost()
+        || params.t_cost() != current.t_cost()
+        || params.p_cost() != current.p_cost()
+        || params.keyid() != current.keyid()
+}
@@ -1,2 +1,5 @@
 // Renamed to avoid "module inception" warning
 pub mod helpers;
+pub mod password_policy;
+#[cfg(test)]
+pub mod test_db;
@@ -0,0 +1,178 @@
+use crate::components::config::ConfigService;
+use crate::config_service;
+use data_encoding::HEXUPPER;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::collections::{HashMap, HashSet};
+
+/// Used when PASSWORD_BREACHED_LIST_PATH is not set
+const BUNDLED_BREACHED_LIST: &str = include_str!("breached_passwords.txt");
+
+/// Hex characters of the SHA-1 used to bucket the list, as in the HIBP range API
+const PREFIX_LEN: usize = 5;
+
+/// Personal info shorter than this is too common to reject passwords over
+const MIN_PERSONAL_INFO_LEN: usize = 3;
+
+/// Loaded once, forced at startup so a bad path fails fast
+pub static BREACHED_PASSWORDS: Lazy<BreachedPasswords> = Lazy::new(|| {
+    match config_service().password_breached_list_path {
+        Some(path) => {
+            let contents = std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Failed to read breached password list {path}: {e}"));
+            BreachedPasswords::parse(&contents)
+        }
+        None => BreachedPasswords::parse(BUNDLED_BREACHED_LIST),
+    }
+});
+
+/// Offline breached-password list, bucketed by SHA-1 prefix like the HIBP range API.
+pub struct BreachedPasswords {
+    ranges: HashMap<String, HashSet<String>>,
+}
+
+impl BreachedPasswords {
+    /// One uppercase or lowercase SHA-1 per line, optionally followed by `:count`.
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn parse(contents: &str) -> Self {
+        let mut ranges: HashMap<String, HashSet<String>> = HashMap::new();
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let hash = line.split(':').next().unwrap_or_default().to_uppercase();
+            if hash.len() != 40 {
+                continue;
+            }
+            let (prefix, suffix) = hash.split_at(PREFIX_LEN);
+            ranges
+                .entry(prefix.to_string())
+                .or_default()
+                .insert(suffix.to_string());
+        }
+        Self { ranges }
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        let hash = HEXUPPER.encode(&Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(PREFIX_LEN);
+        self.ranges
+            .get(prefix)
+            .is_some_and(|range| range.contains(suffix))
+    }
+}
+
+/// One failed rule, returned to the client so every problem can be shown at once.
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordViolation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl PasswordViolation {
+    fn new(rule: &'static str, message: String) -> Self {
+        Self { rule, message }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub disallow_personal_info: bool,
+    pub check_breached: bool,
+}
+
+impl PasswordPolicy {
+    pub fn from_config(config: &ConfigService) -> Self {
+        Self {
+            min_length: config.password_min_length,
+            max_length: config.password_max_length,
+            require_uppercase: config.password_require_uppercase,
+            require_lowercase: config.password_require_lowercase,
+            require_digit: config.password_require_digit,
+            require_symbol: config.password_require_symbol,
+            disallow_personal_info: config.password_disallow_personal_info,
+            check_breached: config.password_check_breached,
+        }
+    }
+
+    /// Every rule the password breaks, empty when it is acceptable.
+    pub fn check(
+        &self,
+        password: &str,
+        email: &str,
+        username: Option<&str>,
+    ) -> Vec<PasswordViolation> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+
+        if length < self.min_length {
+            violations.push(PasswordViolation::new(
+                "min_length",
+                format!("Password must be at least {} characters long", self.min_length),
+            ));
+        }
+        if length > self.max_length {
+            violations.push(PasswordViolation::new(
+                "max_length",
+                format!("Password must be at most {} characters long", self.max_length),
+            ));
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            violations.push(PasswordViolation::new(
+                "uppercase",
+                "Password must contain at least one uppercase letter".to_string(),
+            ));
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            violations.push(PasswordViolation::new(
+                "lowercase",
+                "Password must contain at least one lowercase letter".to_string(),
+            ));
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            violations.push(PasswordViolation::new(
+                "digit",
+                "Password must contain at least one digit".to_string(),
+            ));
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            violations.push(PasswordViolation::new(
+                "symbol",
+                "Password must contain at least one symbol".to_string(),
+            ));
+        }
+        if self.disallow_personal_info && contains_personal_info(password, email, username) {
+            violations.push(PasswordViolation::new(
+                "personal_info",
+                "Password must not contain your email or username".to_string(),
+            ));
+        }
+        if self.check_breached && BREACHED_PASSWORDS.contains(password) {
+            violations.push(PasswordViolation::new(
+                "breached",
+                "Password appears in a list of breached passwords".to_string(),
+            ));
+        }
+
+        violations
+    }
+}
+
+fn contains_personal_info(password: &str, email: &str, username: Option<&str>) -> bool {
+    let password = password.to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default();
+
+    [Some(local_part), username]
+        .into_iter()
+        .flatten()
+        .map(str::to_lowercase)
+        .filter(|value| value.chars().count() >= MIN_PERSONAL_INFO_LEN)
+        .any(|value| password.contains(&value))
+}
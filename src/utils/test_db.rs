@@ -0,0 +1,92 @@
+//! Fixtures for tests that need Postgres. They run only when `TEST_DATABASE_URL`
+//! points at a migrated database (see `docker-compose.test.yml`) and pass otherwise.
+//! Tests that issue tokens also call `configure_env`.
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::RsaPrivateKey;
+use sea_orm::{ConnectionTrait, Database, DatabaseConnection, DbBackend, Statement};
+use std::sync::Once;
+use uuid::Uuid;
+
+static CONFIGURE_ENV: Once = Once::new();
+
+/// Fills in the variables `ConfigService::new` requires and a fresh signing key,
+/// for tests that issue tokens. Variables already set are kept.
+pub fn configure_env() {
+    CONFIGURE_ENV.call_once(|| {
+        let key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        let private_pem = key.to_pkcs8_pem(LineEnding::LF).unwrap();
+        let public_pem = key.to_public_key().to_public_key_pem(LineEnding::LF).unwrap();
+        let private_key = STANDARD.encode(private_pem.as_bytes());
+        let public_key = STANDARD.encode(public_pem.as_bytes());
+        let database_url = std::env::var("TEST_DATABASE_URL").unwrap_or_default();
+
+        for (name, value) in [
+            ("DATABASE_URL", database_url.as_str()),
+            ("PROD_DATABASE_URL", database_url.as_str()),
+            ("ACCESS_TOKEN_PRIVATE_KEY", private_key.as_str()),
+            ("ACCESS_TOKEN_PUBLIC_KEY", public_key.as_str()),
+            ("ACCESS_TOKEN_EXPIRED_IN", "15m"),
+            ("ACCESS_TOKEN_MAXAGE", "15"),
+            ("REFRESH_TOKEN_EXPIRED_IN", "60m"),
+            ("REFRESH_TOKEN_MAXAGE", "60"),
+            ("RUST_LOG", "info"),
+            ("SCHEMA_SYNCHRONIZE", "false"),
+            ("HOST", "127.0.0.1"),
+            ("PORT", "4100"),
+            ("APP_ENV", "test"),
+            ("SYNCHRONIZE", "false"),
+            ("AUTO_MIGRATE", "false"),
+            ("EMAIL_ADDRESS", "auth@test.local"),
+            ("EMAIL_PASSWORD", "unused"),
+            ("SMTP_PASSWORD", "unused"),
+            ("SMTP_TRANSPORT", "localhost"),
+            ("PORT_HOST", "https://auth.test.local"),
+            ("PASSWORD_RESET_URL", "https://auth.test.local/reset-password"),
+        ] {
+            if std::env::var(name).is_err() {
+                std::env::set_var(name, value);
+            }
+        }
+    });
+}
+
+pub async fn connect() -> Option<DatabaseConnection> {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        eprintln!("TEST_DATABASE_URL is not set, skipping");
+        return None;
+    };
+    Some(Database::connect(url).await.expect("Failed to connect to the test database"))
+}
+
+/// An active user with base role `role`, its email and username are unique per call
+pub async fn insert_user(db: &DatabaseConnection, role: &str) -> Uuid {
+    let user_id = Uuid::new_v4();
+    db.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        INSERT INTO auth.users (id, email, username, password_hash, role, status, email_verified)
+        VALUES ($1, $2, $3, 'not-a-hash', $4::user_role, 'ACTIVE', true)
+        "#,
+        [
+            user_id.into(),
+            format!("{user_id}@test.local").into(),
+            format!("test-{user_id}").into(),
+            role.into(),
+        ],
+    ))
+    .await
+    .expect("Failed to insert test user");
+    user_id
+}
+
+pub async fn delete_user(db: &DatabaseConnection, user_id: Uuid) {
+    db.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "DELETE FROM auth.users WHERE id = $1",
+        [user_id.into()],
+    ))
+    .await
+    .expect("Failed to delete test user");
+}